@@ -0,0 +1,87 @@
+use std::fs;
+
+use super::editor::{SbyteError, parse_words};
+use super::shell::Shell;
+
+/// Run each line of the script at `script_path` as a command, without a terminal.
+/// Stops at the first command that fails, giving a message saying where.
+/// Gives false if the script quit before reaching the end.
+pub fn run_script(shell: &mut Shell, script_path: &str) -> Result<bool, String> {
+    let script = match fs::read_to_string(script_path) {
+        Ok(script) => script,
+        Err(e) => {
+            return Err(format!("{}: {}", script_path, e));
+        }
+    };
+
+    for (i, line) in script.lines().enumerate() {
+        match run_line(shell, line) {
+            Ok(true) => (),
+            Ok(false) => {
+                return Ok(false);
+            }
+            Err(msg) => {
+                return Err(format!("{}:{}: {}: {}", script_path, i + 1, line.trim(), msg));
+            }
+        }
+    }
+
+    Ok(true)
+}
+
+/// Run a line as it would be written in the rc file, printing any feedback to stderr
+/// to keep stdout for the content.
+/// Gives false if it was a command to quit.
+pub fn run_line(shell: &mut Shell, line: &str) -> Result<bool, String> {
+    if parse_words(line).is_empty() {
+        return Ok(true);
+    }
+
+    let result = shell.run_line(line);
+    // There's no screen to show a search's progress on, so wait for it here
+    shell.finish_search();
+    if let Some(feedback) = shell.fetch_feedback() {
+        eprintln!("{}", feedback);
+    }
+
+    // Commands report some failures only through the error message, even ones that go on to quit
+    match (result, shell.fetch_error()) {
+        (Err(SbyteError::KillSignal), Some(msg)) => Err(msg),
+        (Err(SbyteError::KillSignal), None) => Ok(false),
+        (Err(e), Some(msg)) => Err(format!("{} ({:?})", msg, e)),
+        (Err(e), None) => Err(format!("{:?}", e)),
+        (Ok(_), Some(msg)) => Err(msg),
+        (Ok(_), None) => Ok(true)
+    }
+}
+
+/// Save every open buffer that has a file and unsaved changes, unless editing is read-only
+pub fn save_all(shell: &mut Shell) -> Result<(), String> {
+    let editor = shell.get_editor_mut();
+    if editor.is_read_only() {
+        return Ok(());
+    }
+
+    let active_index = editor.get_active_buffer_index();
+    let mut result = Ok(());
+    for i in 0 .. editor.get_buffer_count() {
+        editor.switch_to_buffer(i).ok();
+        let path = match editor.get_active_file_path() {
+            Some(path) => path.clone(),
+            None => {
+                continue;
+            }
+        };
+        if !editor.is_modified() {
+            continue;
+        }
+
+        if let Err(e) = editor.save() {
+            result = Err(format!("failed to save {}: {:?}", path, e));
+            break;
+        }
+    }
+    editor.switch_to_buffer(active_index).ok();
+
+    result
+}
@@ -0,0 +1,65 @@
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+
+use super::editor::export::{base64_encode, base64_decode, ImportError};
+
+// Where escape sequences go to reach the terminal, even when stdout has been redirected
+#[cfg(not(windows))]
+const TERMINAL_PATH: &str = "/dev/tty";
+#[cfg(windows)]
+const TERMINAL_PATH: &str = "CONOUT$";
+
+/// How bytes are written as text on the system clipboard
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Encoding {
+    Raw,
+    // Two digits per byte, with no spaces
+    Hex,
+    Base64
+}
+
+pub fn encode(bytes: &[u8], encoding: Encoding) -> Vec<u8> {
+    match encoding {
+        Encoding::Raw => bytes.to_vec(),
+        Encoding::Hex => {
+            let mut output = String::new();
+            for byte in bytes.iter() {
+                output += &format!("{:02X}", byte);
+            }
+            output.into_bytes()
+        }
+        Encoding::Base64 => base64_encode(bytes).into_bytes()
+    }
+}
+
+pub fn decode(text: &[u8], encoding: Encoding) -> Result<Vec<u8>, ImportError> {
+    match encoding {
+        Encoding::Raw => Ok(text.to_vec()),
+        Encoding::Hex => {
+            // Whatever copied it may have spaced out the bytes or put a newline at the end
+            let digits: String = String::from_utf8_lossy(text).split_whitespace().collect();
+            let mut output = Vec::new();
+            for pair in digits.as_bytes().chunks(2) {
+                let pair = String::from_utf8_lossy(pair);
+                match u8::from_str_radix(&pair, 16) {
+                    Ok(byte) if pair.len() == 2 => {
+                        output.push(byte);
+                    }
+                    _ => {
+                        return Err(ImportError::InvalidToken(pair.to_string()));
+                    }
+                }
+            }
+            Ok(output)
+        }
+        Encoding::Base64 => base64_decode(&String::from_utf8_lossy(text))
+    }
+}
+
+/// Ask the terminal to put `text` on the system clipboard with an OSC 52 escape sequence.
+/// Terminals that don't support it ignore it.
+pub fn copy_with_osc52(text: &[u8]) -> io::Result<()> {
+    let mut terminal = OpenOptions::new().write(true).open(TERMINAL_PATH)?;
+    write!(terminal, "\x1b]52;c;{}\x07", base64_encode(text))?;
+    terminal.flush()
+}
@@ -1,5 +1,5 @@
 use std::collections::{HashMap, HashSet};
-use std::cmp::max;
+use std::cmp::{min, max};
 use std::error::Error;
 use wrecked::{RectManager, Color, WreckedError};
 
@@ -10,6 +10,9 @@ use std::{time, thread};
 
 use usize as RectId;
 
+// Columns taken by the side pane (data and template inspectors), divider included
+const INSPECTOR_WIDTH: usize = 32;
+
 pub struct FrontEnd {
     rectmanager: RectManager,
 
@@ -21,14 +24,18 @@ pub struct FrontEnd {
 
     rect_display_wrapper: RectId,
     rects_display: (RectId, RectId),
+    rects_diff_display: (RectId, RectId),
     rect_meta: RectId,
     rect_offset: RectId,
     rect_feedback: RectId,
     rect_scrollbar: RectId,
+    rect_inspector: RectId,
 
 
     row_dict: HashMap<usize, (RectId, RectId)>,
     cell_dict: HashMap<usize, HashMap<usize, (RectId, RectId)>>,
+    diff_cell_dict: HashMap<usize, HashMap<usize, (RectId, RectId)>>,
+    active_diff_cursor_cells: HashSet<(RectId, RectId)>,
     input_context: String, // things may be displayed differently based on context
     rerow_flag: bool,
 
@@ -44,7 +51,13 @@ pub struct FrontEnd {
     rendered_viewport_offset: Option<usize>,
     rendered_formatter: Option<FormatterRef>,
     rendered_cursor: Option<(usize, usize)>,
-    rendered_cmd_cursor: Option<usize>
+    rendered_cmd_cursor: Option<usize>,
+    rendered_search_status: Option<String>,
+    show_data_inspector: bool,
+
+    // Whether the diff pane and the side pane are shown
+    rendered_layout: (bool, bool),
+    resized_layout: (bool, bool)
 
 }
 
@@ -54,10 +67,16 @@ impl FrontEnd {
         let rect_display_wrapper = rectmanager.new_rect(wrecked::ROOT).ok().unwrap();
         let id_display_bits = rectmanager.new_rect(rect_display_wrapper).ok().unwrap();
         let id_display_human = rectmanager.new_rect(rect_display_wrapper).ok().unwrap();
+        let id_diff_display_bits = rectmanager.new_rect(rect_display_wrapper).ok().unwrap();
+        let id_diff_display_human = rectmanager.new_rect(rect_display_wrapper).ok().unwrap();
+        rectmanager.disable(id_diff_display_bits).ok().unwrap();
+        rectmanager.disable(id_diff_display_human).ok().unwrap();
         let rect_meta = rectmanager.new_rect(wrecked::ROOT).ok().unwrap();
         let rect_feedback = rectmanager.new_rect(rect_meta).ok().unwrap();
         let rect_offset = rectmanager.new_rect(rect_meta).ok().unwrap();
         let rect_scrollbar = rectmanager.new_rect(rect_display_wrapper).ok().unwrap();
+        let rect_inspector = rectmanager.new_rect(rect_display_wrapper).ok().unwrap();
+        rectmanager.disable(rect_inspector).ok().unwrap();
         let rect_help_window = rectmanager.new_rect(wrecked::ROOT).ok().unwrap();
         rectmanager.detach(rect_help_window).ok().unwrap();
 
@@ -73,10 +92,14 @@ impl FrontEnd {
             rect_feedback,
             rect_offset,
             rect_scrollbar,
+            rect_inspector,
             rect_help_window,
             rects_display: (id_display_bits, id_display_human),
+            rects_diff_display: (id_diff_display_bits, id_diff_display_human),
             row_dict: HashMap::new(),
             cell_dict: HashMap::new(),
+            diff_cell_dict: HashMap::new(),
+            active_diff_cursor_cells: HashSet::new(),
             rendered_viewport_y_offset: 9999,
 
             input_context: "DEFAULT".to_string(),
@@ -88,7 +111,12 @@ impl FrontEnd {
             rendered_viewport_offset: None,
             rendered_formatter: None,
             rendered_cursor: None,
-            rendered_cmd_cursor: None
+            rendered_cmd_cursor: None,
+            rendered_search_status: None,
+            show_data_inspector: false,
+
+            rendered_layout: (false, false),
+            resized_layout: (false, false)
         };
 
 
@@ -110,6 +138,7 @@ impl FrontEnd {
     }
 
     pub fn tick(&mut self, shell: &mut Shell) -> Result<(), Box::<dyn Error>> {
+        self.show_data_inspector = shell.get_option_flag("data_inspector");
         let editor = shell.get_editor_mut();
         if !editor.is_loading() {
             let new_viewport_size = editor.get_viewport_size();
@@ -120,17 +149,21 @@ impl FrontEnd {
             let new_cursor = (editor.get_cursor_offset(), editor.get_cursor_length());
             let changed_cursor = Some(new_cursor) != self.rendered_cursor;
 
+            let diff_mode = editor.get_diff_buffer_index().is_some();
+            let layout = (diff_mode, self.is_side_pane_shown(editor));
+            let changed_layout = layout != self.rendered_layout;
 
-            if changed_viewport_size {
+            if changed_viewport_size || changed_layout {
                 match self.setup_displays(editor) {
                     Ok(_) => {}
                     Err(error) => {
                         Err(SbyteError::SetupFailed(error))?
                     }
                 }
+                self.rendered_layout = layout;
             }
 
-            if changed_viewport_size || changed_viewport_offset {
+            if changed_viewport_size || changed_viewport_offset || changed_layout {
                 match self.remap_active_rows(editor) {
                     Ok(_) => {}
                     Err(error) => {
@@ -159,6 +192,17 @@ impl FrontEnd {
                 }
             }
 
+            // The compared buffer's pane isn't shifted along with the main one, so redraw it all on scroll
+            let mut refreshed_diff_rows = false;
+            let refreshed_rows = !self.rows_to_refresh.is_empty();
+            if diff_mode && (changed_viewport_size || changed_viewport_offset || changed_layout) {
+                let (viewport_width, viewport_height) = editor.get_viewport_size();
+                let first_row = editor.get_viewport_offset() / viewport_width;
+                for y in first_row .. first_row + viewport_height {
+                    self.rows_to_refresh.insert(y);
+                }
+            }
+
             if !self.rows_to_refresh.is_empty() {
                 let tmp_rows_to_refresh: Vec<usize> = self.rows_to_refresh.drain().collect();
                 for y in tmp_rows_to_refresh.iter() {
@@ -168,6 +212,15 @@ impl FrontEnd {
                             Err(SbyteError::RowSetFailed(error))?
                         }
                     }
+                    if diff_mode {
+                        match self.set_diff_row_characters(editor, *y) {
+                            Ok(_) => {}
+                            Err(error) => {
+                                Err(SbyteError::RowSetFailed(error))?
+                            }
+                        }
+                        refreshed_diff_rows = true;
+                    }
                 }
             }
 
@@ -181,6 +234,24 @@ impl FrontEnd {
                 }
             }
 
+            if diff_mode && (changed_cursor || refreshed_diff_rows) {
+                match self.apply_diff_cursor(editor) {
+                    Ok(_) => {}
+                    Err(error) => {
+                        Err(SbyteError::ApplyCursorFailed(error))?
+                    }
+                }
+            }
+
+            if layout.1 && (changed_cursor || refreshed_rows || changed_layout) {
+                match self.display_side_pane(editor) {
+                    Ok(_) => {}
+                    Err(error) => {
+                        Err(SbyteError::DrawFailed(error))?
+                    }
+                }
+            }
+
             if changed_cursor {
                 self.display_user_offset(editor)?;
                 self.rendered_cursor = Some(new_cursor);
@@ -208,6 +279,8 @@ impl FrontEnd {
             if !feedback_or_error && (changed_cursor || self.flag_context_changed) {
                 if (self.input_context == "DEFAULT" || self.input_context == "CMD") {
                     self.clear_feedback()?;
+                    // The command line shares the rect, and a search moves the cursor while it's being typed
+                    self.rendered_buffer = None;
                 }
             }
 
@@ -228,18 +301,32 @@ impl FrontEnd {
     }
 
     pub fn auto_resize(&mut self, shell: &mut Shell) -> bool {
+        self.show_data_inspector = shell.get_option_flag("data_inspector");
         let editor = shell.get_editor_mut();
         let new_formatter = editor.get_active_formatter_ref();
-        if self.rectmanager.auto_resize() || Some(new_formatter) != self.rendered_formatter {
+        let diff_mode = editor.get_diff_buffer_index().is_some();
+        let layout = (diff_mode, self.is_side_pane_shown(editor));
+        if self.rectmanager.auto_resize() || Some(new_formatter) != self.rendered_formatter || layout != self.resized_layout {
             let delay = time::Duration::from_nanos(1_000);
             thread::sleep(delay);
 
             let viewport_height = self.get_viewport_height();
-            let screensize = self.size();
+            let mut screensize = self.size();
+            if layout.1 {
+                screensize.0 = screensize.0.saturating_sub(INSPECTOR_WIDTH);
+            }
+            if diff_mode {
+                // Each buffer gets half of the screen
+                screensize.0 /= 2;
+            }
             let display_ratio = editor.get_display_ratio() as f64;
             let r: f64 = 1f64 / display_ratio;
             let a: f64 = 1f64 - (1f64 / (r + 1f64));
-            let base_width = ((screensize.0 as f64 - 1f64) * a) as usize;
+            let mut base_width = ((screensize.0 as f64 - 1f64) * a) as usize;
+            // A fixed width is used as long as it fits on screen
+            if let Some(fixed_width) = editor.get_fixed_viewport_width() {
+                base_width = min(fixed_width, max(base_width, 1));
+            }
 
             let cursor_offset = editor.get_cursor_real_offset();
             let cursor_length = editor.get_cursor_real_length();
@@ -251,6 +338,7 @@ impl FrontEnd {
             editor.set_cursor_offset(cursor_offset).ok();
             editor.set_cursor_length(cursor_length);
             self.rendered_formatter = Some(new_formatter);
+            self.resized_layout = layout;
             true
         } else {
             false
@@ -441,11 +529,16 @@ impl FrontEnd {
         let (bits_display, human_display) = self.rects_display;
         self.rectmanager.clear_children(bits_display)?;
         self.rectmanager.clear_children(human_display)?;
+
+        let (diff_bits_display, diff_human_display) = self.rects_diff_display;
+        self.rectmanager.clear_children(diff_bits_display)?;
+        self.rectmanager.clear_children(diff_human_display)?;
         self.arrange_displays(editor)?;
 
 
         self.cell_dict.drain();
         self.row_dict.drain();
+        self.diff_cell_dict.drain();
 
         let display_ratio = editor.get_display_ratio() as usize;
         let width_bits;
@@ -527,12 +620,48 @@ impl FrontEnd {
             }
         }
 
+        if editor.get_diff_buffer_index().is_some() {
+            self.setup_diff_display(editor, width_bits)?;
+        }
+
         self.active_cursor_cells.drain();
+        self.active_diff_cursor_cells.drain();
         self.force_rerow();
 
         Ok(())
     }
 
+    fn setup_diff_display(&mut self, editor: &Editor, width_bits: usize) -> Result<(), WreckedError> {
+        let (viewport_width, viewport_height) = editor.get_viewport_size();
+        let display_ratio = editor.get_display_ratio() as usize;
+        let (bits_display, human_display) = self.rects_diff_display;
+
+        for y in 0 .. viewport_height {
+            let bits_row_id = self.rectmanager.new_rect(bits_display)?;
+            self.rectmanager.resize(bits_row_id, (viewport_width * display_ratio) - 1, 1)?;
+            self.rectmanager.set_position(bits_row_id, 0, y as isize)?;
+
+            let human_row_id = self.rectmanager.new_rect(human_display)?;
+            self.rectmanager.resize(human_row_id, viewport_width, 1)?;
+            self.rectmanager.set_position(human_row_id, 0, y as isize)?;
+
+            let cells_hashmap = self.diff_cell_dict.entry(y).or_default();
+            for x in 0 .. viewport_width {
+                let bits_cell_id = self.rectmanager.new_rect(bits_row_id)?;
+                self.rectmanager.resize(bits_cell_id, width_bits, 1)?;
+                self.rectmanager.set_position(bits_cell_id, (x * display_ratio) as isize, 0)?;
+
+                let human_cell_id = self.rectmanager.new_rect(human_row_id)?;
+                self.rectmanager.resize(human_cell_id, 1, 1)?;
+                self.rectmanager.set_position(human_cell_id, x as isize, 0)?;
+
+                cells_hashmap.insert(x, (bits_cell_id, human_cell_id));
+            }
+        }
+
+        Ok(())
+    }
+
     fn arrange_displays(&mut self, editor: &Editor) -> Result<(), WreckedError> {
         let full_width = self.rectmanager.get_width();
         let full_height = self.rectmanager.get_height();
@@ -564,9 +693,27 @@ impl FrontEnd {
         let (vwidth, _vheight) = editor.get_viewport_size();
 
         let (bits_id, human_id) = self.rects_display;
+        let (diff_bits_id, diff_human_id) = self.rects_diff_display;
+        let diff_mode = editor.get_diff_buffer_index().is_some();
+
+        // The side pane takes the right edge. In diff mode, each buffer is laid out in its own half of what's left
+        let mut pane_width = full_width;
+        if self.is_side_pane_shown(editor) {
+            pane_width = pane_width.saturating_sub(INSPECTOR_WIDTH);
+            self.rectmanager.enable(self.rect_inspector)?;
+            self.rectmanager.resize(self.rect_inspector, INSPECTOR_WIDTH, display_height)?;
+            self.rectmanager.set_position(self.rect_inspector, pane_width as isize, 0)?;
+        } else {
+            self.rectmanager.disable(self.rect_inspector)?;
+        }
+
+        if diff_mode {
+            pane_width /= 2;
+        }
+
         let human_display_width = vwidth;
         let bits_display_width = vwidth * display_ratio as usize;
-        let remaining_space = full_width - bits_display_width - human_display_width;
+        let remaining_space = pane_width.saturating_sub(bits_display_width + human_display_width);
 
         let bits_display_x = remaining_space / 2;
         let human_display_x = (remaining_space / 2) + bits_display_width;
@@ -577,6 +724,20 @@ impl FrontEnd {
         self.rectmanager.resize(human_id, human_display_width, display_height)?;
         self.rectmanager.set_position(human_id, human_display_x as isize, 0)?;
 
+        if diff_mode {
+            self.rectmanager.enable(diff_bits_id)?;
+            self.rectmanager.enable(diff_human_id)?;
+
+            self.rectmanager.resize(diff_bits_id, bits_display_width, display_height)?;
+            self.rectmanager.set_position(diff_bits_id, (pane_width + bits_display_x) as isize, 0)?;
+
+            self.rectmanager.resize(diff_human_id, human_display_width, display_height)?;
+            self.rectmanager.set_position(diff_human_id, (pane_width + human_display_x) as isize, 0)?;
+        } else {
+            self.rectmanager.disable(diff_bits_id)?;
+            self.rectmanager.disable(diff_human_id)?;
+        }
+
         self.rectmanager.set_fg_color(self.rect_scrollbar, wrecked::Color::BRIGHTBLACK)?;
         self.rectmanager.resize(self.rect_scrollbar, 1, display_height)?;
         self.rectmanager.set_position(self.rect_scrollbar, (human_display_x + human_display_width) as isize, 0)?;
@@ -594,14 +755,48 @@ impl FrontEnd {
         let offset = width * absolute_y;
 
         let chunk = editor.get_chunk(offset, width);
+        let diff_mask = editor.get_diff_mask(offset, width);
+        let template_mask = editor.get_template_mask(offset, width);
+        let search_mask = editor.get_search_mask(offset, width);
         let relative_y = absolute_y - (editor.get_viewport_offset() / width);
 
         match self.cell_dict.get_mut(&relative_y) {
             Some(cellhash) => {
-                for (_x, (rect_id_bits, rect_id_human)) in cellhash.iter_mut() {
+                for (x, (rect_id_bits, rect_id_human)) in cellhash.iter_mut() {
                     self.rectmanager.clear_characters(*rect_id_human)?;
                     self.rectmanager.clear_children(*rect_id_bits)?;
                     self.rectmanager.clear_characters(*rect_id_bits)?;
+
+                    // Differences take priority, then template fields alternate colors
+                    let color = if diff_mask.get(*x) == Some(&true) {
+                        Some(Color::RED)
+                    } else {
+                        match template_mask.get(*x) {
+                            Some(Some(position)) if position % 2 == 0 => Some(Color::CYAN),
+                            Some(Some(_)) => Some(Color::YELLOW),
+                            _ => None
+                        }
+                    };
+
+                    match color {
+                        Some(color) => {
+                            self.rectmanager.set_fg_color(*rect_id_bits, color)?;
+                            self.rectmanager.set_fg_color(*rect_id_human, color)?;
+                        }
+                        None => {
+                            self.rectmanager.unset_fg_color(*rect_id_bits)?;
+                            self.rectmanager.unset_fg_color(*rect_id_human)?;
+                        }
+                    }
+
+                    // Matches of the search being typed sit on a background of their own, over any of the above
+                    if search_mask.get(*x) == Some(&true) {
+                        self.rectmanager.set_bg_color(*rect_id_bits, Color::BLUE)?;
+                        self.rectmanager.set_bg_color(*rect_id_human, Color::BLUE)?;
+                    } else {
+                        self.rectmanager.unset_bg_color(*rect_id_bits)?;
+                        self.rectmanager.unset_bg_color(*rect_id_human)?;
+                    }
                 }
 
                 let mut tmp_bits_str;
@@ -655,13 +850,178 @@ impl FrontEnd {
         Ok(())
     }
 
+    /// Fill a row of the compared buffer's pane, marking the bytes that differ from the active buffer.
+    fn set_diff_row_characters(&mut self, editor: &Editor, absolute_y: usize) -> Result<(), WreckedError> {
+        let human_formatter = OneToOneFormatter {};
+        let active_formatter = editor.get_active_formatter();
+        let (width, _height) = editor.get_viewport_size();
+        let offset = width * absolute_y;
+
+        let chunk = editor.get_diff_chunk(offset, width).unwrap_or_default();
+        let diff_mask = editor.get_diff_mask(offset, width);
+        let relative_y = absolute_y - (editor.get_viewport_offset() / width);
+
+        if let Some(cellhash) = self.diff_cell_dict.get(&relative_y) {
+            for (x, (bits, human)) in cellhash.iter() {
+                self.rectmanager.clear_characters(*human)?;
+                self.rectmanager.clear_children(*bits)?;
+                self.rectmanager.clear_characters(*bits)?;
+
+                if diff_mask.get(*x) == Some(&true) {
+                    self.rectmanager.set_fg_color(*bits, Color::RED)?;
+                    self.rectmanager.set_fg_color(*human, Color::RED)?;
+                } else {
+                    self.rectmanager.unset_fg_color(*bits)?;
+                    self.rectmanager.unset_fg_color(*human)?;
+                }
+
+                if let Some(byte) = chunk.get(*x) {
+                    let tmp_bits = active_formatter.read_in(*byte);
+                    let tmp_bits_str = std::str::from_utf8(tmp_bits.as_slice()).unwrap_or(".");
+                    for (i, c) in tmp_bits_str.chars().enumerate() {
+                        self.rectmanager.set_character(*bits, i as isize, 0, c)?;
+                    }
+
+                    let (tmp_human, _fmt_response) = human_formatter.read_in(*byte);
+                    let tmp_human_str = std::str::from_utf8(tmp_human.as_slice()).unwrap_or(".");
+                    for (i, c) in tmp_human_str.chars().enumerate() {
+                        self.rectmanager.set_character(*human, i as isize, 0, c)?;
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Mirror the cursor onto the compared buffer's pane
+    fn apply_diff_cursor(&mut self, editor: &Editor) -> Result<(), WreckedError> {
+        for (bits, human) in self.active_diff_cursor_cells.drain() {
+            self.rectmanager.unset_invert_flag(bits).ok();
+            self.rectmanager.unset_invert_flag(human).ok();
+        }
+
+        let (viewport_width, viewport_height) = editor.get_viewport_size();
+        let viewport_offset = editor.get_viewport_offset();
+        let viewport_end = viewport_offset + (viewport_width * viewport_height);
+        let start = max(editor.get_cursor_offset(), viewport_offset);
+        let end = min(editor.get_cursor_offset() + editor.get_cursor_length(), viewport_end);
+
+        for i in start .. end {
+            let y = (i - viewport_offset) / viewport_width;
+            let x = (i - viewport_offset) % viewport_width;
+            if let Some((bits, human)) = self.diff_cell_dict.get(&y).and_then(|cellhash| cellhash.get(&x)) {
+                self.rectmanager.set_invert_flag(*bits)?;
+                self.rectmanager.set_invert_flag(*human)?;
+                self.active_diff_cursor_cells.insert((*bits, *human));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn is_side_pane_shown(&self, editor: &Editor) -> bool {
+        self.show_data_inspector || editor.has_template()
+    }
+
+    /// Draw the data inspector above the template inspector, whichever are active
+    fn display_side_pane(&mut self, editor: &Editor) -> Result<(), WreckedError> {
+        self.rectmanager.clear_characters(self.rect_inspector)?;
+        self.rectmanager.clear_children(self.rect_inspector)?;
+
+        let height = self.rectmanager.get_rect_height(self.rect_inspector);
+        for y in 0 .. height {
+            self.rectmanager.set_character(self.rect_inspector, 0, y as isize, '\u{2502}')?;
+        }
+
+        let mut top = 0;
+        if self.show_data_inspector {
+            top = self.display_data_inspector(editor, height)?;
+        }
+
+        if editor.has_template() && top < height {
+            if top > 0 {
+                for x in 1 .. INSPECTOR_WIDTH {
+                    self.rectmanager.set_character(self.rect_inspector, x as isize, top as isize, '\u{2500}')?;
+                }
+                top += 1;
+            }
+            self.display_template_inspector(editor, top, height - top)?;
+        }
+
+        Ok(())
+    }
+
+    /// List the bytes under the cursor decoded as each type. Gives the number of rows used.
+    fn display_data_inspector(&mut self, editor: &Editor, height: usize) -> Result<usize, WreckedError> {
+        let text_width = INSPECTOR_WIDTH - 2;
+        let values = editor.inspect_at_cursor();
+        let label_width = values.iter().map(|(label, _)| label.len()).max().unwrap_or(0);
+
+        let mut rows = 0;
+        for (label, value) in values.iter().take(height) {
+            let value = match value {
+                Some(value) => value.as_str(),
+                None => "-"
+            };
+
+            let line = format!("{:width$} {}", label, value, width = label_width);
+            let line: String = line.chars().take(text_width).collect();
+            self.rectmanager.set_string(self.rect_inspector, 2, rows as isize, &line)?;
+            rows += 1;
+        }
+
+        Ok(rows)
+    }
+
+    /// List the template's fields around the one under the cursor, in the rows from `top`
+    fn display_template_inspector(&mut self, editor: &Editor, top: usize, height: usize) -> Result<(), WreckedError> {
+        let fields = editor.get_template_fields();
+        let current = editor.get_template_field_index_at(editor.get_cursor_offset());
+
+        // Keep the current field in the middle where possible
+        let first = match current {
+            Some(index) => {
+                min(index.saturating_sub(height / 2), fields.len().saturating_sub(height))
+            }
+            None => 0
+        };
+
+        let text_width = INSPECTOR_WIDTH - 2;
+        for (row, (i, field)) in fields.iter().enumerate().skip(first).take(height).enumerate() {
+            let y = top + row;
+            let name = match field.name.rfind('.') {
+                Some(index) => &field.name[index + 1 ..],
+                None => field.name.as_str()
+            };
+
+            let mut line = format!("{}{}", "  ".repeat(field.depth), name);
+            if let Some(value) = &field.value {
+                line = format!("{}: {}", line, value);
+            }
+            let line: String = line.chars().take(text_width).collect();
+
+            if Some(i) == current {
+                let highlight = self.rectmanager.new_rect(self.rect_inspector)?;
+                self.rectmanager.resize(highlight, text_width, 1)?;
+                self.rectmanager.set_position(highlight, 2, y as isize)?;
+                self.rectmanager.set_string(highlight, 0, 0, &line)?;
+                self.rectmanager.set_invert_flag(highlight)?;
+            } else {
+                self.rectmanager.set_string(self.rect_inspector, 2, y as isize, &line)?;
+            }
+        }
+
+        Ok(())
+    }
+
     pub fn display_user_offset(&mut self, editor: &Editor) -> Result<(), WreckedError> {
         let mut cursor_string = format!("{}", editor.get_cursor_offset());
-        let active_content = editor.get_active_content();
+        let content_length = editor.len();
         let (viewport_width, viewport_height) = editor.get_viewport_size();
 
-        if active_content.len() > 0 {
-            let digit_count = (active_content.len() as f64).log10().ceil() as usize;
+        if content_length > 0 {
+            let digit_count = (content_length as f64).log10().ceil() as usize;
             let l = cursor_string.len();
             if l < digit_count {
                 for _ in 0 .. (digit_count - l) {
@@ -671,15 +1031,25 @@ impl FrontEnd {
 
         }
 
-        let denominator = if active_content.len() == 0 {
+        let denominator = if content_length == 0 {
             0
         } else {
-            active_content.len() - 1
+            content_length - 1
         };
 
         let cursor_len = editor.get_cursor_length();
+        let base_address = editor.get_base_address();
         let offset_display;
-        if cursor_len == 1 {
+        if base_address != 0 {
+            // Firmware images are easier to follow by the addresses they load at
+            let address = base_address + editor.get_cursor_offset();
+            let last_address = base_address + denominator;
+            if cursor_len == 1 {
+                offset_display = format!("Address: 0x{:08X} / 0x{:08X}", address, last_address)
+            } else {
+                offset_display = format!("Address: 0x{:08X} ({}) / 0x{:08X}", address, cursor_len, last_address)
+            }
+        } else if cursor_len == 1 {
             offset_display = format!("Offset: {} / {}", cursor_string, denominator)
         } else {
             offset_display = format!("Offset: {} ({}) / {}", cursor_string, cursor_len, denominator)
@@ -841,7 +1211,9 @@ impl FrontEnd {
     pub fn display_command_line(&mut self, shell: &Shell) -> Result<(), WreckedError> {
         let buffer_option = shell.buffer_get();
         let cursor_x = shell.get_cursor_position();
-        if self.rendered_buffer != buffer_option || self.rendered_cmd_cursor != Some(cursor_x) {
+        // The search being typed keeps going after the line stops changing
+        let search_status = shell.get_search_status();
+        if self.rendered_buffer != buffer_option || self.rendered_cmd_cursor != Some(cursor_x) || self.rendered_search_status != search_status {
             match buffer_option {
                 Some(buffer) => {
                     self.clear_feedback()?;
@@ -857,8 +1229,18 @@ impl FrontEnd {
                     self.rectmanager.set_invert_flag(cursor_id)?;
 
                     self.rectmanager.set_string(self.rect_feedback, 0, 0, &vec![":", &buffer].join(""))?;
+
+                    // Where the search being typed has got to, at the far end so it doesn't move as the line grows
+                    if let Some(status) = &search_status {
+                        let width = self.rectmanager.get_rect_width(self.rect_feedback);
+                        if width > buffer.len() + status.len() + 2 {
+                            self.rectmanager.set_string(self.rect_feedback, (width - status.len()) as isize, 0, &status)?;
+                        }
+                    }
+
                     self.rendered_buffer = Some(buffer);
                     self.rendered_cmd_cursor = Some(cursor_x);
+                    self.rendered_search_status = search_status;
                 }
                 None => { }
             }
@@ -0,0 +1,258 @@
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Write};
+
+use super::editor::formatter::FormatterRef;
+use super::editor::export::{DumpLayout, dump_row, parse_dump_row};
+use super::editor::string_to_integer;
+
+pub const USAGE: &str = "\
+Usage: sbyte dump [OPTIONS] [INFILE [OUTFILE]]
+
+Print INFILE as rows of an offset, its bytes and their text, like xxd.
+With -r, read rows like these back into bytes. INFILE and OUTFILE default to stdin and stdout.
+
+Options:
+  -r                 Read a dump and write out the bytes in it
+  --format FORMAT    Show bytes as hex, dec or bin (default hex)
+  --width N          Show N bytes per row (default 16)
+  --group N          Put a space after every N bytes, or none if 0 (default 2 for hex, otherwise 1)
+  --offset-radix N   Show offsets in base 16, 10 or 8 (default 16)
+  --offset N         Start at offset N of INFILE, or with -r, write rows from offset N onward
+  -h, --help         Print this message and exit";
+
+pub struct Options {
+    pub reverse: bool,
+    format: FormatterRef,
+    width: usize,
+    group: Option<usize>,
+    offset_radix: u32,
+    offset: usize,
+    input_path: Option<String>,
+    output_path: Option<String>,
+    pub show_help: bool
+}
+
+pub fn parse_args(args: &[String]) -> Result<Options, String> {
+    let mut options = Options {
+        reverse: false,
+        format: FormatterRef::HEX,
+        width: 16,
+        group: None,
+        offset_radix: 16,
+        offset: 0,
+        input_path: None,
+        output_path: None,
+        show_help: false
+    };
+
+    let mut paths = vec![];
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--format" | "--width" | "--group" | "--offset-radix" | "--offset" => {
+                let value = match args.next() {
+                    Some(value) => value,
+                    None => {
+                        return Err(format!("{} needs a value", arg));
+                    }
+                };
+
+                match arg.as_str() {
+                    "--format" => {
+                        options.format = match value.to_lowercase().as_str() {
+                            "hex" => FormatterRef::HEX,
+                            "dec" => FormatterRef::DEC,
+                            "bin" => FormatterRef::BIN,
+                            _ => {
+                                return Err(format!("unknown format: {} (expected hex, dec or bin)", value));
+                            }
+                        };
+                    }
+                    "--width" => {
+                        match parse_number(arg, value)? {
+                            0 => {
+                                return Err("--width must be at least 1".to_string());
+                            }
+                            width => {
+                                options.width = width;
+                            }
+                        }
+                    }
+                    "--group" => {
+                        options.group = Some(parse_number(arg, value)?);
+                    }
+                    "--offset-radix" => {
+                        match parse_number(arg, value)? {
+                            radix @ (8 | 10 | 16) => {
+                                options.offset_radix = radix as u32;
+                            }
+                            _ => {
+                                return Err("--offset-radix must be 16, 10 or 8".to_string());
+                            }
+                        }
+                    }
+                    _ => {
+                        options.offset = parse_number(arg, value)?;
+                    }
+                }
+            }
+            "-r" => {
+                options.reverse = true;
+            }
+            "-h" | "--help" => {
+                options.show_help = true;
+            }
+            _ => {
+                if arg.starts_with('-') && arg.len() > 1 {
+                    return Err(format!("unknown option: {}", arg));
+                }
+                paths.push(arg.to_string());
+            }
+        }
+    }
+
+    if paths.len() > 2 {
+        return Err("too many files".to_string());
+    }
+    let mut paths = paths.into_iter().filter(|path| path != "-");
+    options.input_path = paths.next();
+    options.output_path = paths.next();
+
+    Ok(options)
+}
+
+fn parse_number(option: &str, value: &str) -> Result<usize, String> {
+    match string_to_integer(value) {
+        Ok(number) => Ok(number),
+        Err(_) => Err(format!("{} needs a number, got {}", option, value))
+    }
+}
+
+/// Dump, or with -r, undump, between the files given in `options`
+pub fn run(options: &Options) -> Result<(), String> {
+    let input: Box<dyn Read> = match &options.input_path {
+        Some(path) => match File::open(path) {
+            Ok(file) => Box::new(file),
+            Err(e) => {
+                return Err(format!("{}: {}", path, e));
+            }
+        },
+        None => Box::new(io::stdin())
+    };
+    let output: Box<dyn Write> = match &options.output_path {
+        Some(path) => match File::create(path) {
+            Ok(file) => Box::new(file),
+            Err(e) => {
+                return Err(format!("{}: {}", path, e));
+            }
+        },
+        None => Box::new(io::stdout())
+    };
+
+    let mut reader = BufReader::new(input);
+    let mut writer = BufWriter::new(output);
+    let result = if options.reverse {
+        undump(&mut reader, &mut writer, options)
+    } else {
+        dump(&mut reader, &mut writer, options).map_err(|e| e.to_string())
+    };
+    result?;
+
+    match writer.flush() {
+        Ok(_) => Ok(()),
+        Err(e) => Err(e.to_string())
+    }
+}
+
+// The layout given by the options, where a group of 0 puts the whole row in one
+fn get_layout(options: &Options) -> DumpLayout {
+    let group = match (options.group, options.format) {
+        (Some(0), _) => options.width,
+        (Some(group), _) => group,
+        (None, FormatterRef::HEX) => 2,
+        (None, _) => 1
+    };
+
+    DumpLayout {
+        format: options.format,
+        width: options.width,
+        group,
+        offset_radix: options.offset_radix
+    }
+}
+
+pub fn dump<R: Read, W: Write>(reader: &mut R, writer: &mut W, options: &Options) -> io::Result<()> {
+    let layout = get_layout(options);
+
+    io::copy(&mut reader.by_ref().take(options.offset as u64), &mut io::sink())?;
+
+    let mut offset = options.offset;
+    let mut row = vec![0; options.width];
+    loop {
+        let length = read_row(reader, &mut row)?;
+        if length == 0 {
+            break;
+        }
+
+        writeln!(writer, "{}", dump_row(&row[0 .. length], offset, &layout))?;
+        offset += length;
+    }
+
+    Ok(())
+}
+
+// Fill `row` as far as the input goes, since a pipe may give less than asked for at a time
+fn read_row<R: Read>(reader: &mut R, row: &mut [u8]) -> io::Result<usize> {
+    let mut length = 0;
+    while length < row.len() {
+        match reader.read(&mut row[length ..])? {
+            0 => {
+                break;
+            }
+            n => {
+                length += n;
+            }
+        }
+    }
+
+    Ok(length)
+}
+
+/// Write out the bytes of each row of a dump at its offset, filling any gap before it with zeroes
+pub fn undump<R: BufRead, W: Write>(reader: &mut R, writer: &mut W, options: &Options) -> Result<(), String> {
+    let layout = get_layout(options);
+
+    let mut position = options.offset;
+    for (i, line) in reader.lines().enumerate() {
+        let line_number = i + 1;
+        let line = match line {
+            Ok(line) => line,
+            Err(e) => {
+                return Err(e.to_string());
+            }
+        };
+
+        let (offset, bytes) = match parse_dump_row(&line, &layout) {
+            Ok(Some(row)) => row,
+            Ok(None) => {
+                continue;
+            }
+            Err(msg) => {
+                return Err(format!("line {}: {}", line_number, msg));
+            }
+        };
+        if offset < position {
+            return Err(format!("line {}: offset {} goes back over what's already written", line_number, offset));
+        }
+
+        // Streamed, since a far off offset can leave a gap bigger than would fit in memory
+        let gap = (offset - position) as u64;
+        let written = io::copy(&mut io::repeat(0).take(gap), writer).and_then(|_| writer.write_all(&bytes));
+        if let Err(e) = written {
+            return Err(e.to_string());
+        }
+        position = offset + bytes.len();
+    }
+
+    Ok(())
+}
@@ -0,0 +1,82 @@
+use std::cell::OnceCell;
+use std::mem;
+
+use super::Editor;
+use super::undo_tree::UndoTree;
+use super::content::Content;
+use super::cursor::Cursor;
+use super::formatter::FormatterRef;
+use super::template::{Template, TemplateOverlay};
+use super::save::FileStamp;
+use super::records::FileFormat;
+
+/// The state of a file that is open but not currently being edited.
+pub struct Buffer {
+    pub(super) content: Content,
+    pub(super) file_path: Option<String>,
+    pub(super) file_stamp: Option<FileStamp>,
+    pub(super) file_format: FileFormat,
+    pub(super) base_address: usize,
+    pub(super) start_address: Option<u32>,
+    pub(super) locked_ranges: Vec<(usize, usize)>,
+    pub(super) cursor: Cursor,
+    pub(super) subcursor: Cursor,
+    pub(super) formatter: FormatterRef,
+    pub(super) display_ratio: u8,
+    pub(super) undo_tree: UndoTree,
+    pub(super) template: Option<(Template, usize)>,
+    pub(super) template_overlay: OnceCell<TemplateOverlay>,
+    pub(super) viewport_offset: usize
+}
+
+impl Buffer {
+    pub(super) fn new() -> Buffer {
+        Buffer {
+            content: Content::new(),
+            file_path: None,
+            file_stamp: None,
+            file_format: FileFormat::Raw,
+            base_address: 0,
+            start_address: None,
+            locked_ranges: Vec::new(),
+            cursor: Cursor::new(),
+            subcursor: Cursor::new(),
+            formatter: FormatterRef::HEX,
+            display_ratio: 3,
+            undo_tree: UndoTree::new(),
+            template: None,
+            template_overlay: OnceCell::new(),
+            viewport_offset: 0
+        }
+    }
+
+    pub fn get_file_path(&self) -> Option<&String> {
+        self.file_path.as_ref()
+    }
+
+    pub fn is_modified(&self) -> bool {
+        !self.undo_tree.is_saved()
+    }
+
+    /// Exchange this buffer's state with the one the editor is currently working on.
+    pub(super) fn swap(&mut self, editor: &mut Editor) {
+        mem::swap(&mut self.content, &mut editor.active_content);
+        mem::swap(&mut self.file_path, &mut editor.active_file_path);
+        mem::swap(&mut self.file_stamp, &mut editor.file_stamp);
+        mem::swap(&mut self.file_format, &mut editor.file_format);
+        mem::swap(&mut self.base_address, &mut editor.base_address);
+        mem::swap(&mut self.start_address, &mut editor.start_address);
+        mem::swap(&mut self.locked_ranges, &mut editor.locked_ranges);
+        mem::swap(&mut self.cursor, &mut editor.cursor);
+        mem::swap(&mut self.subcursor, &mut editor.subcursor);
+        mem::swap(&mut self.formatter, &mut editor.active_formatter);
+        mem::swap(&mut self.display_ratio, &mut editor._active_display_ratio);
+        mem::swap(&mut self.undo_tree, &mut editor.undo_tree);
+        mem::swap(&mut self.template, &mut editor.active_template);
+        mem::swap(&mut self.template_overlay, &mut editor.template_overlay);
+
+        let viewport_offset = editor.viewport.get_offset();
+        editor.viewport.set_offset(self.viewport_offset);
+        self.viewport_offset = viewport_offset;
+    }
+}
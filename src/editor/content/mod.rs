@@ -1,5 +1,8 @@
-use std::cmp::min;
-use regex::bytes::Regex;
+use std::cmp::{min, max};
+use std::cell::OnceCell;
+use std::io::Write;
+use std::sync::Arc;
+use regex::bytes::{Captures, Regex};
 
 #[derive(Debug, PartialEq, Eq)]
 pub enum ContentError {
@@ -17,71 +20,152 @@ pub enum BitMask {
 }
 
 pub mod tests;
+pub mod paged;
+mod piece_tree;
 
+use paged::{PagedFile, PAGE_SIZE};
+use piece_tree::{Piece, PieceSource, PieceTree};
+
+// Bytes searched at once in find_all. Consecutive windows overlap by SEARCH_OVERLAP
+// so that matches straddling a window boundary are still found.
+pub(crate) const SEARCH_WINDOW: usize = 4 * 1024 * 1024;
+const SEARCH_OVERLAP: usize = 64 * 1024;
+
+/// The longest match a search is sure to find in full. Longer ones crossing from one window into the next may be missed.
+pub const MAX_MATCH_LENGTH: usize = SEARCH_OVERLAP;
+
+/// The bytes being edited. The original file is read from disk as needed and
+/// edits are kept in memory as a tree of pieces over the file and an append-only buffer.
 pub struct Content {
-    content_array: Vec<u8>
+    // Shared with snapshots, which read it from other threads
+    original: Option<Arc<PagedFile>>,
+    added: Vec<u8>,
+    pieces: PieceTree,
+    flat_cache: OnceCell<Vec<u8>>
 }
 
 impl Content {
     pub fn new() -> Content {
         Content {
-            content_array: Vec::new()
+            original: None,
+            added: Vec::new(),
+            pieces: PieceTree::new(),
+            flat_cache: OnceCell::new()
+        }
+    }
+
+    pub fn from_file(path: &str) -> std::io::Result<Content> {
+        let original = PagedFile::open(path)?;
+        let length = original.len();
+
+        let mut output = Content::new();
+        output.pieces.insert(0, Piece {
+            source: PieceSource::Original,
+            offset: 0,
+            length
+        });
+        output.original = Some(Arc::new(original));
+
+        Ok(output)
+    }
+
+    /// A copy of the content as it is now, for searching on another thread while this one is edited.
+    /// The file on disk is shared rather than read again.
+    pub fn snapshot(&self) -> Content {
+        Content {
+            original: self.original.clone(),
+            added: self.added.clone(),
+            pieces: self.pieces.clone(),
+            flat_cache: OnceCell::new()
         }
     }
 
     pub fn clear(&mut self) {
-        self.content_array.drain(..);
+        self.original = None;
+        self.added.drain(..);
+        self.pieces.clear();
+        self.flat_cache = OnceCell::new();
     }
 
     pub fn get_byte(&self, offset: usize) -> Option<u8> {
-        if offset < self.content_array.len() {
-            Some(self.content_array[offset])
-        } else {
-            None
-        }
+        self.get_chunk(offset, 1).first().copied()
     }
+
     pub fn set_byte(&mut self, offset: usize, new_byte: u8) -> Result<u8, ContentError> {
-        if offset < self.len() {
-            let old_byte = self.content_array[offset];
-            self.content_array[offset] = new_byte;
-            Ok(old_byte)
-        } else {
-            Err(ContentError::OutOfBounds(offset, self.len()))
+        match self.get_byte(offset) {
+            Some(old_byte) => {
+                let (piece, piece_start) = self.pieces.find(offset).unwrap();
+                if piece.source == PieceSource::Added {
+                    // Each added byte belongs to exactly one piece, so it can be changed in place.
+                    self.added[piece.offset + (offset - piece_start)] = new_byte;
+                } else {
+                    self.pieces.replace(offset, Piece {
+                        source: PieceSource::Added,
+                        offset: self.added.len(),
+                        length: 1
+                    });
+                    self.added.push(new_byte);
+                }
+                self.flat_cache = OnceCell::new();
+
+                Ok(old_byte)
+            }
+            None => {
+                Err(ContentError::OutOfBounds(offset, self.len()))
+            }
         }
     }
 
     pub fn get_chunk(&self, offset: usize, length: usize) -> Vec<u8> {
+        let end = min(self.len(), offset.saturating_add(length));
         let mut output: Vec<u8> = Vec::new();
-        for i in min(offset, self.len()) .. min(self.len(), offset + length) {
-            output.push(self.get_byte(i).unwrap());
+        if offset >= end {
+            return output;
         }
 
+        self.pieces.for_each_in(offset, end - offset, &mut |piece, start, length| {
+            // Bytes the file no longer has still take up their place, so nothing after them shifts.
+            // check_original tells when that's happened, and write_to won't save them.
+            let read_to = output.len() + length;
+            if self.read_piece(piece, start, length, &mut output).is_err() {
+                output.resize(read_to, 0);
+            }
+        });
+
         output
     }
 
+    /// Fail if the file being paged from has changed on disk since it was opened
+    pub fn check_original(&self) -> std::io::Result<()> {
+        match &self.original {
+            Some(original) => original.check(),
+            None => Ok(())
+        }
+    }
+
     pub fn push(&mut self, byte: u8) {
-        self.content_array.push(byte);
+        self.insert_bytes(self.len(), &[byte]).ok();
     }
 
     pub fn increment_byte(&mut self, offset: usize, word_size: usize) -> Result<Vec<u8>, ContentError> {
         let mut current_byte_offset = offset;
         if self.len() > current_byte_offset {
-            let mut current_byte_value = self.content_array[current_byte_offset];
+            let mut current_byte_value = self.get_byte(current_byte_offset).unwrap();
             let mut initial_bytes = vec![];
 
             loop {
                 initial_bytes.insert(0, current_byte_value);
                 if current_byte_value < 255 {
-                    self.content_array[current_byte_offset] = current_byte_value + 1;
+                    self.set_byte(current_byte_offset, current_byte_value + 1)?;
                     break;
                 } else {
-                    self.content_array[current_byte_offset] = 0;
+                    self.set_byte(current_byte_offset, 0)?;
                     if current_byte_offset > offset - (word_size - 1) {
                         current_byte_offset -= 1;
                     } else {
                         break;
                     }
-                    current_byte_value = self.content_array[current_byte_offset];
+                    current_byte_value = self.get_byte(current_byte_offset).unwrap();
                 }
             }
 
@@ -94,23 +178,23 @@ impl Content {
     pub fn decrement_byte(&mut self, offset: usize, word_size: usize) -> Result<Vec<u8>, ContentError> {
         let mut current_byte_offset = offset;
 
-        if self.content_array.len() > current_byte_offset {
-            let mut current_byte_value = self.content_array[current_byte_offset];
+        if self.len() > current_byte_offset {
+            let mut current_byte_value = self.get_byte(current_byte_offset).unwrap();
             let mut initial_bytes = vec![];
 
             loop {
                 initial_bytes.insert(0, current_byte_value);
                 if current_byte_value > 0 {
-                    self.content_array[current_byte_offset] = current_byte_value - 1;
+                    self.set_byte(current_byte_offset, current_byte_value - 1)?;
                     break;
                 } else {
-                    self.content_array[current_byte_offset] = 255;
+                    self.set_byte(current_byte_offset, 255)?;
                     if current_byte_offset > offset - (word_size - 1) {
                         current_byte_offset -= 1;
                     } else {
                         break;
                     }
-                    current_byte_value = self.content_array[current_byte_offset];
+                    current_byte_value = self.get_byte(current_byte_offset).unwrap();
                 }
             }
 
@@ -123,18 +207,13 @@ impl Content {
 
     pub fn apply_mask(&mut self, offset: usize, mask: &[u8], operation: BitMask) -> Result<Vec<u8>, ContentError> {
         let mut new_bytes = Vec::new();
-        for (i, byte) in mask.iter().enumerate() {
-            match self.get_byte(offset + i) {
-                Some(v) => {
-                    match operation {
-                        BitMask::Or => { new_bytes.push(v | *byte); }
-                        BitMask::Xor => { new_bytes.push(v ^ *byte); }
-                        BitMask::Nor => { new_bytes.push(!(v | *byte)); }
-                        BitMask::And => { new_bytes.push(v & *byte); }
-                        BitMask::Nand => { new_bytes.push(!(v & *byte)); }
-                    }
-                }
-                None => { }
+        for (v, byte) in self.get_chunk(offset, mask.len()).iter().zip(mask.iter()) {
+            match operation {
+                BitMask::Or => { new_bytes.push(v | *byte); }
+                BitMask::Xor => { new_bytes.push(v ^ *byte); }
+                BitMask::Nor => { new_bytes.push(!(v | *byte)); }
+                BitMask::And => { new_bytes.push(v & *byte); }
+                BitMask::Nand => { new_bytes.push(!(v & *byte)); }
             }
         }
 
@@ -147,12 +226,19 @@ impl Content {
 
 
     pub fn insert_bytes(&mut self, offset: usize, new_bytes: &[u8]) -> Result<(), ContentError> {
-        if offset <= self.content_array.len() {
-            let mut new_content = self.content_array[0..offset].to_vec();
-            let chunk_last = self.content_array[offset..].to_vec();
-            new_content.extend(new_bytes.iter().copied());
-            new_content.extend(chunk_last.iter().copied());
-            self.content_array = new_content;
+        if offset <= self.len() {
+            if !new_bytes.is_empty() {
+                let added_length = self.added.len();
+                if !self.pieces.try_extend(offset, PieceSource::Added, added_length, new_bytes.len()) {
+                    self.pieces.insert(offset, Piece {
+                        source: PieceSource::Added,
+                        offset: added_length,
+                        length: new_bytes.len()
+                    });
+                }
+                self.added.extend_from_slice(new_bytes);
+                self.flat_cache = OnceCell::new();
+            }
 
             Ok(())
         } else {
@@ -161,15 +247,53 @@ impl Content {
 
     }
 
+    /// Insert `length` copies of `byte` that stand in for bytes that aren't there, without holding them in memory.
+    /// They read like any others, but aren't counted among the data ranges.
+    pub fn insert_fill(&mut self, offset: usize, byte: u8, length: usize) -> Result<(), ContentError> {
+        if offset <= self.len() {
+            self.pieces.insert(offset, Piece {
+                source: PieceSource::Fill(byte),
+                offset: 0,
+                length
+            });
+            self.flat_cache = OnceCell::new();
+
+            Ok(())
+        } else {
+            Err(ContentError::OutOfBounds(offset, self.len()))
+        }
+    }
+
+    /// The offset and length of each run of bytes that isn't fill, in order.
+    /// Anything edited over fill counts as data.
+    pub fn get_data_ranges(&self) -> Vec<(usize, usize)> {
+        let mut output: Vec<(usize, usize)> = Vec::new();
+        let mut position = 0;
+        self.pieces.for_each_in(0, self.len(), &mut |piece, _start, length| {
+            if !matches!(piece.source, PieceSource::Fill(_)) {
+                match output.last_mut() {
+                    Some((offset, range_length)) if *offset + *range_length == position => {
+                        *range_length += length;
+                    }
+                    _ => {
+                        output.push((position, length));
+                    }
+                }
+            }
+            position += length;
+        });
+
+        output
+    }
+
     pub fn remove_bytes(&mut self, offset: usize, length: usize) -> Vec<u8> {
         let output;
-        if offset < self.content_array.len() {
-            let mut removed_bytes = Vec::new();
-            let adj_length = min(self.content_array.len() - offset, length);
-            for _ in 0..adj_length {
-                removed_bytes.push(self.content_array.remove(offset));
-            }
-            output = removed_bytes;
+        if offset < self.len() {
+            let adj_length = min(self.len() - offset, length);
+            output = self.get_chunk(offset, adj_length);
+
+            self.pieces.remove(offset, adj_length);
+            self.flat_cache = OnceCell::new();
         } else {
             output = vec![];
         }
@@ -177,34 +301,148 @@ impl Content {
         output
     }
 
+    /// Get the whole content as one contiguous slice.
+    /// This reads the entire file into memory, so it should be avoided on large files.
     pub fn as_slice(&self) -> &[u8] {
-        self.content_array.as_slice()
+        self.flat_cache.get_or_init(|| {
+            self.get_chunk(0, self.len())
+        }).as_slice()
     }
 
     pub fn len(&self) -> usize {
-        self.content_array.len()
+        self.pieces.len()
+    }
+
+    /// Write the content out without holding all of it in memory at once.
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        self.check_original()?;
+
+        let mut buffer = Vec::new();
+        let mut result = Ok(());
+        self.pieces.for_each_in(0, self.len(), &mut |piece, start, length| {
+            let mut position = start;
+            while result.is_ok() && position < start + length {
+                let read_length = min(PAGE_SIZE, start + length - position);
+                buffer.clear();
+                result = self.read_piece(piece, position, read_length, &mut buffer).and_then(|_| {
+                    writer.write_all(&buffer)
+                });
+                position += read_length;
+            }
+        });
+
+        result
     }
 
     // TODO: Overlapping hits
     // eg when look for 33 in 333, there should be 2 hits.
     pub fn find_all(&self, search_for: &str) -> Result<Vec<(usize, usize)>, regex::Error> {
-        let working_string = format!("(?-u:{})", search_for);
+        self.find_all_in_range(search_for, (0, self.len()))
+    }
+
+    /// Like find_all, but only looking at the bytes from `range.0` up to `range.1`
+    pub fn find_all_in_range(&self, search_for: &str, range: (usize, usize)) -> Result<Vec<(usize, usize)>, regex::Error> {
+        let patt = Content::compile_pattern(search_for)?;
+        let mut output = vec![];
+        self.stream_matches(&patt, range, |hits, _| {
+            output.extend(hits.into_iter().map(|(start, end, _)| (start, end)));
+            true
+        });
+
+        output.sort();
+        Ok(output)
+    }
+
+    /// Like find_all_in_range, but with whatever `on_match` makes of the capture groups of each hit
+    pub fn find_all_captures<T, F>(&self, search_for: &str, range: (usize, usize), on_match: F) -> Result<Vec<(usize, usize, T)>, regex::Error>
+    where F: FnMut(&Captures) -> T {
+        let patt = Content::compile_pattern(search_for)?;
+        let mut output = vec![];
+        self.stream_captures(&patt, range, on_match, |hits, _| {
+            output.extend(hits);
+            true
+        });
+
+        output.sort_by_key(|(start, end, _)| (*start, *end));
+        Ok(output)
+    }
+
+    /// The regex find_all searches with for `search_for`
+    pub fn compile_pattern(search_for: &str) -> Result<Regex, regex::Error> {
+        Regex::new(&format!("(?-u:{})", search_for))
+    }
+
+    /// Search the range a window at a time, handing `on_window` the hits in each, in order, and the offset searched up to.
+    /// Returns false if `on_window` did, which stops the search there.
+    pub fn stream_matches<P>(&self, patt: &Regex, range: (usize, usize), on_window: P) -> bool
+    where P: FnMut(Vec<(usize, usize, ())>, usize) -> bool {
+        self.search_windows(range, |chunk, at| {
+            hits_from(chunk.len(), at, |at| {
+                patt.find_at(chunk, at).map(|hit| (hit.start(), hit.end(), ()))
+            })
+        }, on_window)
+    }
+
+    /// Like stream_matches, but with whatever `on_match` makes of the capture groups of each hit
+    pub fn stream_captures<T, F, P>(&self, patt: &Regex, range: (usize, usize), mut on_match: F, on_window: P) -> bool
+    where F: FnMut(&Captures) -> T,
+          P: FnMut(Vec<(usize, usize, T)>, usize) -> bool {
+        self.search_windows(range, |chunk, at| {
+            hits_from(chunk.len(), at, |at| {
+                patt.captures_at(chunk, at).map(|captures| {
+                    let hit = captures.get(0).unwrap();
+                    (hit.start(), hit.end(), on_match(&captures))
+                })
+            })
+        }, on_window)
+    }
 
-        match Regex::new(&working_string) {
-            Ok(patt) => {
-                let mut output = Vec::new();
-                for hit in patt.find_iter(self.as_slice()) {
-                    output.push((hit.start(), hit.end()))
+    // Run `search` over each window of the range. It's given the window, with the byte before it if the range
+    // goes back further, and where in that the window starts. It gives back the hits from there on at their
+    // offsets in what it was given, which go on to `on_window` at their offsets in the content.
+    fn search_windows<T, F, P>(&self, range: (usize, usize), mut search: F, mut on_window: P) -> bool
+    where F: FnMut(&[u8], usize) -> Vec<(usize, usize, T)>,
+          P: FnMut(Vec<(usize, usize, T)>, usize) -> bool {
+        let range_end = min(range.1, self.len());
+        let mut window_start = range.0;
+        loop {
+            // Seeing the byte before keeps anchors and word boundaries from matching at the start of a window
+            // that isn't the start of the range
+            let context = min(window_start - range.0, 1);
+            let chunk_start = window_start - context;
+            let window_length = min(SEARCH_WINDOW + SEARCH_OVERLAP, range_end.saturating_sub(window_start));
+            let chunk = self.get_chunk(chunk_start, window_length + context);
+            let chunk_end = chunk_start + chunk.len();
+            let is_last_window = chunk_end >= range_end;
+
+            let mut next_start = window_start + SEARCH_WINDOW;
+            let mut hits = vec![];
+            for (start, end, found) in search(&chunk, context) {
+                let (start, end) = (chunk_start + start, chunk_start + end);
+                if !is_last_window {
+                    // Matches starting in the overlap are left to the next window
+                    if start >= window_start + SEARCH_WINDOW {
+                        break;
+                    }
+                    // So are ones running up to the end of this one, which may go on past it or only match there,
+                    // unless they're already as long as a window can hold
+                    if end >= chunk_end && start > window_start {
+                        next_start = start;
+                        break;
+                    }
                 }
+                next_start = max(next_start, end);
+                hits.push((start, end, found));
+            }
 
-                output.sort();
-                Ok(output)
+            if is_last_window {
+                return on_window(hits, range_end);
             }
-            Err(e) => {
-                Err(e)
+            if !on_window(hits, next_start) {
+                return false;
             }
+            window_start = next_start;
         }
-
     }
 
     pub fn replace_digit(&mut self, offset: usize, position: u8, digit_value: u8, radix: u8) -> Result<u8, ContentError> {
@@ -237,5 +475,52 @@ impl Content {
             }
         }
     }
+
+    fn read_piece(&self, piece: &Piece, offset: usize, length: usize, output: &mut Vec<u8>) -> std::io::Result<()> {
+        let start = piece.offset + offset;
+        match piece.source {
+            PieceSource::Original => {
+                if let Some(original) = &self.original {
+                    output.extend(original.read(start, length)?);
+                }
+            }
+            PieceSource::Added => {
+                output.extend_from_slice(&self.added[start .. start + length]);
+            }
+            PieceSource::Fill(byte) => {
+                output.resize(output.len() + length, byte);
+            }
+        }
+
+        Ok(())
+    }
 }
 
+// Every hit `find_at` turns up from `at` on in a haystack `length` bytes long, one after another the way find_iter
+// gives them. Searching from an offset rather than slicing the haystack lets anchors see what comes before it.
+fn hits_from<T, F>(length: usize, at: usize, mut find_at: F) -> Vec<(usize, usize, T)>
+where F: FnMut(usize) -> Option<(usize, usize, T)> {
+    let mut output = vec![];
+    let mut at = at;
+    let mut last_end = None;
+    while at <= length {
+        let (start, end, found) = match find_at(at) {
+            Some(hit) => hit,
+            None => {
+                break;
+            }
+        };
+
+        // An empty match right where the last one ended is passed over
+        if start == end && last_end == Some(end) {
+            at = end + 1;
+            continue;
+        }
+
+        at = end;
+        last_end = Some(end);
+        output.push((start, end, found));
+    }
+
+    output
+}
@@ -0,0 +1,132 @@
+use std::collections::{HashMap, VecDeque};
+use std::fs::File;
+use std::io::{self, ErrorKind, Read, Seek, SeekFrom};
+use std::sync::Mutex;
+
+use super::super::save::FileStamp;
+
+pub const PAGE_SIZE: usize = 64 * 1024;
+const MAX_CACHED_PAGES: usize = 256;
+
+/// Read-only view of a file on disk, loaded one page at a time as it is accessed.
+pub struct PagedFile {
+    file: Mutex<File>,
+    length: usize,
+    // As the file was when opened. Pages read after it changes would mix two versions of it.
+    stamp: FileStamp,
+    cache: Mutex<PageCache>
+}
+
+struct PageCache {
+    pages: HashMap<usize, Vec<u8>>,
+    order: VecDeque<usize>
+}
+
+impl PagedFile {
+    pub fn open(path: &str) -> std::io::Result<PagedFile> {
+        let file = File::open(path)?;
+        let metadata = file.metadata()?;
+        let length = metadata.len() as usize;
+
+        Ok(PagedFile {
+            file: Mutex::new(file),
+            length,
+            stamp: (metadata.modified().ok(), metadata.len()),
+            cache: Mutex::new(PageCache {
+                pages: HashMap::new(),
+                order: VecDeque::new()
+            })
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.length
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.length == 0
+    }
+
+    /// Fail if the file has been truncated or written to since it was opened
+    pub fn check(&self) -> io::Result<()> {
+        let file = lock(&self.file)?;
+        self.check_file(&file)
+    }
+
+    fn check_file(&self, file: &File) -> io::Result<()> {
+        let metadata = file.metadata()?;
+        if (metadata.modified().ok(), metadata.len()) == self.stamp {
+            Ok(())
+        } else {
+            Err(io::Error::other("file changed on disk since it was opened"))
+        }
+    }
+
+    /// Copy the `length` bytes starting at `offset`, or as many as there are before the end.
+    /// Fails rather than give back fewer if the file can't be read, or has changed since it was opened.
+    pub fn read(&self, offset: usize, length: usize) -> io::Result<Vec<u8>> {
+        let end = std::cmp::min(self.length, offset.saturating_add(length));
+        let mut output = Vec::with_capacity(end.saturating_sub(offset));
+
+        let mut position = offset;
+        while position < end {
+            let page_index = position / PAGE_SIZE;
+            let page_offset = position % PAGE_SIZE;
+            let read = self.with_page(page_index, |page| {
+                let page_end = std::cmp::min(page.len(), page_offset + (end - position));
+                if page_offset < page_end {
+                    output.extend_from_slice(&page[page_offset .. page_end]);
+                }
+                page_end.saturating_sub(page_offset)
+            })?;
+
+            if read == 0 {
+                return Err(io::Error::new(ErrorKind::UnexpectedEof, "file is shorter than when it was opened"));
+            }
+            position += read;
+        }
+
+        Ok(output)
+    }
+
+    fn with_page<T, F: FnOnce(&[u8]) -> T>(&self, page_index: usize, callback: F) -> io::Result<T> {
+        let mut cache = lock(&self.cache)?;
+        if !cache.pages.contains_key(&page_index) {
+            let page = self.load_page(page_index)?;
+            if cache.order.len() >= MAX_CACHED_PAGES {
+                if let Some(oldest) = cache.order.pop_front() {
+                    cache.pages.remove(&oldest);
+                }
+            }
+            cache.pages.insert(page_index, page);
+            cache.order.push_back(page_index);
+        }
+
+        Ok(callback(&cache.pages[&page_index]))
+    }
+
+    fn load_page(&self, page_index: usize) -> io::Result<Vec<u8>> {
+        let start = page_index * PAGE_SIZE;
+        if start >= self.length {
+            return Ok(vec![]);
+        }
+
+        let page_length = std::cmp::min(PAGE_SIZE, self.length - start);
+        let mut page = vec![0; page_length];
+
+        let mut file = lock(&self.file)?;
+        // Pages already cached are as the file was, but one read now could be from another version of it
+        self.check_file(&file)?;
+        file.seek(SeekFrom::Start(start as u64))?;
+        file.read_exact(&mut page)?;
+
+        Ok(page)
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> io::Result<std::sync::MutexGuard<'_, T>> {
+    match mutex.lock() {
+        Ok(guard) => Ok(guard),
+        Err(_) => Err(io::Error::other("page cache lock poisoned"))
+    }
+}
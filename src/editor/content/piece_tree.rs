@@ -0,0 +1,261 @@
+use std::cmp::{min, max};
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum PieceSource {
+    Original,
+    Added,
+    // One byte repeated, standing in for bytes that aren't really there, like the gaps between records
+    Fill(u8)
+}
+
+/// A run of bytes taken from either the original file or the buffer of added bytes, or filled in.
+#[derive(Debug, Clone, Copy)]
+pub struct Piece {
+    pub source: PieceSource,
+    pub offset: usize,
+    pub length: usize
+}
+
+impl Piece {
+    fn split(&self, at: usize) -> (Piece, Piece) {
+        (
+            Piece {
+                source: self.source,
+                offset: self.offset,
+                length: at
+            },
+            Piece {
+                source: self.source,
+                offset: self.offset + at,
+                length: self.length - at
+            }
+        )
+    }
+}
+
+#[derive(Clone)]
+struct PieceNode {
+    piece: Piece,
+    priority: u64,
+    subtree_length: usize,
+    left: Option<Box<PieceNode>>,
+    right: Option<Box<PieceNode>>
+}
+
+impl PieceNode {
+    fn new(piece: Piece, priority: u64) -> PieceNode {
+        PieceNode {
+            piece,
+            priority,
+            subtree_length: piece.length,
+            left: None,
+            right: None
+        }
+    }
+
+    fn update(&mut self) {
+        self.subtree_length = self.piece.length
+            + subtree_length(&self.left)
+            + subtree_length(&self.right);
+    }
+}
+
+type Link = Option<Box<PieceNode>>;
+
+fn subtree_length(link: &Link) -> usize {
+    match link {
+        Some(node) => node.subtree_length,
+        None => 0
+    }
+}
+
+/// Pieces kept in a treap ordered by position, so finding, inserting
+/// and removing at an offset take O(log n) in the number of pieces.
+#[derive(Clone)]
+pub struct PieceTree {
+    root: Link,
+    seed: u64
+}
+
+impl PieceTree {
+    pub fn new() -> PieceTree {
+        PieceTree {
+            root: None,
+            seed: 0x9E37_79B9_7F4A_7C15
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        subtree_length(&self.root)
+    }
+
+    pub fn clear(&mut self) {
+        self.root = None;
+    }
+
+    pub fn insert(&mut self, offset: usize, piece: Piece) {
+        if piece.length == 0 {
+            return;
+        }
+
+        let priority = self.next_priority();
+        let (left, right) = PieceTree::split(self.root.take(), offset);
+        let middle = Some(Box::new(PieceNode::new(piece, priority)));
+        self.root = PieceTree::merge(PieceTree::merge(left, middle), right);
+    }
+
+    pub fn remove(&mut self, offset: usize, length: usize) {
+        let (left, rest) = PieceTree::split(self.root.take(), offset);
+        let (_removed, right) = PieceTree::split(rest, length);
+        self.root = PieceTree::merge(left, right);
+    }
+
+    /// Replace whatever covers [offset, offset + piece.length) with `piece`.
+    pub fn replace(&mut self, offset: usize, piece: Piece) {
+        self.remove(offset, piece.length);
+        self.insert(offset, piece);
+    }
+
+    /// Get the piece covering `offset` and the offset at which that piece begins.
+    pub fn find(&self, mut offset: usize) -> Option<(Piece, usize)> {
+        let mut piece_start = 0;
+        let mut link = &self.root;
+        while let Some(node) = link {
+            let left_length = subtree_length(&node.left);
+            if offset < left_length {
+                link = &node.left;
+            } else if offset < left_length + node.piece.length {
+                return Some((node.piece, piece_start + left_length));
+            } else {
+                offset -= left_length + node.piece.length;
+                piece_start += left_length + node.piece.length;
+                link = &node.right;
+            }
+        }
+
+        None
+    }
+
+    /// Lengthen the piece ending exactly at `offset` if it is `source` and ends at `source_end`.
+    /// Lets consecutive typing grow a single piece instead of adding one per keystroke.
+    pub fn try_extend(&mut self, offset: usize, source: PieceSource, source_end: usize, extra: usize) -> bool {
+        PieceTree::extend_node(&mut self.root, offset, source, source_end, extra)
+    }
+
+    /// Call `callback` on each part of a piece overlapping [offset, offset + length), in order.
+    /// The callback receives the piece along with the start and length within it.
+    pub fn for_each_in<F: FnMut(&Piece, usize, usize)>(&self, offset: usize, length: usize, callback: &mut F) {
+        let end = offset.saturating_add(length);
+        PieceTree::visit(&self.root, 0, offset, end, callback);
+    }
+
+    fn visit<F: FnMut(&Piece, usize, usize)>(link: &Link, node_start: usize, offset: usize, end: usize, callback: &mut F) {
+        if let Some(node) = link {
+            if node_start >= end || node_start + node.subtree_length <= offset {
+                return;
+            }
+
+            PieceTree::visit(&node.left, node_start, offset, end, callback);
+
+            let piece_start = node_start + subtree_length(&node.left);
+            let piece_end = piece_start + node.piece.length;
+            if piece_start < end && piece_end > offset {
+                let start = max(offset, piece_start);
+                let stop = min(end, piece_end);
+                callback(&node.piece, start - piece_start, stop - start);
+            }
+
+            PieceTree::visit(&node.right, piece_end, offset, end, callback);
+        }
+    }
+
+    fn extend_node(link: &mut Link, offset: usize, source: PieceSource, source_end: usize, extra: usize) -> bool {
+        match link {
+            Some(node) => {
+                let left_length = subtree_length(&node.left);
+                let piece_end = left_length + node.piece.length;
+                let extended = if offset <= left_length {
+                    PieceTree::extend_node(&mut node.left, offset, source, source_end, extra)
+                } else if offset == piece_end
+                    && node.piece.source == source
+                    && node.piece.offset + node.piece.length == source_end {
+                    node.piece.length += extra;
+                    true
+                } else if offset > piece_end {
+                    PieceTree::extend_node(&mut node.right, offset - piece_end, source, source_end, extra)
+                } else {
+                    false
+                };
+
+                if extended {
+                    node.update();
+                }
+
+                extended
+            }
+            None => {
+                false
+            }
+        }
+    }
+
+    /// Split so that the left tree holds exactly the first `offset` bytes.
+    fn split(link: Link, offset: usize) -> (Link, Link) {
+        match link {
+            Some(mut node) => {
+                let left_length = subtree_length(&node.left);
+                if offset <= left_length {
+                    let (left, right) = PieceTree::split(node.left.take(), offset);
+                    node.left = right;
+                    node.update();
+                    (left, Some(node))
+                } else if offset >= left_length + node.piece.length {
+                    let (left, right) = PieceTree::split(node.right.take(), offset - left_length - node.piece.length);
+                    node.right = left;
+                    node.update();
+                    (Some(node), right)
+                } else {
+                    // The split falls inside this node's piece.
+                    let (head, tail) = node.piece.split(offset - left_length);
+                    let mut tail_node = Box::new(PieceNode::new(tail, node.priority));
+                    tail_node.right = node.right.take();
+                    tail_node.update();
+
+                    node.piece = head;
+                    node.update();
+
+                    (Some(node), Some(tail_node))
+                }
+            }
+            None => {
+                (None, None)
+            }
+        }
+    }
+
+    fn merge(left: Link, right: Link) -> Link {
+        match (left, right) {
+            (None, right) => right,
+            (left, None) => left,
+            (Some(mut left), Some(mut right)) => {
+                if left.priority > right.priority {
+                    left.right = PieceTree::merge(left.right.take(), Some(right));
+                    left.update();
+                    Some(left)
+                } else {
+                    right.left = PieceTree::merge(Some(left), right.left.take());
+                    right.update();
+                    Some(right)
+                }
+            }
+        }
+    }
+
+    fn next_priority(&mut self) -> u64 {
+        // xorshift64
+        self.seed ^= self.seed << 13;
+        self.seed ^= self.seed >> 7;
+        self.seed ^= self.seed << 17;
+        self.seed
+    }
+}
@@ -1,6 +1,6 @@
 #[cfg (test)]
 mod tests {
-    use crate::editor::content::{Content, BitMask, ContentError};
+    use crate::editor::content::{Content, BitMask, ContentError, MAX_MATCH_LENGTH, SEARCH_WINDOW};
 
     #[test]
     fn test_initialize() {
@@ -275,4 +275,165 @@ mod tests {
         assert_eq!(content.replace_digit(10, 0, 2, 3), Err(ContentError::OutOfBounds(10, 1)));
 
     }
+
+    #[test]
+    fn test_from_file() {
+        let mut content = Content::from_file("src/testfiles/00").expect("Couldn't open file");
+        assert_eq!(content.len(), 16);
+        assert_eq!(content.get_chunk(4, 4).as_slice(), "FILE".as_bytes());
+
+        assert!(content.set_byte(0, b'B').is_ok());
+        assert!(content.insert_bytes(8, b"...").is_ok());
+        assert_eq!(content.remove_bytes(12, 4).as_slice(), "ONTE".as_bytes());
+        assert_eq!(content.as_slice(), "BESTFILE...CNTS".as_bytes());
+
+        assert!(Content::from_file("src/testfiles/does_not_exist").is_err());
+    }
+
+    #[test]
+    fn test_file_changed_on_disk() {
+        let path = std::env::temp_dir().join("sbyte_test_file_changed_on_disk");
+        std::fs::write(&path, "TESTFILECONTENTS").ok();
+        let content = Content::from_file(path.to_str().unwrap()).expect("Couldn't open file");
+        assert!(content.check_original().is_ok());
+
+        std::fs::write(&path, "TEST").ok();
+        assert!(content.check_original().is_err());
+        // What can't be read keeps its place rather than shifting what follows
+        assert_eq!(content.get_chunk(0, 16).len(), 16);
+
+        let mut output = Vec::new();
+        assert!(content.write_to(&mut output).is_err());
+
+        std::fs::remove_file(path).ok();
+    }
+
+    #[test]
+    fn test_write_to() {
+        let mut content = Content::from_file("src/testfiles/00").expect("Couldn't open file");
+        content.remove_bytes(0, 4);
+        content.insert_bytes(content.len(), b"!").ok();
+
+        let mut output = Vec::new();
+        assert!(content.write_to(&mut output).is_ok());
+        assert_eq!(output.as_slice(), "FILECONTENTS!".as_bytes());
+    }
+
+    #[test]
+    fn test_find_all_across_windows() {
+        let mut content = Content::new();
+        let mut slice = vec![0; SEARCH_WINDOW * 2];
+        slice[SEARCH_WINDOW - 2 .. SEARCH_WINDOW + 2].copy_from_slice(&[1, 2, 3, 4]);
+        slice[SEARCH_WINDOW * 2 - 1] = 1;
+        content.insert_bytes(0, &slice).ok();
+
+        assert_eq!(
+            content.find_all("\\x01\\x02\\x03\\x04").ok(),
+            Some(vec![(SEARCH_WINDOW - 2, SEARCH_WINDOW + 2)])
+        );
+        assert_eq!(
+            content.find_all("\\x01").ok(),
+            Some(vec![(SEARCH_WINDOW - 2, SEARCH_WINDOW - 1), (SEARCH_WINDOW * 2 - 1, SEARCH_WINDOW * 2)])
+        );
+        // Matches can't run past the end of a range
+        assert_eq!(
+            content.find_all_in_range("\\x01\\x02\\x03\\x04", (1, SEARCH_WINDOW + 1)).ok(),
+            Some(vec![])
+        );
+        assert_eq!(
+            content.find_all_in_range("\\x01", (SEARCH_WINDOW, SEARCH_WINDOW * 2)).ok(),
+            Some(vec![(SEARCH_WINDOW * 2 - 1, SEARCH_WINDOW * 2)])
+        );
+
+        // A match running out of the end of a window is found whole, not cut short there
+        let mut content = Content::new();
+        let mut slice = vec![0; SEARCH_WINDOW * 2];
+        slice[SEARCH_WINDOW - 10 .. SEARCH_WINDOW + MAX_MATCH_LENGTH + 10].fill(5);
+        content.insert_bytes(0, &slice).ok();
+        assert_eq!(
+            content.find_all("\\x05+").ok(),
+            Some(vec![(SEARCH_WINDOW - 10, SEARCH_WINDOW + MAX_MATCH_LENGTH + 10)])
+        );
+
+        // Anchors only match at the ends of the range, not of each window
+        assert_eq!(content.find_all("^\\x00").ok(), Some(vec![(0, 1)]));
+        assert_eq!(content.find_all("\\x00$").ok(), Some(vec![(SEARCH_WINDOW * 2 - 1, SEARCH_WINDOW * 2)]));
+        assert_eq!(
+            content.find_all_in_range("^\\x05", (SEARCH_WINDOW, SEARCH_WINDOW * 2)).ok(),
+            Some(vec![(SEARCH_WINDOW, SEARCH_WINDOW + 1)])
+        );
+    }
+
+    #[test]
+    fn test_stream_matches() {
+        let mut content = Content::new();
+        let mut slice = vec![0; SEARCH_WINDOW * 3];
+        slice[1] = 1;
+        slice[SEARCH_WINDOW * 2 + 1] = 1;
+        content.insert_bytes(0, &slice).ok();
+
+        let patt = Content::compile_pattern("\\x01").unwrap();
+        let mut windows = vec![];
+        let finished = content.stream_matches(&patt, (0, content.len()), |hits, searched_to| {
+            windows.push((hits.len(), searched_to));
+            true
+        });
+        assert!(finished);
+        assert_eq!(windows, vec![(1, SEARCH_WINDOW), (0, SEARCH_WINDOW * 2), (1, SEARCH_WINDOW * 3)]);
+
+        // Stopping after the first window leaves the rest unsearched
+        let mut windows = 0;
+        let finished = content.stream_matches(&patt, (0, content.len()), |_, _| {
+            windows += 1;
+            false
+        });
+        assert!(!finished);
+        assert_eq!(windows, 1);
+    }
+
+    #[test]
+    fn test_many_edits() {
+        // Compare against a plain Vec after a long run of scattered edits
+        let mut content = Content::from_file("src/testfiles/00").expect("Couldn't open file");
+        let mut expected = "TESTFILECONTENTS".as_bytes().to_vec();
+        let mut seed: usize = 7;
+        for i in 0 .. 2000 {
+            seed = (seed * 1103515245 + 12345) % 2147483648;
+            let offset = seed % (expected.len() + 1);
+            match i % 4 {
+                0 | 1 => {
+                    let new_bytes = [i as u8, (i >> 8) as u8];
+                    content.insert_bytes(offset, &new_bytes).ok();
+                    expected.splice(offset .. offset, new_bytes.iter().copied());
+                }
+                2 => {
+                    let length = seed % 5;
+                    let removed = content.remove_bytes(offset, length);
+                    let end = std::cmp::min(expected.len(), offset + length);
+                    let expected_removed: Vec<u8> = expected.drain(std::cmp::min(offset, end) .. end).collect();
+                    assert_eq!(removed, expected_removed);
+                }
+                _ => {
+                    if offset < expected.len() {
+                        content.set_byte(offset, i as u8).ok();
+                        expected[offset] = i as u8;
+                    }
+                }
+            }
+            assert_eq!(content.len(), expected.len());
+        }
+
+        assert_eq!(content.as_slice(), expected.as_slice());
+        assert_eq!(content.get_chunk(3, 50), expected[3 .. 53].to_vec());
+    }
+
+    #[test]
+    fn test_typing_extends_piece() {
+        let mut content = Content::new();
+        for (i, byte) in "typing".as_bytes().iter().enumerate() {
+            content.insert_bytes(i, &[*byte]).ok();
+        }
+        assert_eq!(content.as_slice(), "typing".as_bytes());
+        assert_eq!(content.pieces.find(5).map(|(_piece, start)| start), Some(0));
+    }
 }
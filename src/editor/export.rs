@@ -0,0 +1,441 @@
+// Bytes written out as text other tools and languages take, and read back in
+
+use super::formatter::FormatterRef;
+
+const BASE64_ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+// Values per line of the C and Rust arrays, as xxd -i has them
+const ARRAY_ROW_LENGTH: usize = 12;
+// Characters per line of base64, as base64 wraps them
+const BASE64_ROW_LENGTH: usize = 76;
+// Bytes per line of the dump, and per group within it
+const DUMP_ROW_LENGTH: usize = 16;
+const DUMP_GROUP_LENGTH: usize = 2;
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum ExportFormat {
+    CArray,
+    RustArray,
+    PythonBytes,
+    Base64,
+    Xxd
+}
+
+impl ExportFormat {
+    pub fn from_name(name: &str) -> Option<ExportFormat> {
+        match name.to_lowercase().as_str() {
+            "c" => Some(ExportFormat::CArray),
+            "rust" | "rs" => Some(ExportFormat::RustArray),
+            "python" | "py" => Some(ExportFormat::PythonBytes),
+            "base64" | "b64" => Some(ExportFormat::Base64),
+            "xxd" | "dump" => Some(ExportFormat::Xxd),
+            _ => None
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum ImportError {
+    // A value that isn't a byte, or isn't anything
+    InvalidToken(String),
+    InvalidLine(usize)
+}
+
+/// Write `bytes` out as text in `format`. Dumps count offsets from `offset`.
+pub fn export(format: ExportFormat, bytes: &[u8], offset: usize) -> String {
+    match format {
+        ExportFormat::CArray => {
+            format!("uint8_t data[{}] = {{\n{}}};\n", bytes.len(), array_rows(bytes))
+        }
+        ExportFormat::RustArray => {
+            format!("const DATA: [u8; {}] = [\n{}];\n", bytes.len(), array_rows(bytes))
+        }
+        ExportFormat::PythonBytes => {
+            format!("b'{}'\n", python_escape(bytes))
+        }
+        ExportFormat::Base64 => {
+            let encoded = base64_encode(bytes);
+            let mut output = String::new();
+            for row in encoded.as_bytes().chunks(BASE64_ROW_LENGTH) {
+                output += &String::from_utf8_lossy(row);
+                output.push('\n');
+            }
+            output
+        }
+        ExportFormat::Xxd => {
+            let mut output = String::new();
+            for (i, row) in bytes.chunks(DUMP_ROW_LENGTH).enumerate() {
+                output += &dump_row(row, offset + (i * DUMP_ROW_LENGTH), &DumpLayout::XXD);
+                output.push('\n');
+            }
+            output
+        }
+    }
+}
+
+/// Read back bytes written out as text in `format`
+pub fn import(format: ExportFormat, text: &str) -> Result<Vec<u8>, ImportError> {
+    match format {
+        ExportFormat::CArray | ExportFormat::RustArray => import_array(text),
+        ExportFormat::PythonBytes => import_python(text),
+        ExportFormat::Base64 => base64_decode(text),
+        ExportFormat::Xxd => import_dump(text)
+    }
+}
+
+fn array_rows(bytes: &[u8]) -> String {
+    let mut output = String::new();
+    for row in bytes.chunks(ARRAY_ROW_LENGTH) {
+        let values: Vec<String> = row.iter().map(|byte| format!("0x{:02x}", byte)).collect();
+        output += &format!("    {},\n", values.join(", "));
+    }
+
+    output
+}
+
+// Every value after the `=`, or in the whole text if there isn't one
+fn import_array(text: &str) -> Result<Vec<u8>, ImportError> {
+    // Skip the declaration, whose type and length would otherwise be read as values
+    let values = match text.find('=') {
+        Some(index) => &text[index + 1 ..],
+        None => text
+    };
+
+    let mut output = Vec::new();
+    for line in values.lines() {
+        let line = match line.find("//") {
+            Some(index) => &line[0 .. index],
+            None => line
+        };
+
+        for token in line.split(|c: char| c.is_whitespace() || "{}[],;&".contains(c)) {
+            if token.is_empty() {
+                continue;
+            }
+
+            match parse_array_value(token) {
+                Some(byte) => {
+                    output.push(byte);
+                }
+                None => {
+                    return Err(ImportError::InvalidToken(token.to_string()));
+                }
+            }
+        }
+    }
+
+    Ok(output)
+}
+
+fn parse_array_value(token: &str) -> Option<u8> {
+    let lowered = token.to_lowercase();
+    // Rust allows a type on the end of a literal, and C an unsigned suffix
+    let token = lowered.trim_end_matches("u8").trim_end_matches('u');
+    let (digits, radix) = if let Some(digits) = token.strip_prefix("0x") {
+        (digits, 16)
+    } else if let Some(digits) = token.strip_prefix("0b") {
+        (digits, 2)
+    } else if let Some(digits) = token.strip_prefix("0o") {
+        (digits, 8)
+    } else {
+        (token, 10)
+    };
+
+    u8::from_str_radix(&digits.replace('_', ""), radix).ok()
+}
+
+fn python_escape(bytes: &[u8]) -> String {
+    let mut output = String::new();
+    for byte in bytes.iter() {
+        match byte {
+            b'\\' => output += "\\\\",
+            b'\'' => output += "\\'",
+            b'\n' => output += "\\n",
+            b'\r' => output += "\\r",
+            b'\t' => output += "\\t",
+            0x20 ..= 0x7E => output.push(*byte as char),
+            _ => output += &format!("\\x{:02x}", byte)
+        }
+    }
+
+    output
+}
+
+// The contents of every bytes literal in the text, joined as Python joins adjacent ones
+fn import_python(text: &str) -> Result<Vec<u8>, ImportError> {
+    let mut output = Vec::new();
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != 'b' && c != 'B' {
+            continue;
+        }
+
+        let quote = match chars.peek() {
+            Some('\'') => '\'',
+            Some('"') => '"',
+            _ => {
+                continue;
+            }
+        };
+        chars.next();
+
+        loop {
+            match chars.next() {
+                Some(c) if c == quote => {
+                    break;
+                }
+                Some('\\') => {
+                    let escape = chars.next().unwrap_or('\\');
+                    match escape {
+                        'n' => output.push(b'\n'),
+                        'r' => output.push(b'\r'),
+                        't' => output.push(b'\t'),
+                        'a' => output.push(0x07),
+                        'b' => output.push(0x08),
+                        'f' => output.push(0x0C),
+                        'v' => output.push(0x0B),
+                        'x' => {
+                            let digits: String = chars.by_ref().take(2).collect();
+                            match u8::from_str_radix(&digits, 16) {
+                                Ok(byte) => output.push(byte),
+                                Err(_) => {
+                                    return Err(ImportError::InvalidToken(format!("\\x{}", digits)));
+                                }
+                            }
+                        }
+                        '0' ..= '7' => {
+                            let mut digits = escape.to_string();
+                            while digits.len() < 3 && matches!(chars.peek(), Some('0' ..= '7')) {
+                                digits.push(chars.next().unwrap());
+                            }
+                            match u8::from_str_radix(&digits, 8) {
+                                Ok(byte) => output.push(byte),
+                                Err(_) => {
+                                    return Err(ImportError::InvalidToken(format!("\\{}", digits)));
+                                }
+                            }
+                        }
+                        // Escaped quotes and backslashes, and anything else Python keeps as written
+                        '\\' | '\'' | '"' => output.push(escape as u8),
+                        _ => {
+                            output.push(b'\\');
+                            output.extend(escape.to_string().as_bytes());
+                        }
+                    }
+                }
+                Some(c) if c.is_ascii() => {
+                    output.push(c as u8);
+                }
+                Some(c) => {
+                    return Err(ImportError::InvalidToken(c.to_string()));
+                }
+                None => {
+                    return Err(ImportError::InvalidToken(format!("b{}", quote)));
+                }
+            }
+        }
+    }
+
+    Ok(output)
+}
+
+pub fn base64_encode(bytes: &[u8]) -> String {
+    let mut output = String::new();
+    for chunk in bytes.chunks(3) {
+        let mut value = 0u32;
+        for i in 0 .. 3 {
+            value = (value << 8) + *chunk.get(i).unwrap_or(&0) as u32;
+        }
+
+        for i in 0 .. 4 {
+            if i <= chunk.len() {
+                let index = (value >> (18 - (i * 6))) & 0x3F;
+                output.push(BASE64_ALPHABET[index as usize] as char);
+            } else {
+                output.push('=');
+            }
+        }
+    }
+
+    output
+}
+
+pub fn base64_decode(text: &str) -> Result<Vec<u8>, ImportError> {
+    let mut output = Vec::new();
+    let mut value = 0u32;
+    let mut bits = 0;
+    for c in text.chars() {
+        if c.is_whitespace() || c == '=' {
+            continue;
+        }
+
+        let index = match BASE64_ALPHABET.iter().position(|digit| *digit as char == c) {
+            Some(index) => index,
+            None => {
+                return Err(ImportError::InvalidToken(c.to_string()));
+            }
+        };
+
+        value = (value << 6) | index as u32;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            output.push((value >> bits) as u8);
+            value &= (1 << bits) - 1;
+        }
+    }
+
+    Ok(output)
+}
+
+/// How the rows of a dump are laid out
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct DumpLayout {
+    pub format: FormatterRef,
+    // Bytes per row, and per space separated group within it
+    pub width: usize,
+    pub group: usize,
+    pub offset_radix: u32
+}
+
+impl DumpLayout {
+    /// The layout xxd uses by default
+    pub const XXD: DumpLayout = DumpLayout {
+        format: FormatterRef::HEX,
+        width: DUMP_ROW_LENGTH,
+        group: DUMP_GROUP_LENGTH,
+        offset_radix: 16
+    };
+
+    fn get_digits(&self) -> usize {
+        match self.format {
+            FormatterRef::HEX => 2,
+            FormatterRef::DEC => 3,
+            FormatterRef::BIN => 8
+        }
+    }
+}
+
+fn format_byte(byte: u8, format: FormatterRef) -> String {
+    match format {
+        FormatterRef::HEX => format!("{:02x}", byte),
+        FormatterRef::DEC => format!("{:03}", byte),
+        FormatterRef::BIN => format!("{:08b}", byte)
+    }
+}
+
+fn format_offset(offset: usize, radix: u32) -> String {
+    match radix {
+        8 => format!("{:08o}", offset),
+        10 => format!("{:08}", offset),
+        _ => format!("{:08x}", offset)
+    }
+}
+
+/// A row of a dump: the offset, the bytes and the bytes as text, as xxd writes it with the XXD layout
+pub fn dump_row(row: &[u8], offset: usize, layout: &DumpLayout) -> String {
+    let mut cells = String::new();
+    for (i, byte) in row.iter().enumerate() {
+        if i > 0 && i % layout.group == 0 {
+            cells.push(' ');
+        }
+        cells += &format_byte(*byte, layout.format);
+    }
+
+    // Wide enough for a full row, so the text lines up on short ones
+    let cells_width = (layout.width * layout.get_digits()) + layout.width.div_ceil(layout.group) - 1;
+    let text: String = row.iter().map(|byte| {
+        if (0x20 .. 0x7F).contains(byte) {
+            *byte as char
+        } else {
+            '.'
+        }
+    }).collect();
+
+    format!("{}: {:width$}  {}", format_offset(offset, layout.offset_radix), cells, text, width = cells_width)
+}
+
+/// The offset and bytes of a row of a dump laid out as `layout`, or None for a line with no row on it.
+/// The text column is ignored.
+pub fn parse_dump_row(line: &str, layout: &DumpLayout) -> Result<Option<(usize, Vec<u8>)>, String> {
+    // xxd -a squeezes repeated rows to a *
+    if line.trim().is_empty() || line.trim() == "*" {
+        return Ok(None);
+    }
+
+    let (offset, rest) = match line.split_once(':') {
+        Some((offset, rest)) => (offset.trim(), rest),
+        None => {
+            return Err("no offset".to_string());
+        }
+    };
+    let offset = match usize::from_str_radix(offset, layout.offset_radix) {
+        Ok(offset) => offset,
+        Err(_) => {
+            return Err(format!("invalid offset {}", offset));
+        }
+    };
+
+    // The text column starts after two spaces
+    let cells: String = match rest.trim_start().split_once("  ") {
+        Some((cells, _)) => cells,
+        None => rest
+    }.split_whitespace().collect();
+
+    let radix = match layout.format {
+        FormatterRef::HEX => 16,
+        FormatterRef::DEC => 10,
+        FormatterRef::BIN => 2
+    };
+    let digits = layout.get_digits();
+    let mut bytes = Vec::new();
+    for digit_chunk in cells.as_bytes().chunks(digits) {
+        let digit_chunk = String::from_utf8_lossy(digit_chunk);
+        if digit_chunk.len() < digits {
+            return Err(format!("expected {} digits per byte", digits));
+        }
+
+        match u8::from_str_radix(&digit_chunk, radix) {
+            Ok(byte) => {
+                bytes.push(byte);
+            }
+            Err(_) => {
+                return Err(format!("invalid byte {}", digit_chunk));
+            }
+        }
+    }
+
+    Ok(Some((offset, bytes)))
+}
+
+// Bytes of a dump, placed by the offsets of its rows relative to the first.
+// Anything skipped over is left as zeroes, and rows that go back write over what's there, as xxd -r does.
+fn import_dump(text: &str) -> Result<Vec<u8>, ImportError> {
+    let mut output = Vec::new();
+    let mut first_offset = None;
+
+    for (i, line) in text.lines().enumerate() {
+        let line_number = i + 1;
+        let (offset, bytes) = match parse_dump_row(line, &DumpLayout::XXD) {
+            Ok(Some(row)) => row,
+            Ok(None) => {
+                continue;
+            }
+            Err(_) => {
+                return Err(ImportError::InvalidLine(line_number));
+            }
+        };
+
+        let first_offset = *first_offset.get_or_insert(offset);
+        if offset < first_offset {
+            return Err(ImportError::InvalidLine(line_number));
+        }
+
+        let start = offset - first_offset;
+        if output.len() < start + bytes.len() {
+            output.resize(start + bytes.len(), 0);
+        }
+        output[start .. start + bytes.len()].copy_from_slice(&bytes);
+    }
+
+    Ok(output)
+}
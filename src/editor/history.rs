@@ -0,0 +1,181 @@
+use std::fs::File;
+use std::io::{self, Read, Write, BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use super::UndoTask;
+use super::undo_tree::UndoTree;
+
+const MAGIC: &[u8] = b"SBYTEUNDO3\n";
+// Stands in for a missing current child or transaction
+const NO_NODE: u64 = u64::MAX;
+
+/// Where the undo history of the file at `file_path` is kept: a hidden file beside it.
+pub fn sidecar_path(file_path: &str) -> PathBuf {
+    let path = Path::new(file_path);
+    let name = match path.file_name() {
+        Some(name) => name.to_string_lossy().to_string(),
+        None => file_path.to_string()
+    };
+
+    path.with_file_name(format!(".{}.sbyte-undo", name))
+}
+
+/// Hashes everything written to it (FNV-1a), so content can be hashed without being held in memory
+pub struct ContentHasher {
+    hash: u64,
+    length: usize
+}
+
+impl ContentHasher {
+    pub fn new() -> ContentHasher {
+        ContentHasher {
+            hash: 0xCBF2_9CE4_8422_2325,
+            length: 0
+        }
+    }
+
+    pub fn finish(&self) -> (u64, usize) {
+        (self.hash, self.length)
+    }
+}
+
+impl Write for ContentHasher {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        for byte in buf.iter() {
+            self.hash ^= *byte as u64;
+            self.hash = self.hash.wrapping_mul(0x0100_0000_01B3);
+        }
+        self.length += buf.len();
+
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Save the undo tree of the file at `file_path`, whose content has the given hash and length
+pub fn write_history(file_path: &str, content_key: (u64, usize), undo_tree: &UndoTree) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(sidecar_path(file_path))?);
+    writer.write_all(MAGIC)?;
+    write_bytes(&mut writer, absolute_path(file_path).as_bytes())?;
+    write_number(&mut writer, content_key.0)?;
+    write_number(&mut writer, content_key.1 as u64)?;
+
+    let nodes = undo_tree.get_nodes();
+    write_number(&mut writer, nodes.len() as u64)?;
+    for (parent, current_child, task, transaction) in nodes.iter() {
+        write_number(&mut writer, *parent as u64)?;
+        write_node(&mut writer, *current_child)?;
+        write_task(&mut writer, task)?;
+        write_node(&mut writer, *transaction)?;
+    }
+    write_number(&mut writer, undo_tree.get_current() as u64)?;
+
+    writer.flush()
+}
+
+/// Load the undo tree saved for the file at `file_path`.
+/// Gives None if there is none, or if it was saved for different content.
+pub fn read_history(file_path: &str, content_key: (u64, usize)) -> Option<UndoTree> {
+    let mut reader = BufReader::new(File::open(sidecar_path(file_path)).ok()?);
+
+    let mut magic = vec![0; MAGIC.len()];
+    reader.read_exact(&mut magic).ok()?;
+    if magic != MAGIC
+        || read_bytes(&mut reader)? != absolute_path(file_path).as_bytes()
+        || read_number(&mut reader)? != content_key.0
+        || read_number(&mut reader)? != content_key.1 as u64 {
+        return None;
+    }
+
+    let mut nodes = Vec::new();
+    for _ in 0 .. read_number(&mut reader)? {
+        let parent = read_number(&mut reader)? as usize;
+        let current_child = read_node(&mut reader)?;
+        let task = read_task(&mut reader)?;
+        let transaction = read_node(&mut reader)?;
+
+        nodes.push((parent, current_child, task, transaction));
+    }
+    let current = read_number(&mut reader)? as usize;
+
+    UndoTree::from_nodes(nodes, current)
+}
+
+// Instants can't be stored, so timestamps are kept as wall clock time
+fn write_task<W: Write>(writer: &mut W, task: &UndoTask) -> io::Result<()> {
+    let (offset, bytes_to_remove, bytes_to_insert, timestamp) = task;
+    let age = Instant::now().saturating_duration_since(*timestamp);
+    let millis = match (SystemTime::now() - age).duration_since(UNIX_EPOCH) {
+        Ok(since_epoch) => since_epoch.as_millis() as u64,
+        Err(_) => 0
+    };
+
+    write_number(writer, *offset as u64)?;
+    write_number(writer, *bytes_to_remove as u64)?;
+    write_bytes(writer, bytes_to_insert)?;
+    write_number(writer, millis)
+}
+
+fn read_task<R: Read>(reader: &mut R) -> Option<UndoTask> {
+    let offset = read_number(reader)? as usize;
+    let bytes_to_remove = read_number(reader)? as usize;
+    let bytes_to_insert = read_bytes(reader)?;
+
+    let saved_at = UNIX_EPOCH + Duration::from_millis(read_number(reader)?);
+    let age = SystemTime::now().duration_since(saved_at).unwrap_or_default();
+    let now = Instant::now();
+    let timestamp = now.checked_sub(age).unwrap_or(now);
+
+    Some((offset, bytes_to_remove, bytes_to_insert, timestamp))
+}
+
+fn write_node<W: Write>(writer: &mut W, node: Option<usize>) -> io::Result<()> {
+    match node {
+        Some(node) => write_number(writer, node as u64),
+        None => write_number(writer, NO_NODE)
+    }
+}
+
+fn read_node<R: Read>(reader: &mut R) -> Option<Option<usize>> {
+    match read_number(reader)? {
+        NO_NODE => Some(None),
+        node => Some(Some(node as usize))
+    }
+}
+
+fn absolute_path(file_path: &str) -> String {
+    match std::fs::canonicalize(file_path) {
+        Ok(path) => path.to_string_lossy().to_string(),
+        Err(_) => file_path.to_string()
+    }
+}
+
+fn write_number<W: Write>(writer: &mut W, number: u64) -> io::Result<()> {
+    writer.write_all(&number.to_le_bytes())
+}
+
+fn write_bytes<W: Write>(writer: &mut W, bytes: &[u8]) -> io::Result<()> {
+    write_number(writer, bytes.len() as u64)?;
+    writer.write_all(bytes)
+}
+
+fn read_number<R: Read>(reader: &mut R) -> Option<u64> {
+    let mut bytes = [0; 8];
+    reader.read_exact(&mut bytes).ok()?;
+    Some(u64::from_le_bytes(bytes))
+}
+
+fn read_bytes<R: Read>(reader: &mut R) -> Option<Vec<u8>> {
+    let length = read_number(reader)?;
+    let mut bytes = Vec::new();
+    reader.take(length).read_to_end(&mut bytes).ok()?;
+    if bytes.len() as u64 == length {
+        Some(bytes)
+    } else {
+        None
+    }
+}
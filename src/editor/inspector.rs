@@ -0,0 +1,151 @@
+use std::convert::TryInto;
+
+// Enough bytes for the widest interpretation, a 10 byte LEB128
+pub const INSPECT_LENGTH: usize = 10;
+
+/// Interpret the bytes at the start of `bytes` in each of the common ways.
+/// Gives a label and the value, or None where there aren't enough (or valid) bytes.
+pub fn inspect(bytes: &[u8]) -> Vec<(&'static str, Option<String>)> {
+    vec![
+        ("binary", bytes.first().map(|byte| format!("{:08b}", byte))),
+        ("i8", bytes.first().map(|byte| format!("{}", *byte as i8))),
+        ("u8", bytes.first().map(|byte| format!("{}", byte))),
+        ("i16le", first::<2>(bytes).map(|b| format!("{}", i16::from_le_bytes(b)))),
+        ("i16be", first::<2>(bytes).map(|b| format!("{}", i16::from_be_bytes(b)))),
+        ("u16le", first::<2>(bytes).map(|b| format!("{}", u16::from_le_bytes(b)))),
+        ("u16be", first::<2>(bytes).map(|b| format!("{}", u16::from_be_bytes(b)))),
+        ("i32le", first::<4>(bytes).map(|b| format!("{}", i32::from_le_bytes(b)))),
+        ("i32be", first::<4>(bytes).map(|b| format!("{}", i32::from_be_bytes(b)))),
+        ("u32le", first::<4>(bytes).map(|b| format!("{}", u32::from_le_bytes(b)))),
+        ("u32be", first::<4>(bytes).map(|b| format!("{}", u32::from_be_bytes(b)))),
+        ("i64le", first::<8>(bytes).map(|b| format!("{}", i64::from_le_bytes(b)))),
+        ("i64be", first::<8>(bytes).map(|b| format!("{}", i64::from_be_bytes(b)))),
+        ("u64le", first::<8>(bytes).map(|b| format!("{}", u64::from_le_bytes(b)))),
+        ("u64be", first::<8>(bytes).map(|b| format!("{}", u64::from_be_bytes(b)))),
+        ("f32le", first::<4>(bytes).map(|b| format_float(f32::from_le_bytes(b) as f64))),
+        ("f32be", first::<4>(bytes).map(|b| format_float(f32::from_be_bytes(b) as f64))),
+        ("f64le", first::<8>(bytes).map(|b| format_float(f64::from_le_bytes(b)))),
+        ("f64be", first::<8>(bytes).map(|b| format_float(f64::from_be_bytes(b)))),
+        ("uleb128", read_uleb128(bytes).map(|(value, width)| format!("{} ({}B)", value, width))),
+        ("sleb128", read_sleb128(bytes).map(|(value, width)| format!("{} ({}B)", value, width))),
+        ("utf-8", read_utf8(bytes).map(format_char)),
+        ("utf-16le", read_utf16(bytes, false).map(format_char)),
+        ("utf-16be", read_utf16(bytes, true).map(format_char)),
+        ("unix32le", first::<4>(bytes).map(|b| format_timestamp(u32::from_le_bytes(b) as i64))),
+        ("unix64le", first::<8>(bytes).map(|b| format_timestamp(i64::from_le_bytes(b))))
+    ]
+}
+
+fn first<const N: usize>(bytes: &[u8]) -> Option<[u8; N]> {
+    bytes.get(0 .. N)?.try_into().ok()
+}
+
+fn format_float(value: f64) -> String {
+    let magnitude = value.abs();
+    if value != 0.0 && value.is_finite() && !(1e-4 .. 1e10).contains(&magnitude) {
+        format!("{:e}", value)
+    } else {
+        format!("{}", value)
+    }
+}
+
+/// Read an unsigned LEB128, giving its value and width in bytes
+pub fn read_uleb128(bytes: &[u8]) -> Option<(u64, usize)> {
+    let mut value: u64 = 0;
+    for (i, byte) in bytes.iter().enumerate().take(INSPECT_LENGTH) {
+        let shift = 7 * i as u32;
+        let bits = (*byte & 0x7F) as u64;
+        if shift >= 64 || (bits << shift) >> shift != bits {
+            return None;
+        }
+
+        value |= bits << shift;
+        if *byte & 0x80 == 0 {
+            return Some((value, i + 1));
+        }
+    }
+
+    None
+}
+
+/// Read a signed LEB128, giving its value and width in bytes
+pub fn read_sleb128(bytes: &[u8]) -> Option<(i64, usize)> {
+    let mut value: i64 = 0;
+    for (i, byte) in bytes.iter().enumerate().take(INSPECT_LENGTH) {
+        let shift = 7 * i as u32;
+        if shift >= 64 {
+            return None;
+        }
+
+        value |= ((*byte & 0x7F) as i64) << shift;
+        if *byte & 0x80 == 0 {
+            // Sign-extend from the last group read
+            let used_bits = shift + 7;
+            if used_bits < 64 && *byte & 0x40 != 0 {
+                value |= -1i64 << used_bits;
+            }
+            return Some((value, i + 1));
+        }
+    }
+
+    None
+}
+
+fn read_utf8(bytes: &[u8]) -> Option<char> {
+    let width = match *bytes.first()? {
+        0x00 ..= 0x7F => 1,
+        0xC0 ..= 0xDF => 2,
+        0xE0 ..= 0xEF => 3,
+        0xF0 ..= 0xF7 => 4,
+        _ => {
+            return None;
+        }
+    };
+
+    std::str::from_utf8(bytes.get(0 .. width)?).ok()?.chars().next()
+}
+
+fn read_utf16(bytes: &[u8], big_endian: bool) -> Option<char> {
+    let mut units = Vec::new();
+    for pair in bytes.chunks_exact(2).take(2) {
+        let pair = [pair[0], pair[1]];
+        if big_endian {
+            units.push(u16::from_be_bytes(pair));
+        } else {
+            units.push(u16::from_le_bytes(pair));
+        }
+    }
+
+    char::decode_utf16(units).next()?.ok()
+}
+
+fn format_char(c: char) -> String {
+    if c.is_control() {
+        format!("U+{:04X}", c as u32)
+    } else {
+        format!("U+{:04X} '{}'", c as u32, c)
+    }
+}
+
+/// Seconds since the epoch as a UTC date and time
+fn format_timestamp(seconds: i64) -> String {
+    let days = seconds.div_euclid(86_400);
+    let time = seconds.rem_euclid(86_400);
+
+    // Civil date from days since 1970-01-01 (Howard Hinnant's algorithm)
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 { shifted_month + 3 } else { shifted_month - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+        year, month, day,
+        time / 3600, (time % 3600) / 60, time % 60
+    )
+}
@@ -1,7 +1,9 @@
 use std::collections::{HashMap, HashSet};
+use std::cell::OnceCell;
 use std::cmp::{min, max};
-use std::fs::File;
-use std::io::{Write, Read};
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::Path;
 use std::error::Error;
 use std::fmt;
 use std::time::{Duration, Instant};
@@ -16,11 +18,42 @@ pub mod cursor;
 pub mod formatter;
 pub mod tests;
 pub mod content;
+pub mod buffer;
+pub mod structured;
+pub mod template;
+pub mod inspector;
+pub mod save;
+pub mod records;
+pub mod export;
+pub mod substitute;
+pub mod typed;
+pub mod search_job;
+mod history;
+mod undo_tree;
 
 use formatter::{BinaryFormatter, HexFormatter, Formatter, FormatterRef, DecFormatter, FormatterError};
 use viewport::ViewPort;
 use cursor::Cursor;
 use content::{Content, ContentError, BitMask};
+use buffer::Buffer;
+use undo_tree::UndoTree;
+use template::{Template, TemplateError, TemplateField, TemplateOverlay};
+use save::{Backup, FileStamp};
+use records::{FileFormat, RecordError};
+use substitute::{Replacement, Substitution};
+use typed::{parse_typed_value, TypedValueError};
+use search_job::SearchJob;
+
+// Position, bytes to remove, bytes to insert
+type UndoTask = (usize, usize, Vec<u8>, Instant);
+
+// Tasks made within this long of each other are undone and redone together, unless either was made in a
+// transaction: a transaction is always undone as one, and only along with another when its change carried
+// on the one before, like typing
+const UNDO_THRESHOLD: Duration = Duration::from_millis(50);
+
+// Bytes compared at a time when looking for the next difference between buffers
+const DIFF_BLOCK_SIZE: usize = 4096;
 
 
 #[derive(Debug, Eq, PartialEq)]
@@ -47,6 +80,17 @@ pub enum SbyteError {
     InvalidDigit(FormatterRef),
     InvalidRadix(u8),
     BufferEmpty,
+    InvalidBuffer(usize),
+    NoDifference,
+    InvalidTemplate(TemplateError),
+    InvalidRecords(RecordError),
+    InvalidTypedValue(TypedValueError),
+    InvalidSubstitution(String),
+    // The file the content pages from was changed on disk while it was open
+    OriginalChanged,
+    ReadOnly,
+    // Start and end of the locked range an edit would have touched
+    LockedRange(usize, usize),
     KillSignal,
     IOError
 }
@@ -101,18 +145,49 @@ pub struct Editor {
 
     //Editor
     clipboard: Vec<u8>,
+    // Named registers, a to z, and - for the last deletion
+    registers: HashMap<char, Vec<u8>>,
     active_content: Content,
     active_file_path: Option<String>,
+    // Ranges of bytes (start to end, exclusive) that can't be edited, sorted and not overlapping
+    locked_ranges: Vec<(usize, usize)>,
+    // How the file looked on disk when last loaded or saved
+    file_stamp: Option<FileStamp>,
+    file_format: FileFormat,
+    // Address of the first byte, as given by the file's records or set by the user
+    base_address: usize,
+    start_address: Option<u32>,
     cursor: Cursor,
     subcursor: Cursor,
     active_formatter: FormatterRef,
-    undo_stack: Vec<(usize, usize, Vec<u8>, Instant)>, // Position, bytes to remove, bytes to insert
-    redo_stack: Vec<(usize, usize, Vec<u8>, Instant)>, // Position, bytes to remove, bytes to insert
+    undo_tree: UndoTree,
+    // How many transactions are open; changes made while any are undo as one
+    transaction_depth: usize,
+
+    // Open files other than the active one. The active file sits at active_buffer_index.
+    buffers: Vec<Buffer>,
+    active_buffer_index: usize,
+    diff_buffer_index: Option<usize>,
+
+    // Template and the offset it's laid over, along with the fields it gives (built when first needed)
+    active_template: Option<(Template, usize)>,
+    template_overlay: OnceCell<TemplateOverlay>,
+
+    // Keep the undo history in a file beside the one being edited, written on save
+    persistent_undo: bool,
+    backup: Backup,
+    read_only: bool,
+    // Shown in the gaps between records, which aren't saved
+    pad_byte: u8,
 
     // VisualEditor
     viewport: ViewPort,
+    // Bytes per row asked for, rather than as many as fit on screen
+    fixed_viewport_width: Option<usize>,
 
     search_history: Vec<String>,
+    // Matches of the search being typed, sorted, shown in their own colour
+    search_highlights: Vec<(usize, usize)>,
     changed_offsets: HashSet<(usize, usize, bool)>,
 
     _active_display_ratio: u8
@@ -127,18 +202,38 @@ impl Editor {
             user_error_msg: None,
 
             clipboard: Vec::new(),
+            registers: HashMap::new(),
             active_content: Content::new(),
             active_file_path: None,
+            locked_ranges: Vec::new(),
+            file_stamp: None,
+            file_format: FileFormat::Raw,
+            base_address: 0,
+            start_address: None,
             cursor: Cursor::new(),
             subcursor: Cursor::new(),
             active_formatter: FormatterRef::HEX,
-            undo_stack: Vec::new(),
-            redo_stack: Vec::new(),
+            undo_tree: UndoTree::new(),
+            transaction_depth: 0,
+
+            buffers: Vec::new(),
+            active_buffer_index: 0,
+            diff_buffer_index: None,
+
+            active_template: None,
+            template_overlay: OnceCell::new(),
+
+            persistent_undo: false,
+            backup: Backup::Off,
+            read_only: false,
+            pad_byte: 0xFF,
 
             viewport: ViewPort::new(1, 1),
+            fixed_viewport_width: None,
 
 
             search_history: Vec::new(),
+            search_highlights: Vec::new(),
             changed_offsets: HashSet::new(),
 
             _active_display_ratio: 3
@@ -156,6 +251,7 @@ impl Editor {
     }
 
     pub fn increment_byte(&mut self, offset: usize, word_size: usize) -> Result<(), SbyteError> {
+        self.check_word_editable(offset, word_size)?;
         match self.active_content.increment_byte(offset, word_size) {
             Ok(undo_bytes) => {
                 let undo_len = undo_bytes.len();
@@ -170,6 +266,7 @@ impl Editor {
     }
 
     pub fn decrement_byte(&mut self, offset: usize, word_size: usize) -> Result<(), SbyteError> {
+        self.check_word_editable(offset, word_size)?;
         match self.active_content.decrement_byte(offset, word_size) {
             Ok(undo_bytes) => {
                 let undo_len = undo_bytes.len();
@@ -183,6 +280,12 @@ impl Editor {
         }
     }
 
+    // The word being incremented or decremented ends at `offset`
+    fn check_word_editable(&self, offset: usize, word_size: usize) -> Result<(), SbyteError> {
+        let start = (offset + 1).saturating_sub(word_size);
+        self.check_editable(start, offset + 1 - start)
+    }
+
     pub fn set_user_error_msg(&mut self, msg: &str) {
         self.user_error_msg = Some(msg.to_string());
     }
@@ -199,30 +302,37 @@ impl Editor {
         self.search_history.push(search_string.clone());
     }
 
+    /// Start grouping changes so they're undone and redone as one, until the matching `end_transaction`.
+    /// Transactions can be nested; only the outermost one counts.
+    pub fn begin_transaction(&mut self) {
+        self.transaction_depth += 1;
+    }
+
+    pub fn end_transaction(&mut self) {
+        self.transaction_depth = self.transaction_depth.saturating_sub(1);
+        if self.transaction_depth == 0 {
+            self.undo_tree.close_transaction();
+            // The transaction may have switched buffers part way through
+            for buffer in self.buffers.iter_mut() {
+                buffer.undo_tree.close_transaction();
+            }
+        }
+    }
+
     pub fn undo(&mut self) -> Result<usize, SbyteError> {
         let mut tasks_undone = 0;
-        let threshold = Duration::from_nanos(50_000_000);
-        let mut latest_instant: Option<Instant> = None;
-        loop {
-            let task_option = self.undo_stack.pop();
-            match task_option {
-                Some(task) => {
-                    if match latest_instant {
-                        Some(then) => { (then - task.3) <= threshold }
-                        None => { true }
-                    } {
-                        latest_instant = Some(task.3.clone());
-                        let redo_task = self.do_undo_or_redo(task)?;
-                        self.redo_stack.push(redo_task);
-                        tasks_undone += 1;
-                    } else {
-                        self.undo_stack.push(task);
-                        break;
-                    }
-                }
-                None => {
-                    break;
-                }
+        let mut last_undone = None;
+        while self.undo_tree.get_current() != 0 {
+            let current = self.undo_tree.get_current();
+            if match last_undone {
+                Some(later) => { self.is_same_action(current, later) }
+                None => { true }
+            } {
+                last_undone = Some(current);
+                self.undo_step()?;
+                tasks_undone += 1;
+            } else {
+                break;
             }
         }
 
@@ -235,28 +345,17 @@ impl Editor {
 
     pub fn redo(&mut self) -> Result<usize, SbyteError> {
         let mut tasks_redone = 0;
-        let threshold = Duration::from_nanos(50_000_000);
-        let mut latest_instant: Option<Instant> = None;
-        loop {
-            let task_option = self.redo_stack.pop();
-            match task_option {
-                Some(task) => {
-                    if match latest_instant {
-                        Some(then) => { (task.3 - then) <= threshold }
-                        None => { true }
-                    } {
-                        latest_instant = Some(task.3.clone());
-                        let redo_task = self.do_undo_or_redo(task)?;
-                        self.undo_stack.push(redo_task);
-                        tasks_redone += 1;
-                    } else {
-                        self.undo_stack.push(task);
-                        break;
-                    }
-                }
-                None => {
-                    break;
-                }
+        let mut last_redone = None;
+        while let Some(child) = self.undo_tree.get_redo_child() {
+            if match last_redone {
+                Some(earlier) => { self.is_same_action(earlier, child) }
+                None => { true }
+            } {
+                last_redone = Some(child);
+                self.redo_step(child)?;
+                tasks_redone += 1;
+            } else {
+                break;
             }
         }
 
@@ -267,7 +366,158 @@ impl Editor {
         }
     }
 
-    fn do_undo_or_redo(&mut self, task: (usize, usize, Vec<u8>, Instant)) -> Result<(usize, usize, Vec<u8>, Instant), SbyteError> {
+    // Whether the changes of nodes `earlier` and `later` were made by the same action: in the same
+    // transaction or, when made outside of any, close enough together
+    fn is_same_action(&self, earlier: usize, later: usize) -> bool {
+        match (self.undo_tree.get_transaction(earlier), self.undo_tree.get_transaction(later)) {
+            (Some(a), Some(b)) => {
+                a == b
+            }
+            (None, None) => {
+                let then = self.undo_tree.get_task(earlier).3;
+                self.undo_tree.get_task(later).3.saturating_duration_since(then) <= UNDO_THRESHOLD
+            }
+            _ => {
+                false
+            }
+        }
+    }
+
+    // Undo the change that led to the current state, moving to its parent
+    fn undo_step(&mut self) -> Result<(), SbyteError> {
+        let task = self.undo_tree.get_task(self.undo_tree.get_current()).clone();
+        let redo_task = self.do_undo_or_redo(task)?;
+        self.undo_tree.step_back(redo_task);
+
+        Ok(())
+    }
+
+    // Redo the change leading to `child` from the current state
+    fn redo_step(&mut self, child: usize) -> Result<(), SbyteError> {
+        let task = self.undo_tree.get_task(child).clone();
+        let undo_task = self.do_undo_or_redo(task)?;
+        self.undo_tree.step_forward(child, undo_task);
+
+        Ok(())
+    }
+
+    /// Undo and redo as needed to get to the state after change number `target`, 0 being no changes.
+    pub fn undo_goto(&mut self, target: usize) -> Result<(), SbyteError> {
+        if target >= self.undo_tree.len() {
+            Err(SbyteError::EmptyStack)?;
+        }
+
+        let path = self.undo_tree.get_path(target);
+        let on_path: HashSet<usize> = path.iter().copied().collect();
+        while self.undo_tree.get_current() != 0 && !on_path.contains(&self.undo_tree.get_current()) {
+            self.undo_step()?;
+        }
+
+        let current = self.undo_tree.get_current();
+        let start = match path.iter().position(|node| *node == current) {
+            Some(index) => index + 1,
+            None => 0
+        };
+
+        for node in path[start ..].iter() {
+            self.redo_step(*node)?;
+        }
+
+        Ok(())
+    }
+
+    /// Move to the state before (or after) the current one in the order the changes were made,
+    /// regardless of branch. Changes made together are stepped over together.
+    pub fn undo_chronological(&mut self, is_newer: bool) -> Result<usize, SbyteError> {
+        let current = self.undo_tree.get_current();
+
+        let target = if is_newer {
+            if current + 1 >= self.undo_tree.len() {
+                Err(SbyteError::EmptyStack)?;
+            }
+
+            let mut target = current + 1;
+            while target + 1 < self.undo_tree.len() && self.is_same_action(target, target + 1) {
+                target += 1;
+            }
+            target
+        } else {
+            if current == 0 {
+                Err(SbyteError::EmptyStack)?;
+            }
+
+            let mut target = current - 1;
+            while target > 0 && self.is_same_action(target, target + 1) {
+                target -= 1;
+            }
+            target
+        };
+
+        self.undo_goto(target)?;
+
+        Ok(target)
+    }
+
+    /// Move to the state as it was `duration` before (or after) the current state was reached
+    pub fn undo_by_time(&mut self, duration: Duration, is_later: bool) -> Result<usize, SbyteError> {
+        let current = self.undo_tree.get_current();
+        let current_time = self.undo_tree.get_task(current).3;
+
+        let target_time = if is_later {
+            current_time.checked_add(duration)
+        } else {
+            current_time.checked_sub(duration)
+        };
+
+        // The latest change made by the target time
+        let mut target = 0;
+        if let Some(target_time) = target_time {
+            for node in 1 .. self.undo_tree.len() {
+                if self.undo_tree.get_task(node).3 <= target_time {
+                    target = node;
+                }
+            }
+        }
+
+        if target == current {
+            Err(SbyteError::EmptyStack)?;
+        }
+        self.undo_goto(target)?;
+
+        Ok(target)
+    }
+
+    /// The change number of the current state
+    pub fn get_undo_position(&self) -> usize {
+        self.undo_tree.get_current()
+    }
+
+    /// How many changes can be undone, and redone along the current branch
+    pub fn get_undo_depth(&self) -> (usize, usize) {
+        (self.undo_tree.depth(), self.undo_tree.redo_depth())
+    }
+
+    /// The end of each branch of the undo tree as (change number, changes from the original, age)
+    pub fn get_undo_branches(&self) -> Vec<(usize, usize, Duration)> {
+        let now = Instant::now();
+        let mut output = Vec::new();
+        for leaf in self.undo_tree.get_leaves() {
+            output.push((
+                leaf,
+                self.undo_tree.get_path(leaf).len(),
+                now.saturating_duration_since(self.undo_tree.get_task(leaf).3)
+            ));
+        }
+
+        output
+    }
+
+    fn do_undo_or_redo(&mut self, task: UndoTask) -> Result<UndoTask, SbyteError> {
+        // Locked ranges don't stop changes from being undone, only new ones being made
+        if self.read_only {
+            Err(SbyteError::ReadOnly)?;
+        }
+
         let (offset, bytes_to_remove, bytes_to_insert, timestamp) = task;
         self.set_cursor_length(1);
         self.set_cursor_offset(offset)?;
@@ -285,20 +535,21 @@ impl Editor {
         }
 
         self.changed_offsets.insert((offset, max(opposite_bytes_to_remove, opposite_bytes_to_insert.len()), opposite_bytes_to_remove != opposite_bytes_to_insert.len()));
+        self.shift_locked_ranges(offset, bytes_to_remove, opposite_bytes_to_remove);
+        self.template_overlay = OnceCell::new();
 
         Ok((offset, opposite_bytes_to_remove, opposite_bytes_to_insert, timestamp))
     }
 
     fn push_to_undo_stack(&mut self, offset: usize, bytes_to_remove: usize, bytes_to_insert: Vec<u8>) {
-
-        self.redo_stack.drain(..);
         let is_insert = bytes_to_remove == 0 && bytes_to_insert.len() > 0;
         let is_remove = bytes_to_remove > 0 && bytes_to_insert.len() == 0;
         let is_overwrite = !is_insert && !is_remove;
 
 
         let mut was_merged = false;
-        match self.undo_stack.last_mut() {
+        let in_transaction = self.transaction_depth > 0;
+        match self.undo_tree.get_current_task_mut(in_transaction) {
             Some((next_offset, next_bytes_to_remove, next_bytes_to_insert, prev_timestamp)) => {
                 let will_insert = *next_bytes_to_remove == 0 && next_bytes_to_insert.len() > 0;
                 let will_remove = *next_bytes_to_remove > 0 && next_bytes_to_insert.len() == 0;
@@ -331,9 +582,14 @@ impl Editor {
         }
 
         self.changed_offsets.insert((offset, max(bytes_to_remove, bytes_to_insert.len()), bytes_to_remove != bytes_to_insert.len()));
+        self.shift_locked_ranges(offset, bytes_to_insert.len(), bytes_to_remove);
+        self.template_overlay = OnceCell::new();
 
         if !was_merged {
-            self.undo_stack.push((offset, bytes_to_remove, bytes_to_insert, Instant::now()));
+            self.undo_tree.push((offset, bytes_to_remove, bytes_to_insert, Instant::now()), in_transaction);
+        } else if in_transaction {
+            // The rest of this transaction is undone along with the change it carried on
+            self.undo_tree.resume_transaction();
         }
 
     }
@@ -383,19 +639,42 @@ impl Editor {
     }
 
     pub fn replace(&mut self, search_for: &str, replace_with: &[u8]) -> Result<Vec<usize>, SbyteError> {
-        let mut matches = self.find_all(&search_for)?;
+        self.replace_in_range(search_for, replace_with, (0, self.len()))
+    }
+
+    /// Replace only the matches within `range`, leaving the same bytes anywhere else as they are
+    pub fn replace_in_range(&mut self, search_for: &str, replace_with: &[u8], range: (usize, usize)) -> Result<Vec<usize>, SbyteError> {
+        let replacements = self.find_replacements(search_for, &Substitution::literal(replace_with), range)?;
+        self.replace_matches(&replacements)
+    }
+
+    /// Put each replacement in place of its match, as one undo step. Gives back where the matches started.
+    pub fn replace_matches(&mut self, replacements: &[Replacement]) -> Result<Vec<usize>, SbyteError> {
+        let mut matches = replacements.to_vec();
         // replace in reverse order
-        matches.sort();
+        matches.sort_by_key(|(start, end, _)| (*start, *end));
         matches.reverse();
 
+        // Nothing is replaced if any of it can't be
+        for (start, end, _) in matches.iter() {
+            self.check_editable(*start, *end - *start)?;
+        }
+
         let mut removed_bytes: Vec<u8>;
         let mut hit_positions: Vec<usize> = Vec::new();
-        for (start, end) in matches.iter() {
+        let mut result = Ok(());
+        self.begin_transaction();
+        for (start, end, replace_with) in matches.iter() {
             hit_positions.push(*start);
             removed_bytes = self.active_content.remove_bytes(*start, *end - *start);
-            self.active_content.insert_bytes(*start, replace_with)?;
+            if let Err(e) = self.active_content.insert_bytes(*start, replace_with) {
+                result = Err(e);
+                break;
+            }
             self.push_to_undo_stack(*start, replace_with.len(), removed_bytes.clone());
         }
+        self.end_transaction();
+        result?;
 
         Ok(hit_positions)
     }
@@ -417,47 +696,754 @@ impl Editor {
         self.clipboard.clone()
     }
 
+    /// Put `bytes` in register `name`, or with an uppercase name, add them to the end of it.
+    /// The `"` register is the clipboard.
+    pub fn copy_to_register(&mut self, name: char, bytes: &[u8]) {
+        if name == '"' {
+            self.copy_to_clipboard(bytes.to_vec());
+        } else if name.is_ascii_uppercase() {
+            self.registers.entry(name.to_ascii_lowercase()).or_default().extend_from_slice(bytes);
+        } else {
+            self.registers.insert(name, bytes.to_vec());
+        }
+    }
+
+    pub fn get_register(&self, name: char) -> Option<Vec<u8>> {
+        if name == '"' {
+            Some(self.get_clipboard())
+        } else {
+            self.registers.get(&name.to_ascii_lowercase()).cloned()
+        }
+    }
+
+    /// Names of the registers that have been filled, in order
+    pub fn get_register_names(&self) -> Vec<char> {
+        let mut names: Vec<char> = self.registers.keys().copied().collect();
+        names.sort_unstable();
+        names
+    }
+
     pub fn copy_selection(&mut self) {
         let selected_bytes = self.get_selected();
         self.copy_to_clipboard(selected_bytes);
     }
 
     pub fn load_file(&mut self, file_path: &str) -> Result<(), SbyteError> {
+        let format = FileFormat::detect(file_path);
+        match self.load_file_as(file_path, format) {
+            // The extension was only a guess, so a file that doesn't decode is opened as it is
+            Err(SbyteError::InvalidRecords(_)) if format != FileFormat::Raw => {
+                self.load_file_as(file_path, FileFormat::Raw)
+            }
+            result => result
+        }
+    }
+
+    /// Load the file at `file_path`, decoding it from `format` rather than guessing
+    pub fn load_file_as(&mut self, file_path: &str, format: FileFormat) -> Result<(), SbyteError> {
         self.flag_loading = true;
         self.active_content = Content::new();
+        self.template_overlay = OnceCell::new();
 
         self.set_file_path(file_path);
-        match File::open(file_path) {
-            Ok(mut file) => {
-                let file_length;
-                match file.metadata() {
-                    Ok(metadata) => {
-                        file_length = metadata.len();
+        self.locked_ranges.clear();
+        self.file_format = format;
+        self.base_address = 0;
+        self.start_address = None;
+        match format {
+            FileFormat::Raw => {
+                match Content::from_file(file_path) {
+                    Ok(content) => {
+                        self.active_content = content;
                     }
-                    Err(e) => {
-                        file_length = 0;
-                        Err(e)?;
+                    Err(_e) => {
+                        self.flag_loading = false;
+                        Err(SbyteError::FileNotFound(file_path.to_string()))?
                     }
-                };
+                }
+            }
+            _ => {
+                let result = self.load_records(file_path, format);
+                if result.is_err() {
+                    self.flag_loading = false;
+                    result?;
+                }
+            }
+        }
 
-                let mut buffer: Vec<u8> = vec![0; file_length as usize];
-                file.read(&mut buffer)?;
+        self.flag_loading = false;
+        self.file_stamp = save::get_file_stamp(file_path);
+
+        // Pick up where a previous session left off if the file hasn't changed since
+        if history::sidecar_path(file_path).exists() {
+            if let Some(undo_tree) = history::read_history(file_path, self.get_content_key()) {
+                self.undo_tree = undo_tree;
+            }
+        }
+        self.undo_tree.mark_saved();
+
+        Ok(())
+    }
 
-                for byte in buffer.iter() {
-                    self.active_content.push(*byte);
+    // Records are decoded in one go, so unlike raw files, the whole file is read into memory
+    fn load_records(&mut self, file_path: &str, format: FileFormat) -> Result<(), SbyteError> {
+        let input = match fs::read(file_path) {
+            Ok(input) => input,
+            Err(_e) => {
+                return Err(SbyteError::FileNotFound(file_path.to_string()));
+            }
+        };
+
+        match records::decode(format, &input) {
+            Ok(image) => {
+                // Gaps between records are shown as the pad byte, but not kept in memory or saved
+                let mut address = image.base_address;
+                for (run_address, data) in image.runs.iter() {
+                    let length = self.active_content.len();
+                    self.active_content.insert_fill(length, self.pad_byte, run_address - address)?;
+                    self.active_content.insert_bytes(length + run_address - address, data)?;
+                    address = run_address + data.len();
                 }
+                self.base_address = image.base_address;
+                self.start_address = image.start_address;
+                Ok(())
             }
             Err(e) => {
-                Err(SbyteError::FileNotFound(file_path.to_string()))?
+                Err(SbyteError::InvalidRecords(e))
             }
         }
+    }
 
-        self.flag_loading = false;
+    /// Replace the content with everything read from `reader`, not associated with any file
+    pub fn load_reader<R: Read>(&mut self, reader: &mut R) -> Result<(), SbyteError> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes)?;
+
+        self.active_content = Content::new();
+        self.active_content.insert_bytes(0, &bytes)?;
+        self.active_file_path = None;
+        self.file_stamp = None;
+        self.locked_ranges.clear();
+        self.file_format = FileFormat::Raw;
+        self.base_address = 0;
+        self.start_address = None;
+        self.template_overlay = OnceCell::new();
+
+        Ok(())
+    }
+
+    /// Write all of the content out to `writer`, as it would be saved
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        self.active_content.write_to(writer)?;
+        writer.flush()
+    }
+
+    pub fn set_persistent_undo(&mut self, persistent_undo: bool) {
+        self.persistent_undo = persistent_undo;
+    }
+
+    pub fn is_persistent_undo(&self) -> bool {
+        self.persistent_undo
+    }
+
+    pub fn set_read_only(&mut self, read_only: bool) {
+        self.read_only = read_only;
+    }
+
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    /// Keep the `length` bytes at `offset` from being edited
+    pub fn lock_range(&mut self, offset: usize, length: usize) {
+        if length == 0 {
+            return;
+        }
+
+        let mut start = offset;
+        let mut end = offset + length;
+        // Absorb any ranges it overlaps or touches
+        self.locked_ranges.retain(|(locked_start, locked_end)| {
+            if *locked_start <= end && start <= *locked_end {
+                start = min(start, *locked_start);
+                end = max(end, *locked_end);
+                false
+            } else {
+                true
+            }
+        });
+
+        let index = self.locked_ranges.iter().position(|(locked_start, _)| *locked_start > start).unwrap_or(self.locked_ranges.len());
+        self.locked_ranges.insert(index, (start, end));
+    }
+
+    /// Allow the `length` bytes at `offset` to be edited again
+    pub fn unlock_range(&mut self, offset: usize, length: usize) {
+        let end = offset + length;
+        let mut remaining = Vec::new();
+        for (locked_start, locked_end) in self.locked_ranges.drain(..) {
+            if locked_start < offset {
+                remaining.push((locked_start, min(locked_end, offset)));
+            }
+            if locked_end > end {
+                remaining.push((max(locked_start, end), locked_end));
+            }
+        }
+        self.locked_ranges = remaining;
+    }
+
+    pub fn get_locked_ranges(&self) -> &[(usize, usize)] {
+        &self.locked_ranges
+    }
+
+    // Whether the `length` bytes at `offset` may be changed. Zero length checks an insertion at `offset`,
+    // which is fine at the edges of a locked range but not inside it.
+    fn check_editable(&self, offset: usize, length: usize) -> Result<(), SbyteError> {
+        if self.read_only {
+            Err(SbyteError::ReadOnly)?;
+        }
+
+        for (start, end) in self.locked_ranges.iter() {
+            let touches = if length == 0 {
+                *start < offset && offset < *end
+            } else {
+                offset < *end && *start < offset + length
+            };
+
+            if touches {
+                Err(SbyteError::LockedRange(*start, *end))?;
+            }
+        }
 
         Ok(())
     }
 
+    // Keep locked ranges on the same bytes after `removed` bytes at `offset` are replaced by `inserted` ones
+    fn shift_locked_ranges(&mut self, offset: usize, removed: usize, inserted: usize) {
+        if removed == inserted {
+            return;
+        }
+
+        for (start, end) in self.locked_ranges.iter_mut() {
+            if *start >= offset + removed {
+                *start = *start + inserted - removed;
+                *end = *end + inserted - removed;
+            } else if *end > offset {
+                // Only undoing can change bytes inside a locked range
+                *end = max(*start + 1, (*end + inserted).saturating_sub(removed));
+            }
+        }
+    }
+
+    pub fn get_file_format(&self) -> FileFormat {
+        self.file_format
+    }
+
+    /// Change the format the active file is written in when saved
+    pub fn set_file_format(&mut self, format: FileFormat) {
+        self.file_format = format;
+    }
+
+    pub fn get_base_address(&self) -> usize {
+        self.base_address
+    }
+
+    /// Set the address of the first byte, which records are written from and offsets are shown relative to
+    pub fn set_base_address(&mut self, base_address: usize) {
+        self.base_address = base_address;
+    }
+
+    pub fn get_start_address(&self) -> Option<u32> {
+        self.start_address
+    }
+
+    pub fn set_pad_byte(&mut self, pad_byte: u8) {
+        self.pad_byte = pad_byte;
+    }
+
+    pub fn get_pad_byte(&self) -> u8 {
+        self.pad_byte
+    }
+
+    pub fn set_backup(&mut self, backup: Backup) {
+        self.backup = backup;
+    }
+
+    pub fn get_backup(&self) -> Backup {
+        self.backup
+    }
+
+    /// Whether the active buffer has changes that haven't been saved
+    pub fn is_modified(&self) -> bool {
+        !self.undo_tree.is_saved()
+    }
+
+    /// Whether `path` names the active buffer's file, however it's written
+    pub fn is_active_file(&self, path: &str) -> bool {
+        match &self.active_file_path {
+            Some(active_path) => {
+                if Path::new(active_path) == Path::new(path) {
+                    true
+                } else {
+                    match (fs::canonicalize(active_path), fs::canonicalize(path)) {
+                        (Ok(a), Ok(b)) => a == b,
+                        _ => false
+                    }
+                }
+            }
+            None => {
+                false
+            }
+        }
+    }
+
+    /// Whether the active file was modified or resized on disk since it was loaded or last saved
+    pub fn is_changed_on_disk(&self) -> bool {
+        match (&self.active_file_path, &self.file_stamp) {
+            (Some(path), Some(stamp)) => {
+                save::get_file_stamp(path).as_ref() != Some(stamp)
+            }
+            _ => {
+                false
+            }
+        }
+    }
+
+    // Hash and length of the content, identifying it to the saved undo history
+    fn get_content_key(&self) -> (u64, usize) {
+        let mut hasher = history::ContentHasher::new();
+        self.active_content.write_to(&mut hasher).ok();
+        hasher.finish()
+    }
+
+    /// Open a file in a new buffer and switch to it.
+    /// If the file is already open, switch to its buffer instead.
+    pub fn open_buffer(&mut self, file_path: &str) -> Result<(), SbyteError> {
+        match self.switch_to_open_buffer(file_path) {
+            Some(result) => result,
+            None => self.open_buffer_with(|editor| editor.load_file(file_path))
+        }
+    }
+
+    /// Like `open_buffer`, decoding the file from `format` rather than guessing
+    pub fn open_buffer_as(&mut self, file_path: &str, format: FileFormat) -> Result<(), SbyteError> {
+        match self.switch_to_open_buffer(file_path) {
+            Some(result) => result,
+            None => self.open_buffer_with(|editor| editor.load_file_as(file_path, format))
+        }
+    }
+
+    fn switch_to_open_buffer(&mut self, file_path: &str) -> Option<Result<(), SbyteError>> {
+        let paths = self.get_buffer_paths();
+        let index = paths.iter().position(|path| path.as_deref() == Some(file_path))?;
+        Some(self.switch_to_buffer(index))
+    }
+
+    /// Open everything read from `reader` in a new buffer with no file, and switch to it.
+    pub fn open_buffer_from_reader<R: Read>(&mut self, reader: &mut R) -> Result<(), SbyteError> {
+        self.open_buffer_with(|editor| editor.load_reader(reader))
+    }
+
+    // Reuse the active buffer if nothing's been done in it, otherwise make a new one after it to load into
+    fn open_buffer_with<F>(&mut self, load: F) -> Result<(), SbyteError>
+    where F: FnOnce(&mut Editor) -> Result<(), SbyteError> {
+        let is_blank = self.active_file_path.is_none()
+            && self.active_content.len() == 0
+            && self.undo_tree.is_empty();
+
+        if is_blank {
+            load(self)?;
+            self.refresh_buffer_display();
+        } else {
+            let previous_index = self.active_buffer_index;
+            if let Some(diff_index) = self.diff_buffer_index {
+                if diff_index > previous_index {
+                    self.diff_buffer_index = Some(diff_index + 1);
+                }
+            }
+            self.buffers.insert(previous_index, Buffer::new());
+            self.switch_to_buffer(previous_index + 1)?;
+
+            match load(self) {
+                Ok(_) => {
+                    self.refresh_buffer_display();
+                }
+                Err(e) => {
+                    self.close_buffer();
+                    self.switch_to_buffer(previous_index)?;
+                    Err(e)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn switch_to_buffer(&mut self, index: usize) -> Result<(), SbyteError> {
+        if index >= self.get_buffer_count() {
+            Err(SbyteError::InvalidBuffer(index))
+        } else {
+            if index != self.active_buffer_index {
+                // Switching to the buffer being compared against swaps the sides of the diff
+                if self.diff_buffer_index == Some(index) {
+                    self.diff_buffer_index = Some(self.active_buffer_index);
+                }
+
+                let mut previous = Buffer::new();
+                previous.swap(self);
+                self.buffers.insert(self.active_buffer_index, previous);
+
+                let mut next = self.buffers.remove(index);
+                next.swap(self);
+                self.active_buffer_index = index;
+
+                self.refresh_buffer_display();
+            }
+            Ok(())
+        }
+    }
+
+    pub fn next_buffer(&mut self) -> Result<(), SbyteError> {
+        let index = (self.active_buffer_index + 1) % self.get_buffer_count();
+        self.switch_to_buffer(index)
+    }
+
+    pub fn prev_buffer(&mut self) -> Result<(), SbyteError> {
+        let count = self.get_buffer_count();
+        let index = (self.active_buffer_index + count - 1) % count;
+        self.switch_to_buffer(index)
+    }
+
+    /// Close the active buffer, switching to the one after it.
+    /// Closing the only buffer leaves an empty, unnamed one in its place.
+    pub fn close_buffer(&mut self) {
+        self.diff_buffer_index = None;
+
+        let mut closed = Buffer::new();
+        closed.swap(self);
+
+        if !self.buffers.is_empty() {
+            let index = min(self.active_buffer_index, self.buffers.len() - 1);
+            let mut next = self.buffers.remove(index);
+            next.swap(self);
+            self.active_buffer_index = index;
+        }
+
+        self.refresh_buffer_display();
+    }
+
+    pub fn get_buffer_count(&self) -> usize {
+        self.buffers.len() + 1
+    }
+
+    pub fn get_active_buffer_index(&self) -> usize {
+        self.active_buffer_index
+    }
+
+    /// File paths of all open buffers, in order.
+    pub fn get_buffer_paths(&self) -> Vec<Option<String>> {
+        let mut output: Vec<Option<String>> = self.buffers.iter()
+            .map(|buffer| buffer.get_file_path().cloned())
+            .collect();
+        output.insert(self.active_buffer_index, self.active_file_path.clone());
+
+        output
+    }
+
+    /// The bytes under the cursor decoded as each of the common types
+    pub fn inspect_at_cursor(&self) -> Vec<(&'static str, Option<String>)> {
+        let bytes = self.get_chunk(self.cursor.get_offset(), inspector::INSPECT_LENGTH);
+        inspector::inspect(&bytes)
+    }
+
+    /// Read a template from `file_path` and lay it over the active buffer starting at `offset`.
+    pub fn load_template(&mut self, file_path: &str, offset: usize) -> Result<(), SbyteError> {
+        let input = match fs::read_to_string(file_path) {
+            Ok(input) => input,
+            Err(_e) => {
+                return Err(SbyteError::FileNotFound(file_path.to_string()));
+            }
+        };
+
+        match Template::parse(&input) {
+            Ok(template) => {
+                self.active_template = Some((template, offset));
+                self.template_overlay = OnceCell::new();
+                self.refresh_buffer_display();
+                Ok(())
+            }
+            Err(e) => {
+                Err(SbyteError::InvalidTemplate(e))
+            }
+        }
+    }
+
+    pub fn unset_template(&mut self) {
+        self.active_template = None;
+        self.template_overlay = OnceCell::new();
+        self.refresh_buffer_display();
+    }
+
+    pub fn has_template(&self) -> bool {
+        self.active_template.is_some()
+    }
+
+    fn get_template_overlay(&self) -> Option<&TemplateOverlay> {
+        let (template, offset) = self.active_template.as_ref()?;
+        Some(self.template_overlay.get_or_init(|| {
+            let fields = template.apply(*offset, self.len(), |offset, length| {
+                self.active_content.get_chunk(offset, length)
+            });
+            TemplateOverlay::new(fields)
+        }))
+    }
+
+    /// The fields of the active template, in order, each struct before its members
+    pub fn get_template_fields(&self) -> &[TemplateField] {
+        match self.get_template_overlay() {
+            Some(overlay) => overlay.get_fields(),
+            None => &[]
+        }
+    }
+
+    /// Get the index of the template field with a value that covers `offset`
+    pub fn get_template_field_index_at(&self, offset: usize) -> Option<usize> {
+        self.get_template_overlay()?.get_field_index_at(offset)
+    }
+
+    /// For each byte in the range, the position of the template field covering it among those with values
+    pub fn get_template_mask(&self, offset: usize, length: usize) -> Vec<Option<usize>> {
+        let mut output = Vec::new();
+        if let Some(overlay) = self.get_template_overlay() {
+            for i in offset .. offset + length {
+                output.push(overlay.get_leaf_position_at(i));
+            }
+        }
+
+        output
+    }
+
+    /// Compare the active buffer against another open buffer, byte for byte.
+    pub fn set_diff_buffer(&mut self, index: usize) -> Result<(), SbyteError> {
+        if index >= self.get_buffer_count() || index == self.active_buffer_index {
+            Err(SbyteError::InvalidBuffer(index))
+        } else {
+            self.diff_buffer_index = Some(index);
+            self.refresh_buffer_display();
+            Ok(())
+        }
+    }
+
+    pub fn unset_diff_buffer(&mut self) {
+        self.diff_buffer_index = None;
+        self.refresh_buffer_display();
+    }
+
+    pub fn get_diff_buffer_index(&self) -> Option<usize> {
+        self.diff_buffer_index
+    }
+
+    fn get_diff_buffer(&self) -> Option<&Buffer> {
+        match self.diff_buffer_index {
+            Some(index) => {
+                if index < self.active_buffer_index {
+                    self.buffers.get(index)
+                } else {
+                    self.buffers.get(index - 1)
+                }
+            }
+            None => {
+                None
+            }
+        }
+    }
+
+    pub fn get_diff_len(&self) -> Option<usize> {
+        self.get_diff_buffer().map(|buffer| buffer.content.len())
+    }
+
+    /// Bytes of the buffer being compared against, if any
+    pub fn get_diff_chunk(&self, offset: usize, length: usize) -> Option<Vec<u8>> {
+        self.get_diff_buffer().map(|buffer| buffer.content.get_chunk(offset, length))
+    }
+
+    /// Which bytes in the range differ between the active buffer and the one it's compared against.
+    /// A byte present in only one of the two counts as different.
+    pub fn get_diff_mask(&self, offset: usize, length: usize) -> Vec<bool> {
+        let mut output = Vec::new();
+        if let Some(other_chunk) = self.get_diff_chunk(offset, length) {
+            let chunk = self.get_chunk(offset, length);
+            let end = min(
+                offset + length,
+                max(self.len(), self.get_diff_len().unwrap_or(0))
+            );
+
+            for i in 0 .. end.saturating_sub(offset) {
+                output.push(chunk.get(i) != other_chunk.get(i));
+            }
+        }
+
+        output
+    }
+
+    /// Find the first run of differing bytes after the one containing `offset`.
+    pub fn find_next_difference(&self, offset: usize) -> Option<(usize, usize)> {
+        let diff_len = self.get_diff_len()?;
+        let total_length = max(self.len(), diff_len);
+
+        // Skip past the difference already under the offset
+        let mut block_start = self.find_difference_end(offset);
+        while block_start < total_length {
+            let mask = self.get_diff_mask(block_start, DIFF_BLOCK_SIZE);
+            if let Some(i) = mask.iter().position(|different| *different) {
+                // Everything between the skipped run and here matched, so the run starts here
+                let start = block_start + i;
+                return Some((start, self.find_difference_end(start)));
+            }
+            block_start += DIFF_BLOCK_SIZE;
+        }
+
+        None
+    }
+
+    /// Find the last run of differing bytes before the one containing `offset`.
+    pub fn find_prev_difference(&self, offset: usize) -> Option<(usize, usize)> {
+        self.get_diff_len()?;
+
+        let mut end = offset;
+        if let Some((start, _)) = self.get_difference_at(offset) {
+            end = start;
+        }
+
+        while end > 0 {
+            let block_start = end.saturating_sub(DIFF_BLOCK_SIZE);
+            let mask = self.get_diff_mask(block_start, end - block_start);
+            if let Some(i) = mask.iter().rposition(|different| *different) {
+                return self.get_difference_at(block_start + i);
+            }
+            end = block_start;
+        }
+
+        None
+    }
+
+    /// Get the run of differing bytes containing `offset`, if there is one
+    pub fn get_difference_at(&self, offset: usize) -> Option<(usize, usize)> {
+        if !self.is_different_at(offset) {
+            return None;
+        }
+
+        Some((self.find_difference_start(offset), self.find_difference_end(offset)))
+    }
+
+    // Where the run of differing bytes ending just before `offset` starts, or `offset` if there isn't one
+    fn find_difference_start(&self, offset: usize) -> usize {
+        let common_length = min(self.len(), self.get_diff_len().unwrap_or(0));
+
+        // Past the end of the shorter buffer, every byte differs
+        let mut end = min(offset, common_length);
+        while end > 0 {
+            let block_start = end.saturating_sub(DIFF_BLOCK_SIZE);
+            let mask = self.get_diff_mask(block_start, end - block_start);
+            if let Some(i) = mask.iter().rposition(|different| !*different) {
+                return block_start + i + 1;
+            }
+            end = block_start;
+        }
+
+        0
+    }
+
+    // Where the run of differing bytes from `offset` on ends, or `offset` if there isn't one
+    fn find_difference_end(&self, offset: usize) -> usize {
+        let diff_length = self.get_diff_len().unwrap_or(0);
+        let common_length = min(self.len(), diff_length);
+        let total_length = max(self.len(), diff_length);
+
+        let mut block_start = offset;
+        while block_start < common_length {
+            let length = min(DIFF_BLOCK_SIZE, common_length - block_start);
+            let mask = self.get_diff_mask(block_start, length);
+            if let Some(i) = mask.iter().position(|different| !*different) {
+                return block_start + i;
+            }
+            block_start += length;
+        }
+
+        // Past the end of the shorter buffer, every byte differs
+        max(offset, total_length)
+    }
+
+    fn is_different_at(&self, offset: usize) -> bool {
+        self.get_diff_mask(offset, 1).first() == Some(&true)
+    }
+
+    /// The range copied by diff_get/diff_put: the selection if there is one,
+    /// otherwise the run of differing bytes under the cursor.
+    fn get_diff_copy_range(&self) -> Option<(usize, usize)> {
+        let offset = self.cursor.get_offset();
+        let length = self.cursor.get_length();
+        if length > 1 {
+            Some((offset, offset + length))
+        } else {
+            self.get_difference_at(offset)
+        }
+    }
+
+    /// Copy the differing bytes under the cursor from the compared buffer into the active one.
+    pub fn diff_get(&mut self) -> Result<(usize, usize), SbyteError> {
+        let (start, end) = self.get_diff_copy_range().ok_or(SbyteError::NoDifference)?;
+        let new_bytes = self.get_diff_chunk(start, end - start).ok_or(SbyteError::NoDifference)?;
+        self.copy_diff_range(start, end, &new_bytes)?;
+
+        Ok((start, end))
+    }
+
+    /// Copy the differing bytes under the cursor from the active buffer into the compared one.
+    pub fn diff_put(&mut self) -> Result<(usize, usize), SbyteError> {
+        let (start, end) = self.get_diff_copy_range().ok_or(SbyteError::NoDifference)?;
+        let new_bytes = self.get_chunk(start, end - start);
+        let active_index = self.active_buffer_index;
+        let diff_index = self.diff_buffer_index.ok_or(SbyteError::NoDifference)?;
+
+        // Edit the other buffer as the active one so the change lands on its own undo stack
+        self.switch_to_buffer(diff_index)?;
+        let result = self.copy_diff_range(start, end, &new_bytes);
+        self.switch_to_buffer(active_index)?;
+        result?;
+
+        Ok((start, end))
+    }
+
+    fn copy_diff_range(&mut self, start: usize, end: usize, new_bytes: &[u8]) -> Result<(), SbyteError> {
+        if start > self.len() {
+            Err(SbyteError::OutOfBounds(start, self.len()))?;
+        }
+        self.check_editable(start, max(end - start, new_bytes.len()))?;
+
+        self.overwrite_bytes(start, new_bytes)?;
+        if new_bytes.len() < end - start {
+            self.remove_bytes(start + new_bytes.len(), end - start - new_bytes.len())?;
+        }
+
+        Ok(())
+    }
+
+    // Content under the viewport was swapped out wholesale, so everything on screen needs redrawing
+    fn refresh_buffer_display(&mut self) {
+        self.changed_offsets.drain();
+        self.set_subcursor_length();
+        self.adjust_viewport_offset();
+        let viewport_offset = self.viewport.get_offset();
+        self.changed_offsets.insert((viewport_offset, 0, true));
+    }
+
     pub fn save(&mut self) -> Result<(), SbyteError> {
+        // Writing elsewhere with save_as is still allowed
+        if self.read_only {
+            Err(SbyteError::ReadOnly)?;
+        }
+
         match self.active_file_path.clone() {
             Some(path) => {
                 self.save_as(&path.to_string())?;
@@ -471,14 +1457,36 @@ impl Editor {
     }
 
     pub fn save_as(&mut self, path: &str) -> Result<(), SbyteError> {
-        match File::create(path) {
-            Ok(mut file) => {
-                file.write_all(self.active_content.as_slice())?
-                //file.sync_all();
-            }
-            Err(e) => {
-                Err(e)?;
+        // Bytes still to be read from a file that's since changed may not be the ones that were loaded
+        if self.active_content.check_original().is_err() {
+            Err(SbyteError::OriginalChanged)?;
+        }
+
+        // The content may still be reading from the file at `path`, so it's never written over in place.
+        // Replacing it leaves the old version readable until the content lets go of it.
+        // Written in the format its name asks for, or otherwise however the file was loaded
+        let format = FileFormat::from_extension(path).unwrap_or(self.file_format);
+        if format == FileFormat::Raw {
+            let content = &self.active_content;
+            save::write_atomic(path, self.backup, |writer| content.write_to(writer))?;
+        } else {
+            if let Err(e) = records::check_fits(format, self.base_address, self.len()) {
+                Err(SbyteError::InvalidRecords(e))?;
             }
+            let runs: Vec<records::Run> = self.active_content.get_data_ranges().into_iter().map(|(offset, length)| {
+                (self.base_address + offset, self.get_chunk(offset, length))
+            }).collect();
+            let start_address = self.start_address;
+            save::write_atomic(path, self.backup, |writer| records::encode(format, &runs, start_address, writer))?;
+        }
+
+        if self.is_active_file(path) {
+            self.file_stamp = save::get_file_stamp(path);
+            self.undo_tree.mark_saved();
+        }
+
+        if self.persistent_undo {
+            history::write_history(path, self.get_content_key(), &self.undo_tree)?;
         }
 
         Ok(())
@@ -489,6 +1497,82 @@ impl Editor {
     }
 
     pub fn find_all(&self, search_for: &str) -> Result<Vec<(usize, usize)>, SbyteError> {
+        self.find_all_in_range(search_for, (0, self.len()))
+    }
+
+    /// Every match of `search_for` that lies within the bytes from `range.0` up to `range.1`
+    pub fn find_all_in_range(&self, search_for: &str, range: (usize, usize)) -> Result<Vec<(usize, usize)>, SbyteError> {
+        let working_search = self.translate_pattern(search_for)?;
+        match self.active_content.find_all_in_range(&working_search, range) {
+            Ok(output) => {
+                Ok(output)
+            }
+            Err(_) => {
+                Err(SbyteError::InvalidRegex(search_for.to_string()))
+            }
+        }
+    }
+
+    /// Every match of `search_for` within `range`, with the bytes `substitution` would put in its place
+    pub fn find_replacements(&self, search_for: &str, substitution: &Substitution, range: (usize, usize)) -> Result<Vec<Replacement>, SbyteError> {
+        let working_search = self.translate_pattern(search_for)?;
+        match self.active_content.find_all_captures(&working_search, range, |captures| substitution.expand(captures)) {
+            Ok(output) => {
+                Ok(output)
+            }
+            Err(_) => {
+                Err(SbyteError::InvalidRegex(search_for.to_string()))
+            }
+        }
+    }
+
+    /// Start looking for every match of `search_for` within `range` on a worker thread
+    pub fn spawn_find(&self, search_for: &str, range: (usize, usize)) -> Result<SearchJob<()>, SbyteError> {
+        let patt = self.compile_pattern(search_for)?;
+        Ok(SearchJob::spawn(self.active_content.snapshot(), range, move |content, on_window| {
+            content.stream_matches(&patt, range, on_window);
+        }))
+    }
+
+    /// Like spawn_find, but with the bytes `substitution` would put in place of each match, as find_replacements gives them
+    pub fn spawn_find_replacements(&self, search_for: &str, substitution: Substitution, range: (usize, usize)) -> Result<SearchJob<Vec<u8>>, SbyteError> {
+        let patt = self.compile_pattern(search_for)?;
+        Ok(SearchJob::spawn(self.active_content.snapshot(), range, move |content, on_window| {
+            content.stream_captures(&patt, range, |captures| substitution.expand(captures), on_window);
+        }))
+    }
+
+    fn compile_pattern(&self, search_for: &str) -> Result<Regex, SbyteError> {
+        let working_search = self.translate_pattern(search_for)?;
+        match Content::compile_pattern(&working_search) {
+            Ok(patt) => {
+                Ok(patt)
+            }
+            Err(_) => {
+                Err(SbyteError::InvalidRegex(search_for.to_string()))
+            }
+        }
+    }
+
+    // Turn the typed values, \b binary bytes and \x nibble wildcards that find_all takes into a regex the Content can search with
+    fn translate_pattern(&self, search_for: &str) -> Result<String, SbyteError> {
+        // Typed values, like u32le:0xDEADBEEF, are looked for as the bytes they're stored as
+        if let Some(result) = parse_typed_value(search_for) {
+            return match result {
+                Ok(bytes) if !bytes.is_empty() => {
+                    Ok(bytes.iter().map(|byte| format!("\\x{:02X}", byte)).collect())
+                }
+                Ok(_) => {
+                    // There's nothing to look for in an empty string
+                    let value = search_for.split_once(':').map(|(_, value)| value).unwrap_or_default();
+                    Err(SbyteError::InvalidTypedValue(TypedValueError::InvalidValue(value.to_string())))
+                }
+                Err(e) => {
+                    Err(SbyteError::InvalidTypedValue(e))
+                }
+            };
+        }
+
         let mut working_search = search_for.to_string();
 
         { // Look for binary byte definitions (\b) and translate them to \x
@@ -626,82 +1710,81 @@ impl Editor {
             }
         }
 
-        match self.active_content.find_all(&working_search) {
-            Ok(output) => {
-                Ok(output)
-            }
-            Err(_) => {
-                Err(SbyteError::InvalidRegex(search_for.to_string()))
-            }
-        }
+        Ok(working_search)
     }
 
-    pub fn find_nth_after(&self, pattern: &str, offset: usize, n: usize) -> Result<Option<(usize, usize)>, SbyteError> {
+    /// The nth match after `offset` among those within `range`, wrapping around to the start of it
+    pub fn find_nth_after(&self, pattern: &str, offset: usize, n: usize, range: (usize, usize)) -> Result<Option<(usize, usize)>, SbyteError> {
         //TODO: This could definitely be sped up.
-        let matches = self.find_all(pattern)?;
-        let mut match_index = 0;
-
-        if matches.len() > 0 {
-            for (i, (x, _)) in matches.iter().enumerate() {
-                if *x > offset {
-                    match_index = i;
-                    break;
-                }
-            }
-
-            match_index = (match_index + n) % matches.len();
-
-            Ok(Some(matches[match_index]))
-        } else {
-            Ok(None)
-        }
+        let matches = self.find_all_in_range(pattern, range)?;
+        Ok(nth_match_after(&matches, offset, n))
     }
 
-    pub fn find_nth_before(&self, pattern: &str, offset: usize, n: usize) -> Result<Option<(usize, usize)>, SbyteError> {
+    pub fn find_nth_before(&self, pattern: &str, offset: usize, n: usize, range: (usize, usize)) -> Result<Option<(usize, usize)>, SbyteError> {
         //TODO: This could definitely be sped up.
-        let matches = self.find_all(pattern)?;
+        let matches = self.find_all_in_range(pattern, range)?;
+        Ok(nth_match_before(&matches, offset, n))
+    }
 
-        if matches.len() > 0 {
-            let mut match_index = matches.len() - 1;
+    pub fn find_after(&self, pattern: &str, offset: usize) -> Result<Option<(usize, usize)>, SbyteError> {
+        self.find_nth_after(pattern, offset, 0, (0, self.len()))
+    }
 
-            for (i, (x, _)) in matches.iter().enumerate() {
-                if *x >= offset {
-                    break;
-                } else {
-                    match_index = i;
-                }
-            }
+    pub fn find_before(&self, pattern: &str, offset: usize) -> Result<Option<(usize, usize)>, SbyteError> {
+        self.find_nth_before(pattern, offset, 0, (0, self.len()))
+    }
 
-            match_index = (match_index + n) % matches.len();
+    /// Show `matches`, as find_all gives them, in their own colour until cleared
+    pub fn set_search_highlights(&mut self, matches: Vec<(usize, usize)>) {
+        self.search_highlights = matches;
+        self.refresh_buffer_display();
+    }
 
-            Ok(Some(matches[match_index]))
-        } else {
-            Ok(None)
+    pub fn clear_search_highlights(&mut self) {
+        if !self.search_highlights.is_empty() {
+            self.search_highlights.clear();
+            self.refresh_buffer_display();
         }
     }
 
-    pub fn find_after(&self, pattern: &str, offset: usize) -> Result<Option<(usize, usize)>, SbyteError> {
-        self.find_nth_after(pattern, offset, 0)
-    }
+    /// Which bytes in the range are part of a highlighted match
+    pub fn get_search_mask(&self, offset: usize, length: usize) -> Vec<bool> {
+        let mut output = Vec::new();
+        if self.search_highlights.is_empty() {
+            return output;
+        }
 
-    pub fn find_before(&self, pattern: &str, offset: usize) -> Result<Option<(usize, usize)>, SbyteError> {
-        self.find_nth_before(pattern, offset, 0)
+        output.resize(length, false);
+        // Matches don't overlap, so the first one ending past the offset is the first in range
+        let first = self.search_highlights.partition_point(|(_, end)| *end <= offset);
+        for (start, end) in self.search_highlights[first ..].iter() {
+            if *start >= offset + length {
+                break;
+            }
+            for i in max(*start, offset) .. min(*end, offset + length) {
+                output[i - offset] = true;
+            }
+        }
+
+        output
     }
 
-    pub fn remove_bytes(&mut self, offset: usize, length: usize) -> Vec<u8> {
+    pub fn remove_bytes(&mut self, offset: usize, length: usize) -> Result<Vec<u8>, SbyteError> {
+        self.check_editable(offset, length)?;
         let removed_bytes = self.active_content.remove_bytes(offset, length);
         self.push_to_undo_stack(offset, 0, removed_bytes.clone());
 
-        removed_bytes
+        Ok(removed_bytes)
     }
 
-    pub fn remove_bytes_at_cursor(&mut self) -> Vec<u8> {
+    pub fn remove_bytes_at_cursor(&mut self) -> Result<Vec<u8>, SbyteError> {
         let offset = self.cursor.get_offset();
         let length = self.cursor.get_length();
         self.remove_bytes(offset, length)
     }
 
     pub fn insert_bytes(&mut self, offset: usize, new_bytes: &[u8]) -> Result<(), SbyteError> {
+        self.check_editable(offset, 0)?;
         let adj_byte_width = new_bytes.len();
         self.active_content.insert_bytes(offset, new_bytes)?;
 
@@ -715,6 +1798,7 @@ impl Editor {
         let offset = self.get_cursor_offset() + (self.get_subcursor_offset() / self.get_subcursor_length());
 
         let subcursor_real_position = self.subcursor.get_length() - 1 - (self.subcursor.get_offset() % self.subcursor.get_length());
+        self.check_editable(offset, 1)?;
         match digit.to_digit(radix as u32) {
             Some(value) => {
                 let old_byte = self.active_content.replace_digit(offset, subcursor_real_position as u8, value as u8, radix as u8)?;
@@ -729,6 +1813,7 @@ impl Editor {
 
     pub fn overwrite_bytes(&mut self, position: usize, new_bytes: &[u8]) -> Result<Vec<u8>, SbyteError> {
         let length = new_bytes.len();
+        self.check_editable(position, length)?;
         let removed_bytes = self.active_content.remove_bytes(position, length);
 
         self.active_content.insert_bytes(position, new_bytes)?;
@@ -737,6 +1822,17 @@ impl Editor {
         Ok(removed_bytes)
     }
 
+    /// Replace the `length` bytes at `offset` with `new_bytes`, which can be any length, as one undo step
+    pub fn replace_range(&mut self, offset: usize, length: usize, new_bytes: &[u8]) -> Result<Vec<u8>, SbyteError> {
+        self.check_editable(offset, length)?;
+        let removed_bytes = self.active_content.remove_bytes(offset, length);
+
+        self.active_content.insert_bytes(offset, new_bytes)?;
+        self.push_to_undo_stack(offset, new_bytes.len(), removed_bytes.clone());
+
+        Ok(removed_bytes)
+    }
+
     pub fn insert_bytes_at_cursor(&mut self, new_bytes: &[u8]) -> Result<(), SbyteError> {
         let position = self.cursor.get_offset();
         self.insert_bytes(position, new_bytes)
@@ -966,6 +2062,14 @@ impl Editor {
         self.set_viewport_size(new_width, height);
     }
 
+    pub fn set_fixed_viewport_width(&mut self, width: Option<usize>) {
+        self.fixed_viewport_width = width;
+    }
+
+    pub fn get_fixed_viewport_width(&self) -> Option<usize> {
+        self.fixed_viewport_width
+    }
+
     pub fn set_viewport_offset(&mut self, new_offset: usize) {
         self.viewport.set_offset(new_offset);
     }
@@ -1027,6 +2131,7 @@ impl Editor {
         }
 
         let offset = self.get_cursor_offset();
+        self.check_editable(offset, new_mask.len())?;
         let old_bytes = self.active_content.apply_mask(offset, &new_mask, operation)?;
         self.push_to_undo_stack(offset, new_mask.len(), old_bytes);
 
@@ -1058,6 +2163,47 @@ impl Editor {
     }
 }
 
+/// Of the matches, in order, the nth after the first to start past `offset`, wrapping around to the first
+pub fn nth_match_after(matches: &[(usize, usize)], offset: usize, n: usize) -> Option<(usize, usize)> {
+    let mut match_index = 0;
+
+    if !matches.is_empty() {
+        for (i, (x, _)) in matches.iter().enumerate() {
+            if *x > offset {
+                match_index = i;
+                break;
+            }
+        }
+
+        match_index = (match_index + n) % matches.len();
+
+        Some(matches[match_index])
+    } else {
+        None
+    }
+}
+
+/// Of the matches, in order, the nth after the last to start before `offset`, wrapping around to the last
+pub fn nth_match_before(matches: &[(usize, usize)], offset: usize, n: usize) -> Option<(usize, usize)> {
+    if !matches.is_empty() {
+        let mut match_index = matches.len() - 1;
+
+        for (i, (x, _)) in matches.iter().enumerate() {
+            if *x >= offset {
+                break;
+            } else {
+                match_index = i;
+            }
+        }
+
+        match_index = (match_index + n) % matches.len();
+
+        Some(matches[match_index])
+    } else {
+        None
+    }
+}
+
 /// Takes strings input within the program and parses the words.
 pub fn parse_words(input_string: &str) -> Vec<String> {
     let mut output = Vec::new();
@@ -0,0 +1,403 @@
+use std::cmp::max;
+use std::io::{self, Read, Write};
+use std::fs::File;
+use std::path::Path;
+
+// Data bytes written per record
+const RECORD_LENGTH: usize = 16;
+// Neither format can address past 32 bits
+const ADDRESS_LIMIT: usize = 0x1_0000_0000;
+
+// Data records by their address, and the start address
+type Records = (Vec<(usize, Vec<u8>)>, Option<u32>);
+/// Bytes and the address they start at
+pub type Run = (usize, Vec<u8>);
+
+/// How a file's bytes are stored on disk
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum FileFormat {
+    Raw,
+    IntelHex,
+    SRecord
+}
+
+impl FileFormat {
+    pub fn from_name(name: &str) -> Option<FileFormat> {
+        match name.to_lowercase().as_str() {
+            "raw" | "bin" => Some(FileFormat::Raw),
+            "ihex" | "hex" => Some(FileFormat::IntelHex),
+            "srec" => Some(FileFormat::SRecord),
+            _ => None
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            FileFormat::Raw => "raw",
+            FileFormat::IntelHex => "ihex",
+            FileFormat::SRecord => "srec"
+        }
+    }
+
+    /// The format implied by the extension of `path`, if it's one we know
+    pub fn from_extension(path: &str) -> Option<FileFormat> {
+        let extension = Path::new(path).extension()?.to_string_lossy().to_lowercase();
+        match extension.as_str() {
+            "bin" | "raw" | "img" => Some(FileFormat::Raw),
+            "hex" | "ihex" | "ihx" => Some(FileFormat::IntelHex),
+            "srec" | "s19" | "s28" | "s37" | "mot" => Some(FileFormat::SRecord),
+            _ => None
+        }
+    }
+
+    /// Guess the format of the file at `path` from its extension, or failing that, its first line
+    pub fn detect(path: &str) -> FileFormat {
+        if let Some(format) = FileFormat::from_extension(path) {
+            return format;
+        }
+
+        let mut head = Vec::new();
+        if let Ok(file) = File::open(path) {
+            file.take(600).read_to_end(&mut head).ok();
+        }
+        let head = String::from_utf8_lossy(&head);
+        let first_line = head.trim_start().lines().next().unwrap_or("").trim();
+
+        if parse_intel_hex_line(first_line).is_some() {
+            FileFormat::IntelHex
+        } else if parse_srecord_line(first_line).is_some() {
+            FileFormat::SRecord
+        } else {
+            FileFormat::Raw
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum RecordError {
+    InvalidLine(usize),
+    BadChecksum(usize),
+    UnknownRecord(usize),
+    // The content wouldn't fit below the 32 bit address limit
+    AddressTooLarge
+}
+
+/// Bytes decoded from records, as the runs of addresses the records cover
+pub struct Image {
+    // In order of address, without overlapping or touching
+    pub runs: Vec<Run>,
+    pub base_address: usize,
+    // Where execution starts, if the file says
+    pub start_address: Option<u32>
+}
+
+/// Decode the records in `input`. Gaps between them are left out rather than filled, since they can be huge.
+/// Later records overwrite earlier ones at the same address.
+pub fn decode(format: FileFormat, input: &[u8]) -> Result<Image, RecordError> {
+    let text = String::from_utf8_lossy(input);
+    let (chunks, start_address) = match format {
+        FileFormat::Raw => {
+            return Ok(Image {
+                runs: vec![(0, input.to_vec())],
+                base_address: 0,
+                start_address: None
+            });
+        }
+        FileFormat::IntelHex => decode_intel_hex(&text)?,
+        FileFormat::SRecord => decode_srecord(&text)?
+    };
+
+    // Lay out the runs first, so the records can be copied in the order they came
+    let mut spans: Vec<(usize, usize)> = chunks.iter().map(|(address, data)| (*address, address + data.len())).collect();
+    spans.sort();
+    let mut runs: Vec<Run> = Vec::new();
+    for (address, end) in spans.into_iter() {
+        match runs.last_mut() {
+            Some((run_address, data)) if address <= *run_address + data.len() => {
+                let length = max(data.len(), end - *run_address);
+                data.resize(length, 0);
+            }
+            _ => {
+                runs.push((address, vec![0; end - address]));
+            }
+        }
+    }
+
+    for (address, data) in chunks.iter() {
+        let index = runs.partition_point(|(run_address, _)| run_address <= address) - 1;
+        let (run_address, run) = &mut runs[index];
+        let offset = address - *run_address;
+        run[offset .. offset + data.len()].copy_from_slice(data);
+    }
+    runs.retain(|(_, data)| !data.is_empty());
+
+    Ok(Image {
+        base_address: runs.first().map(|(address, _)| *address).unwrap_or(0),
+        runs,
+        start_address
+    })
+}
+
+/// Check that `length` bytes starting at `base_address` can be written in `format`
+pub fn check_fits(format: FileFormat, base_address: usize, length: usize) -> Result<(), RecordError> {
+    if format != FileFormat::Raw && base_address + length > ADDRESS_LIMIT {
+        Err(RecordError::AddressTooLarge)
+    } else {
+        Ok(())
+    }
+}
+
+/// Write `runs` as records in `format`, leaving out the gaps between them
+pub fn encode<W: Write>(format: FileFormat, runs: &[Run], start_address: Option<u32>, writer: &mut W) -> io::Result<()> {
+    match format {
+        FileFormat::Raw => {
+            for (_, data) in runs.iter() {
+                writer.write_all(data)?;
+            }
+            Ok(())
+        }
+        FileFormat::IntelHex => encode_intel_hex(runs, start_address, writer),
+        FileFormat::SRecord => encode_srecord(runs, start_address, writer)
+    }
+}
+
+// The bytes of a line of hex digits, if that's all it is
+fn hex_to_bytes(digits: &str) -> Option<Vec<u8>> {
+    if digits.len() % 2 == 1 || !digits.is_ascii() {
+        return None;
+    }
+
+    let mut output = Vec::new();
+    for i in (0 .. digits.len()).step_by(2) {
+        output.push(u8::from_str_radix(&digits[i .. i + 2], 16).ok()?);
+    }
+
+    Some(output)
+}
+
+fn bytes_to_hex(bytes: &[u8]) -> String {
+    let mut output = String::new();
+    for byte in bytes.iter() {
+        output += &format!("{:02X}", byte);
+    }
+
+    output
+}
+
+fn be_value(bytes: &[u8]) -> usize {
+    let mut value = 0;
+    for byte in bytes.iter() {
+        value = (value << 8) + *byte as usize;
+    }
+
+    value
+}
+
+// Record type, address and data of a line of Intel HEX, and whether its checksum holds
+fn parse_intel_hex_line(line: &str) -> Option<(u8, usize, Vec<u8>, bool)> {
+    let bytes = hex_to_bytes(line.strip_prefix(':')?)?;
+    if bytes.len() < 5 || bytes.len() != bytes[0] as usize + 5 {
+        return None;
+    }
+
+    let checksum_ok = bytes.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte)) == 0;
+    Some((bytes[3], be_value(&bytes[1 .. 3]), bytes[4 .. bytes.len() - 1].to_vec(), checksum_ok))
+}
+
+fn decode_intel_hex(text: &str) -> Result<Records, RecordError> {
+    let mut chunks = Vec::new();
+    let mut start_address = None;
+    // Added to the address of each data record, set by extended address records
+    let mut upper_address = 0;
+
+    for (i, line) in text.lines().enumerate() {
+        let line_number = i + 1;
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        let (record_type, address, data, checksum_ok) = match parse_intel_hex_line(line) {
+            Some(record) => record,
+            None => {
+                return Err(RecordError::InvalidLine(line_number));
+            }
+        };
+        if !checksum_ok {
+            Err(RecordError::BadChecksum(line_number))?;
+        }
+
+        match (record_type, data.len()) {
+            (0x00, _) => {
+                chunks.push((upper_address + address, data));
+            }
+            (0x01, _) => {
+                break;
+            }
+            (0x02, 2) => {
+                upper_address = be_value(&data) << 4;
+            }
+            (0x04, 2) => {
+                upper_address = be_value(&data) << 16;
+            }
+            // Start segment addresses only matter to real mode x86, so aren't kept
+            (0x03, 4) => (),
+            (0x05, 4) => {
+                start_address = Some(be_value(&data) as u32);
+            }
+            (0x02 ..= 0x05, _) => {
+                Err(RecordError::InvalidLine(line_number))?;
+            }
+            _ => {
+                Err(RecordError::UnknownRecord(line_number))?;
+            }
+        }
+    }
+
+    Ok((chunks, start_address))
+}
+
+fn write_intel_hex_record<W: Write>(writer: &mut W, record_type: u8, address: usize, data: &[u8]) -> io::Result<()> {
+    let mut record = vec![data.len() as u8, (address >> 8) as u8, address as u8, record_type];
+    record.extend_from_slice(data);
+    let sum = record.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte));
+    record.push(sum.wrapping_neg());
+
+    writeln!(writer, ":{}", bytes_to_hex(&record))
+}
+
+fn encode_intel_hex<W: Write>(runs: &[Run], start_address: Option<u32>, writer: &mut W) -> io::Result<()> {
+    let mut upper_address = 0;
+    for (base_address, bytes) in runs.iter() {
+        let mut offset = 0;
+        while offset < bytes.len() {
+            let address = base_address + offset;
+            if address >> 16 != upper_address {
+                upper_address = address >> 16;
+                write_intel_hex_record(writer, 0x04, 0, &[(upper_address >> 8) as u8, upper_address as u8])?;
+            }
+
+            // A record can't cross into the next 64K
+            let length = RECORD_LENGTH.min(bytes.len() - offset).min(0x10000 - (address & 0xFFFF));
+            write_intel_hex_record(writer, 0x00, address & 0xFFFF, &bytes[offset .. offset + length])?;
+            offset += length;
+        }
+    }
+
+    if let Some(start_address) = start_address {
+        write_intel_hex_record(writer, 0x05, 0, &start_address.to_be_bytes())?;
+    }
+    write_intel_hex_record(writer, 0x01, 0, &[])
+}
+
+// Width of the address in each type of S-record
+fn srecord_address_width(record_type: u8) -> Option<usize> {
+    match record_type {
+        0 | 1 | 5 | 9 => Some(2),
+        2 | 6 | 8 => Some(3),
+        3 | 7 => Some(4),
+        _ => None
+    }
+}
+
+// Record type, address and data of an S-record, and whether its checksum holds
+fn parse_srecord_line(line: &str) -> Option<(u8, usize, Vec<u8>, bool)> {
+    let rest = line.strip_prefix('S')?;
+    let record_type = rest.get(0 .. 1)?.parse::<u8>().ok()?;
+    let bytes = hex_to_bytes(rest.get(1 ..)?)?;
+    let address_width = srecord_address_width(record_type)?;
+    if bytes.len() < address_width + 2 || bytes.len() != bytes[0] as usize + 1 {
+        return None;
+    }
+
+    let checksum_ok = bytes.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte)) == 0xFF;
+    let address = be_value(&bytes[1 .. 1 + address_width]);
+    Some((record_type, address, bytes[1 + address_width .. bytes.len() - 1].to_vec(), checksum_ok))
+}
+
+fn decode_srecord(text: &str) -> Result<Records, RecordError> {
+    let mut chunks = Vec::new();
+    let mut start_address = None;
+
+    for (i, line) in text.lines().enumerate() {
+        let line_number = i + 1;
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        let (record_type, address, data, checksum_ok) = match parse_srecord_line(line) {
+            Some(record) => record,
+            None => {
+                let record_type = line.strip_prefix('S').and_then(|rest| rest.get(0 .. 1)).and_then(|digit| digit.parse::<u8>().ok());
+                match record_type {
+                    Some(record_type) if srecord_address_width(record_type).is_none() => {
+                        return Err(RecordError::UnknownRecord(line_number));
+                    }
+                    _ => {
+                        return Err(RecordError::InvalidLine(line_number));
+                    }
+                }
+            }
+        };
+        if !checksum_ok {
+            Err(RecordError::BadChecksum(line_number))?;
+        }
+
+        match record_type {
+            1 ..= 3 => {
+                chunks.push((address, data));
+            }
+            // A start address of zero is the usual way of not giving one
+            7 ..= 9 if address != 0 => {
+                start_address = Some(address as u32);
+            }
+            // Headers and record counts
+            _ => ()
+        }
+    }
+
+    Ok((chunks, start_address))
+}
+
+fn write_srecord<W: Write>(writer: &mut W, record_type: u8, address: usize, data: &[u8]) -> io::Result<()> {
+    let address_width = srecord_address_width(record_type).unwrap_or(4);
+    let mut record = vec![(address_width + data.len() + 1) as u8];
+    for i in (0 .. address_width).rev() {
+        record.push((address >> (i * 8)) as u8);
+    }
+    record.extend_from_slice(data);
+    let sum = record.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte));
+    record.push(!sum);
+
+    writeln!(writer, "S{}{}", record_type, bytes_to_hex(&record))
+}
+
+fn encode_srecord<W: Write>(runs: &[Run], start_address: Option<u32>, writer: &mut W) -> io::Result<()> {
+    // The narrowest records that reach every address
+    let end = runs.last().map(|(address, data)| address + data.len()).unwrap_or(0);
+    let highest = end.max(start_address.unwrap_or(0) as usize + 1);
+    let (data_type, end_type) = if highest <= 0x1_0000 {
+        (1, 9)
+    } else if highest <= 0x100_0000 {
+        (2, 8)
+    } else {
+        (3, 7)
+    };
+
+    write_srecord(writer, 0, 0, &[])?;
+    let mut record_count = 0;
+    for (base_address, bytes) in runs.iter() {
+        for (i, chunk) in bytes.chunks(RECORD_LENGTH).enumerate() {
+            write_srecord(writer, data_type, base_address + i * RECORD_LENGTH, chunk)?;
+            record_count += 1;
+        }
+    }
+
+    if record_count <= 0xFFFF {
+        write_srecord(writer, 5, record_count, &[])?;
+    } else {
+        write_srecord(writer, 6, record_count, &[])?;
+    }
+    write_srecord(writer, end_type, start_address.unwrap_or(0) as usize, &[])
+}
@@ -0,0 +1,103 @@
+use std::fs::{self, File};
+use std::io::{self, Write, BufWriter};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// What to keep of a file's previous version when it's overwritten
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Backup {
+    Off,
+    // file~
+    Simple,
+    // file.~1~, file.~2~, ...
+    Numbered
+}
+
+/// Modification time and size of a file, to tell if it's been changed by something else
+pub type FileStamp = (Option<SystemTime>, u64);
+
+pub fn get_file_stamp(path: &str) -> Option<FileStamp> {
+    let metadata = fs::metadata(path).ok()?;
+    Some((metadata.modified().ok(), metadata.len()))
+}
+
+/// Write a file through a temporary one beside it, so the file at `path` is either
+/// the old version or the complete new one, even if something goes wrong part way.
+pub fn write_atomic<F>(path: &str, backup: Backup, write: F) -> io::Result<()>
+where F: FnOnce(&mut BufWriter<File>) -> io::Result<()> {
+    // Write through symlinks rather than replacing them
+    let target = match fs::canonicalize(path) {
+        Ok(target) => target,
+        Err(_) => PathBuf::from(path)
+    };
+    let previous = fs::metadata(&target).ok();
+
+    let tmp_path = tmp_path(&target);
+    let result = write_synced(&tmp_path, write).and_then(|_| {
+        if let Some(metadata) = &previous {
+            fs::set_permissions(&tmp_path, metadata.permissions())?;
+            if let Some(backup_path) = backup_path(&target, backup) {
+                fs::copy(&target, backup_path)?;
+            }
+        }
+
+        fs::rename(&tmp_path, &target)
+    });
+
+    if result.is_err() {
+        fs::remove_file(&tmp_path).ok();
+    } else if let Some(parent) = target.parent() {
+        // Make the rename itself durable. Not every platform can open a directory for this.
+        if let Ok(directory) = File::open(parent) {
+            directory.sync_all().ok();
+        }
+    }
+
+    result
+}
+
+fn write_synced<F>(path: &Path, write: F) -> io::Result<()>
+where F: FnOnce(&mut BufWriter<File>) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    write(&mut writer)?;
+    writer.flush()?;
+
+    match writer.into_inner() {
+        Ok(file) => file.sync_all(),
+        Err(e) => Err(e.into_error())
+    }
+}
+
+fn tmp_path(target: &Path) -> PathBuf {
+    let mut name = target.file_name().unwrap_or_default().to_os_string();
+    name.push(".sbyte-tmp");
+    target.with_file_name(name)
+}
+
+/// Where the previous version of the file at `target` is kept, if anywhere
+pub fn backup_path(target: &Path, backup: Backup) -> Option<PathBuf> {
+    let name = target.file_name()?.to_string_lossy().to_string();
+    match backup {
+        Backup::Off => None,
+        Backup::Simple => {
+            Some(target.with_file_name(format!("{}~", name)))
+        }
+        Backup::Numbered => {
+            // One past the highest numbered backup already there
+            let prefix = format!("{}.~", name);
+            let mut highest = 0;
+            if let Ok(entries) = fs::read_dir(target.parent()?) {
+                for entry in entries.flatten() {
+                    let entry_name = entry.file_name().to_string_lossy().to_string();
+                    if let Some(number) = entry_name.strip_prefix(&prefix).and_then(|rest| rest.strip_suffix('~')) {
+                        if let Ok(number) = number.parse::<usize>() {
+                            highest = highest.max(number);
+                        }
+                    }
+                }
+            }
+
+            Some(target.with_file_name(format!("{}{}~", prefix, highest + 1)))
+        }
+    }
+}
@@ -0,0 +1,123 @@
+// Searches run on a worker thread over a snapshot of the content, so the editor keeps drawing and can call them off
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::thread;
+
+use super::content::Content;
+
+/// Where a hit starts and ends, and whatever the search made of it
+pub type Hit<T> = (usize, usize, T);
+
+// The hits in a window, in order, and the offset searched up to
+type WindowHits<T> = (Vec<Hit<T>>, usize);
+
+pub struct SearchJob<T> {
+    receiver: Receiver<WindowHits<T>>,
+    cancelled: Arc<AtomicBool>,
+    range: (usize, usize),
+    searched_to: usize,
+    hits: Vec<Hit<T>>,
+    finished: bool
+}
+
+impl<T: Send + 'static> SearchJob<T> {
+    /// Run `search` over `content` on a thread of its own. It's handed a callback to give the hits of each window to,
+    /// which says whether to keep going.
+    pub fn spawn<F>(content: Content, range: (usize, usize), search: F) -> SearchJob<T>
+    where F: FnOnce(&Content, &mut dyn FnMut(Vec<Hit<T>>, usize) -> bool) + Send + 'static {
+        let (sender, receiver) = mpsc::channel();
+        let cancelled = Arc::new(AtomicBool::new(false));
+
+        let worker_cancelled = cancelled.clone();
+        thread::spawn(move || {
+            search(&content, &mut |hits, searched_to| {
+                // Nobody is waiting on the hits once the job is dropped
+                sender.send((hits, searched_to)).is_ok() && !worker_cancelled.load(Ordering::Relaxed)
+            });
+        });
+
+        SearchJob {
+            receiver,
+            cancelled,
+            range,
+            searched_to: range.0,
+            hits: vec![],
+            finished: false
+        }
+    }
+}
+
+impl<T> SearchJob<T> {
+    /// Take in whatever the worker has found since the last poll. Returns whether anything came in.
+    pub fn poll(&mut self) -> bool {
+        let mut changed = false;
+        loop {
+            match self.receiver.try_recv() {
+                Ok(window) => {
+                    self.take_window(window);
+                    changed = true;
+                }
+                Err(TryRecvError::Empty) => {
+                    break;
+                }
+                Err(TryRecvError::Disconnected) => {
+                    self.finished = true;
+                    changed = true;
+                    break;
+                }
+            }
+        }
+
+        changed
+    }
+
+    /// Block until the worker has searched another window, or is done
+    pub fn wait(&mut self) {
+        match self.receiver.recv() {
+            Ok(window) => {
+                self.take_window(window);
+            }
+            Err(_) => {
+                self.finished = true;
+            }
+        }
+    }
+
+    fn take_window(&mut self, window: WindowHits<T>) {
+        let (hits, searched_to) = window;
+        self.hits.extend(hits);
+        self.searched_to = searched_to;
+    }
+
+    /// Have the worker stop after the window it's on. Hits already found are kept.
+    pub fn cancel(&mut self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    /// How much of the range has been searched, out of 100
+    pub fn get_percent(&self) -> usize {
+        let length = self.range.1.saturating_sub(self.range.0);
+        (self.searched_to.saturating_sub(self.range.0) * 100).checked_div(length).unwrap_or(100)
+    }
+
+    /// The hits found so far, in order
+    pub fn get_hits(&self) -> &[Hit<T>] {
+        &self.hits
+    }
+
+    pub fn into_hits(mut self) -> Vec<Hit<T>> {
+        std::mem::take(&mut self.hits)
+    }
+}
+
+impl<T> Drop for SearchJob<T> {
+    fn drop(&mut self) {
+        self.cancel();
+    }
+}
@@ -111,7 +111,7 @@ impl LittleEndianPrefixed {
 
     pub fn decode_prefix(prefix: Vec<u8>) -> usize {
         let mut data_width = 0usize;
-        for n in prefix.iter() {
+        for n in prefix.iter().rev() {
             data_width *= 256;
             data_width += *n as usize;
         }
@@ -160,7 +160,7 @@ impl StructuredDataHandler for LittleEndianPrefixed {
         let mut data_width = 0;
         let total_length = inbytes.len();
 
-        while total_length != prefix_width + data_width {
+        while total_length != prefix_width + data_width && prefix_width < total_length {
             prefix_width += 1;
             data_width = LittleEndianPrefixed::decode_prefix(inbytes[0..prefix_width].to_vec());
         }
@@ -206,6 +206,20 @@ impl VariableLengthPrefixed {
             data: data
         }
     }
+
+    /// Get the width of the prefix at the start of `bytes` and the length it encodes
+    pub fn decode_prefix(bytes: &[u8]) -> Option<(usize, usize)> {
+        let mut data_width: usize = 0;
+        for (i, byte) in bytes.iter().enumerate() {
+            data_width = data_width.checked_mul(128)? + (*byte & 0x7F) as usize;
+            if *byte & 0x80 == 0 {
+                return Some((i + 1, data_width));
+            }
+        }
+
+        None
+    }
+
     pub fn build_prefix(&self) -> Vec<u8> {
         let mut output = Vec::new();
         let mut working_number = self.data.len();
@@ -0,0 +1,135 @@
+// What replaces each match of a search, which can bring in what the pattern's capture groups matched
+
+use regex::bytes::Captures;
+
+use super::{SbyteError, string_to_bytes};
+
+/// Where a match starts and ends, and the bytes going in its place
+pub type Replacement = (usize, usize, Vec<u8>);
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+enum Part {
+    Bytes(Vec<u8>),
+    Group(usize),
+    NamedGroup(String)
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Substitution {
+    parts: Vec<Part>
+}
+
+impl Substitution {
+    /// Put `bytes` in place of every match, as they are
+    pub fn literal(bytes: &[u8]) -> Substitution {
+        Substitution {
+            parts: vec![Part::Bytes(bytes.to_vec())]
+        }
+    }
+
+    /// Read a replacement as REPLACE_ALL takes it. `$1` or `${1}` stands for what the first group matched,
+    /// `${name}` for what the group named `name` did and `$$` for a dollar sign.
+    /// The text in between is read as string_to_bytes reads it, so `\x00$1` is a zero byte then the group.
+    pub fn parse(input: &str) -> Result<Substitution, SbyteError> {
+        let mut parts = vec![];
+        let mut text = String::new();
+        let mut chars = input.chars().peekable();
+        while let Some(c) = chars.next() {
+            if c != '$' {
+                text.push(c);
+                continue;
+            }
+
+            let part = match chars.peek() {
+                Some('$') => {
+                    chars.next();
+                    Part::Bytes(b"$".to_vec())
+                }
+                Some('0' ..= '9') => {
+                    let mut digits = String::new();
+                    while let Some(digit @ '0' ..= '9') = chars.peek() {
+                        digits.push(*digit);
+                        chars.next();
+                    }
+                    Part::Group(parse_group_number(&digits, input)?)
+                }
+                Some('{') => {
+                    chars.next();
+                    let mut name = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => {
+                                break;
+                            }
+                            Some(c) => {
+                                name.push(c);
+                            }
+                            None => {
+                                return Err(SbyteError::InvalidSubstitution(input.to_string()));
+                            }
+                        }
+                    }
+
+                    if name.is_empty() {
+                        return Err(SbyteError::InvalidSubstitution(input.to_string()));
+                    } else if name.chars().all(|c| c.is_ascii_digit()) {
+                        Part::Group(parse_group_number(&name, input)?)
+                    } else {
+                        Part::NamedGroup(name)
+                    }
+                }
+                // A dollar sign that doesn't name a group is kept as it is
+                _ => {
+                    text.push(c);
+                    continue;
+                }
+            };
+
+            push_text(&mut parts, &mut text)?;
+            parts.push(part);
+        }
+        push_text(&mut parts, &mut text)?;
+
+        Ok(Substitution { parts })
+    }
+
+    /// The bytes to put in place of a match. Groups that didn't take part in it, or that the pattern doesn't have, are left out.
+    pub fn expand(&self, captures: &Captures) -> Vec<u8> {
+        let mut output = vec![];
+        for part in self.parts.iter() {
+            match part {
+                Part::Bytes(bytes) => {
+                    output.extend_from_slice(bytes);
+                }
+                Part::Group(index) => {
+                    if let Some(group) = captures.get(*index) {
+                        output.extend_from_slice(group.as_bytes());
+                    }
+                }
+                Part::NamedGroup(name) => {
+                    if let Some(group) = captures.name(name) {
+                        output.extend_from_slice(group.as_bytes());
+                    }
+                }
+            }
+        }
+
+        output
+    }
+}
+
+fn parse_group_number(digits: &str, input: &str) -> Result<usize, SbyteError> {
+    match digits.parse() {
+        Ok(index) => Ok(index),
+        Err(_) => Err(SbyteError::InvalidSubstitution(input.to_string()))
+    }
+}
+
+fn push_text(parts: &mut Vec<Part>, text: &mut String) -> Result<(), SbyteError> {
+    if !text.is_empty() {
+        parts.push(Part::Bytes(string_to_bytes(text)?));
+        text.clear();
+    }
+
+    Ok(())
+}
@@ -0,0 +1,579 @@
+use std::convert::TryFrom;
+use std::collections::HashMap;
+
+use super::string_to_integer;
+use super::structured::{BigEndianPrefixed, LittleEndianPrefixed, VariableLengthPrefixed};
+
+// Evaluation stops at these, so a bad count or a recursive struct can't hang the editor
+const MAX_DEPTH: usize = 32;
+const MAX_FIELDS: usize = 100_000;
+// How many bytes of a byte array or blob are shown as its value
+const PREVIEW_LENGTH: usize = 16;
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum TemplateError {
+    InvalidLine(usize),
+    UnknownType(usize, String),
+    UnknownCount(usize, String),
+    UnknownStruct(String),
+    DuplicateStruct(usize, String),
+    NoStructs
+}
+
+/// How the length of a blob is stored in front of it
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Prefix {
+    BigEndian(usize),
+    LittleEndian(usize),
+    VariableLength
+}
+
+impl Prefix {
+    /// Read the prefix at the start of `bytes`, giving the prefix's width and the length it encodes.
+    fn read(&self, bytes: &[u8]) -> Option<(usize, usize)> {
+        match self {
+            Prefix::BigEndian(width) => {
+                let prefix = bytes.get(0 .. *width)?;
+                Some((*width, BigEndianPrefixed::decode_prefix(prefix.to_vec())))
+            }
+            Prefix::LittleEndian(width) => {
+                let prefix = bytes.get(0 .. *width)?;
+                Some((*width, LittleEndianPrefixed::decode_prefix(prefix.to_vec())))
+            }
+            Prefix::VariableLength => {
+                VariableLengthPrefixed::decode_prefix(bytes)
+            }
+        }
+    }
+
+    fn max_width(&self) -> usize {
+        match self {
+            Prefix::BigEndian(width) | Prefix::LittleEndian(width) => *width,
+            // Enough 7-bit groups for any usize
+            Prefix::VariableLength => 10
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+enum FieldType {
+    // Width in bytes, is big endian
+    Unsigned(usize, bool),
+    Signed(usize, bool),
+    Byte,
+    Char,
+    Blob(Prefix),
+    Struct(String)
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+enum Count {
+    Fixed(usize),
+    Field(String)
+}
+
+#[derive(Debug, Clone)]
+struct FieldDefinition {
+    name: String,
+    field_type: FieldType,
+    count: Option<Count>
+}
+
+/// A field of a template as laid over the content
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct TemplateField {
+    pub name: String,
+    pub depth: usize,
+    pub offset: usize,
+    pub length: usize,
+    // Structs and arrays of them have no value of their own
+    pub value: Option<String>
+}
+
+/// Describes a binary format as a set of structs, the first of which is laid over the content.
+///
+/// ```text
+/// # comments run to the end of the line
+/// struct header {
+///     magic: char[4]
+///     count: u16le
+///     entries: entry[count]
+///     name: blob:vlq
+/// }
+///
+/// struct entry {
+///     id: u8
+///     value: i32be
+/// }
+/// ```
+/// Integers are u8 .. u64 and i8 .. i64 with an optional le/be suffix (little endian by default).
+/// `byte` and `char` arrays are shown as a single field. Blobs are prefixed by their length,
+/// either as a fixed width integer (`blob:be2`, `blob:le4`) or a variable length quantity (`blob:vlq`).
+/// Array lengths are either a number or the name of an integer field earlier in the same struct.
+pub struct Template {
+    root: String,
+    structs: HashMap<String, Vec<FieldDefinition>>
+}
+
+impl Template {
+    pub fn parse(input: &str) -> Result<Template, TemplateError> {
+        let mut root = None;
+        let mut structs: HashMap<String, Vec<FieldDefinition>> = HashMap::new();
+        let mut current_struct: Option<(String, Vec<FieldDefinition>)> = None;
+
+        for (i, raw_line) in input.lines().enumerate() {
+            let line_number = i + 1;
+            let line = match raw_line.find('#') {
+                Some(index) => &raw_line[0 .. index],
+                None => raw_line
+            }.trim();
+
+            if line.is_empty() {
+                continue;
+            }
+
+            match current_struct.take() {
+                Some((name, fields)) => {
+                    if line == "}" {
+                        structs.insert(name, fields);
+                    } else {
+                        let field = Template::parse_field(line, line_number, &fields)?;
+                        let mut fields = fields;
+                        fields.push(field);
+                        current_struct = Some((name, fields));
+                    }
+                }
+                None => {
+                    let words: Vec<&str> = line.split_whitespace().collect();
+                    if words.len() != 3 || words[0] != "struct" || words[2] != "{" || !is_identifier(words[1]) {
+                        Err(TemplateError::InvalidLine(line_number))?;
+                    }
+
+                    let name = words[1].to_string();
+                    if structs.contains_key(&name) {
+                        Err(TemplateError::DuplicateStruct(line_number, name.clone()))?;
+                    }
+
+                    if root.is_none() {
+                        root = Some(name.clone());
+                    }
+                    current_struct = Some((name, Vec::new()));
+                }
+            }
+        }
+
+        if current_struct.is_some() {
+            Err(TemplateError::InvalidLine(input.lines().count()))?;
+        }
+
+        for fields in structs.values() {
+            for field in fields.iter() {
+                if let FieldType::Struct(name) = &field.field_type {
+                    if !structs.contains_key(name) {
+                        Err(TemplateError::UnknownStruct(name.clone()))?;
+                    }
+                }
+            }
+        }
+
+        match root {
+            Some(root) => {
+                Ok(Template { root, structs })
+            }
+            None => {
+                Err(TemplateError::NoStructs)
+            }
+        }
+    }
+
+    fn parse_field(line: &str, line_number: usize, previous_fields: &[FieldDefinition]) -> Result<FieldDefinition, TemplateError> {
+        let (name, type_string) = match line.split_once(':') {
+            Some((name, type_string)) => (name.trim(), type_string.trim()),
+            None => {
+                return Err(TemplateError::InvalidLine(line_number));
+            }
+        };
+
+        if !is_identifier(name) {
+            Err(TemplateError::InvalidLine(line_number))?;
+        }
+
+        let (type_name, count) = match type_string.split_once('[') {
+            Some((type_name, count_string)) => {
+                let count_string = match count_string.strip_suffix(']') {
+                    Some(count_string) => count_string.trim(),
+                    None => {
+                        return Err(TemplateError::InvalidLine(line_number));
+                    }
+                };
+
+                let count = match string_to_integer(count_string) {
+                    Ok(n) => {
+                        Count::Fixed(n)
+                    }
+                    Err(_) => {
+                        let is_integer_field = previous_fields.iter().any(|field| {
+                            field.name == count_string
+                                && field.count.is_none()
+                                && matches!(field.field_type, FieldType::Unsigned(_, _) | FieldType::Signed(_, _))
+                        });
+
+                        if !is_integer_field {
+                            Err(TemplateError::UnknownCount(line_number, count_string.to_string()))?;
+                        }
+                        Count::Field(count_string.to_string())
+                    }
+                };
+
+                (type_name.trim(), Some(count))
+            }
+            None => {
+                (type_string, None)
+            }
+        };
+
+        let field_type = match parse_type(type_name) {
+            Some(field_type) => field_type,
+            None => {
+                return Err(TemplateError::UnknownType(line_number, type_name.to_string()));
+            }
+        };
+
+        Ok(FieldDefinition {
+            name: name.to_string(),
+            field_type,
+            count
+        })
+    }
+
+    /// Lay the template over `length` bytes starting at `offset`, with `read` providing the bytes.
+    /// Fields are listed in order, each struct before its members. Evaluation stops at the first
+    /// field that doesn't fit.
+    pub fn apply<F: Fn(usize, usize) -> Vec<u8>>(&self, offset: usize, length: usize, read: F) -> Vec<TemplateField> {
+        let mut evaluation = Evaluation {
+            template: self,
+            read,
+            end: length,
+            fields: Vec::new()
+        };
+
+        evaluation.read_struct(&self.root, self.root.clone(), 0, offset);
+
+        evaluation.fields
+    }
+}
+
+struct Evaluation<'a, F: Fn(usize, usize) -> Vec<u8>> {
+    template: &'a Template,
+    read: F,
+    end: usize,
+    fields: Vec<TemplateField>
+}
+
+impl<'a, F: Fn(usize, usize) -> Vec<u8>> Evaluation<'a, F> {
+    /// Add the struct and its members, returning the offset after it or None if evaluation should stop.
+    fn read_struct(&mut self, struct_name: &str, path: String, depth: usize, offset: usize) -> Option<usize> {
+        if depth > MAX_DEPTH {
+            return None;
+        }
+
+        let template = self.template;
+        let definitions = template.structs.get(struct_name)?;
+
+        let struct_index = self.push_field(path.clone(), depth, offset, 0, None)?;
+
+        let mut integers: HashMap<&str, usize> = HashMap::new();
+        let mut position = offset;
+        for definition in definitions.iter() {
+            let field_path = format!("{}.{}", path, definition.name);
+            match &definition.count {
+                None => {
+                    let (next_position, integer) = self.read_field(&definition.field_type, field_path, depth + 1, position)?;
+                    if let Some(integer) = integer {
+                        integers.insert(&definition.name, integer);
+                    }
+                    position = next_position;
+                }
+                Some(count) => {
+                    let count = match count {
+                        Count::Fixed(n) => *n,
+                        Count::Field(name) => *integers.get(name.as_str())?
+                    };
+                    position = self.read_array(&definition.field_type, field_path, depth + 1, position, count)?;
+                }
+            }
+        }
+
+        self.fields[struct_index].length = position - offset;
+
+        Some(position)
+    }
+
+    fn read_array(&mut self, field_type: &FieldType, path: String, depth: usize, offset: usize, count: usize) -> Option<usize> {
+        match field_type {
+            FieldType::Byte | FieldType::Char => {
+                let bytes = self.read_bytes(offset, count)?;
+                let value = if *field_type == FieldType::Char {
+                    format!("\"{}\"", as_printable(&bytes))
+                } else {
+                    as_hex(&bytes)
+                };
+                self.push_field(path, depth, offset, count, Some(value))?;
+
+                Some(offset + count)
+            }
+            _ => {
+                let array_index = self.push_field(path.clone(), depth, offset, 0, None)?;
+                let mut position = offset;
+                for i in 0 .. count {
+                    let (next_position, _) = self.read_field(field_type, format!("{}[{}]", path, i), depth + 1, position)?;
+                    position = next_position;
+                }
+                self.fields[array_index].length = position - offset;
+
+                Some(position)
+            }
+        }
+    }
+
+    /// Add a single field, giving the offset after it and its value if it's an integer.
+    fn read_field(&mut self, field_type: &FieldType, path: String, depth: usize, offset: usize) -> Option<(usize, Option<usize>)> {
+        match field_type {
+            FieldType::Unsigned(width, big_endian) | FieldType::Signed(width, big_endian) => {
+                let bytes = self.read_bytes(offset, *width)?;
+                let mut value: u64 = 0;
+                for i in 0 .. *width {
+                    let byte = if *big_endian {
+                        bytes[i]
+                    } else {
+                        bytes[*width - 1 - i]
+                    };
+                    value = (value << 8) | byte as u64;
+                }
+
+                let (display, integer) = match field_type {
+                    FieldType::Signed(..) => {
+                        // Sign-extend to the full 64 bits
+                        let shift = 64 - (*width * 8);
+                        let signed = ((value << shift) as i64) >> shift;
+                        (format!("{}", signed), usize::try_from(signed).ok())
+                    }
+                    _ => {
+                        (format!("{}", value), usize::try_from(value).ok())
+                    }
+                };
+
+                self.push_field(path, depth, offset, *width, Some(display))?;
+
+                Some((offset + width, integer))
+            }
+            FieldType::Byte => {
+                let bytes = self.read_bytes(offset, 1)?;
+                self.push_field(path, depth, offset, 1, Some(as_hex(&bytes)))?;
+
+                Some((offset + 1, None))
+            }
+            FieldType::Char => {
+                let bytes = self.read_bytes(offset, 1)?;
+                self.push_field(path, depth, offset, 1, Some(format!("'{}'", as_printable(&bytes))))?;
+
+                Some((offset + 1, None))
+            }
+            FieldType::Blob(prefix) => {
+                let available = self.end.saturating_sub(offset);
+                let prefix_bytes = (self.read)(offset, prefix.max_width().min(available));
+                let (prefix_width, data_length) = prefix.read(&prefix_bytes)?;
+                let length = prefix_width.checked_add(data_length)?;
+                if length > available {
+                    return None;
+                }
+
+                let preview = (self.read)(offset + prefix_width, data_length.min(PREVIEW_LENGTH));
+                let mut value = format!("{} bytes", data_length);
+                if !preview.is_empty() {
+                    value = format!("{}: {}", value, as_hex(&preview));
+                    if data_length > PREVIEW_LENGTH {
+                        value.push_str(" ...");
+                    }
+                }
+                self.push_field(path, depth, offset, length, Some(value))?;
+
+                Some((offset + length, None))
+            }
+            FieldType::Struct(name) => {
+                let next_offset = self.read_struct(name, path, depth, offset)?;
+                Some((next_offset, None))
+            }
+        }
+    }
+
+    fn read_bytes(&self, offset: usize, length: usize) -> Option<Vec<u8>> {
+        if offset.checked_add(length)? > self.end {
+            return None;
+        }
+
+        let bytes = (self.read)(offset, length);
+        if bytes.len() == length {
+            Some(bytes)
+        } else {
+            None
+        }
+    }
+
+    fn push_field(&mut self, name: String, depth: usize, offset: usize, length: usize, value: Option<String>) -> Option<usize> {
+        if self.fields.len() >= MAX_FIELDS {
+            return None;
+        }
+
+        self.fields.push(TemplateField {
+            name,
+            depth,
+            offset,
+            length,
+            value
+        });
+
+        Some(self.fields.len() - 1)
+    }
+}
+
+fn parse_type(type_name: &str) -> Option<FieldType> {
+    match type_name {
+        "byte" => Some(FieldType::Byte),
+        "char" => Some(FieldType::Char),
+        "blob:vlq" => Some(FieldType::Blob(Prefix::VariableLength)),
+        _ => {
+            if let Some(width) = type_name.strip_prefix("blob:be") {
+                parse_prefix_width(width).map(|width| FieldType::Blob(Prefix::BigEndian(width)))
+            } else if let Some(width) = type_name.strip_prefix("blob:le") {
+                parse_prefix_width(width).map(|width| FieldType::Blob(Prefix::LittleEndian(width)))
+            } else if let Some(integer_type) = parse_integer_type(type_name) {
+                Some(integer_type)
+            } else if is_identifier(type_name) {
+                Some(FieldType::Struct(type_name.to_string()))
+            } else {
+                None
+            }
+        }
+    }
+}
+
+// u8, i16, u32le, i64be ...
+fn parse_integer_type(type_name: &str) -> Option<FieldType> {
+    let signed = match type_name.chars().next()? {
+        'u' => false,
+        'i' => true,
+        _ => {
+            return None;
+        }
+    };
+
+    let rest = &type_name[1 ..];
+    let (bits, big_endian) = if let Some(bits) = rest.strip_suffix("be") {
+        (bits, true)
+    } else if let Some(bits) = rest.strip_suffix("le") {
+        (bits, false)
+    } else {
+        (rest, false)
+    };
+
+    let width = match bits {
+        "8" => 1,
+        "16" => 2,
+        "32" => 4,
+        "64" => 8,
+        _ => {
+            return None;
+        }
+    };
+
+    if signed {
+        Some(FieldType::Signed(width, big_endian))
+    } else {
+        Some(FieldType::Unsigned(width, big_endian))
+    }
+}
+
+fn parse_prefix_width(width: &str) -> Option<usize> {
+    match width.parse::<usize>() {
+        Ok(width) if (1 ..= 8).contains(&width) => Some(width),
+        _ => None
+    }
+}
+
+fn is_identifier(word: &str) -> bool {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => {
+            chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        }
+        _ => false
+    }
+}
+
+fn as_hex(bytes: &[u8]) -> String {
+    let mut words: Vec<String> = bytes.iter().take(PREVIEW_LENGTH).map(|byte| format!("{:02X}", byte)).collect();
+    if bytes.len() > PREVIEW_LENGTH {
+        words.push("...".to_string());
+    }
+    words.join(" ")
+}
+
+fn as_printable(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| {
+        if byte.is_ascii_graphic() || *byte == b' ' {
+            *byte as char
+        } else {
+            '.'
+        }
+    }).collect()
+}
+
+/// The fields of a template laid over the content, with the fields holding values indexed by offset
+pub struct TemplateOverlay {
+    fields: Vec<TemplateField>,
+    // Indices of the fields with values, which don't overlap and are in order of offset
+    leaves: Vec<usize>
+}
+
+impl TemplateOverlay {
+    pub fn new(fields: Vec<TemplateField>) -> TemplateOverlay {
+        let mut leaves = Vec::new();
+        for (i, field) in fields.iter().enumerate() {
+            if field.value.is_some() && field.length > 0 {
+                leaves.push(i);
+            }
+        }
+
+        TemplateOverlay {
+            fields,
+            leaves
+        }
+    }
+
+    pub fn get_fields(&self) -> &[TemplateField] {
+        &self.fields
+    }
+
+    /// Get the index of the field with a value covering `offset`, if there is one
+    pub fn get_field_index_at(&self, offset: usize) -> Option<usize> {
+        let position = self.leaves.partition_point(|i| {
+            let field = &self.fields[*i];
+            field.offset + field.length <= offset
+        });
+
+        let index = *self.leaves.get(position)?;
+        if self.fields[index].offset <= offset {
+            Some(index)
+        } else {
+            None
+        }
+    }
+
+    /// Get the position among the fields with values of the field covering `offset`.
+    /// Used to tell neighbouring fields apart.
+    pub fn get_leaf_position_at(&self, offset: usize) -> Option<usize> {
+        let index = self.get_field_index_at(offset)?;
+        self.leaves.binary_search(&index).ok()
+    }
+}
@@ -1,6 +1,13 @@
 #[cfg (test)]
 mod tests {
     use crate::editor::{Editor, FormatterRef, HexFormatter, BinaryFormatter, DecFormatter, SbyteError, parse_words, string_to_integer, string_to_bytes};
+    use crate::editor::template::{Template, TemplateError};
+    use crate::editor::inspector::{inspect, read_uleb128, read_sleb128};
+    use crate::editor::save::Backup;
+    use crate::editor::records::{self, FileFormat, RecordError};
+    use crate::editor::export::{self, ExportFormat, ImportError};
+    use crate::editor::substitute::Substitution;
+    use crate::editor::typed::{parse_typed_value, TypedValueError};
     use std::{time, thread};
 
     #[test]
@@ -18,6 +25,598 @@ mod tests {
         assert_eq!(editor.get_chunk(0, 44).as_slice(), "TESTFILECONTENTS".as_bytes());
     }
 
+    #[test]
+    fn test_save_over_loaded_file() {
+        let path = std::env::temp_dir().join("sbyte_test_save_over_loaded_file");
+        let path = path.to_str().unwrap();
+        std::fs::copy("src/testfiles/00", path).expect("Couldn't copy test file");
+
+        let mut editor = Editor::new();
+        editor.load_file(path).expect("Couldn't open file");
+        editor.remove_bytes(0, 4).ok();
+        editor.insert_bytes(0, b"BEST").ok();
+        assert!(editor.save().is_ok());
+        assert_eq!(std::fs::read(path).unwrap().as_slice(), "BESTFILECONTENTS".as_bytes());
+
+        // Content still reads from the old file until reloaded
+        assert_eq!(editor.get_chunk(4, 12).as_slice(), "FILECONTENTS".as_bytes());
+        std::fs::remove_file(path).ok();
+    }
+
+
+    #[test]
+    fn test_persistent_undo() {
+        let path = std::env::temp_dir().join("sbyte_test_persistent_undo");
+        let path = path.to_str().unwrap();
+        let sidecar = std::env::temp_dir().join(".sbyte_test_persistent_undo.sbyte-undo");
+        std::fs::copy("src/testfiles/00", path).expect("Couldn't copy test file");
+        std::fs::remove_file(&sidecar).ok();
+
+        let mut editor = Editor::new();
+        editor.load_file(path).expect("Couldn't open file");
+        editor.overwrite_bytes(0, b"B").ok();
+        // Keep the edits from being undone as one
+        thread::sleep(time::Duration::from_millis(60));
+        editor.insert_bytes(16, b"!").ok();
+        editor.undo().ok();
+        editor.save().ok();
+        assert!(!sidecar.exists(), "History was saved without persistent_undo");
+
+        editor.set_persistent_undo(true);
+        editor.save().ok();
+        assert!(sidecar.exists());
+
+        let mut editor = Editor::new();
+        editor.load_file(path).expect("Couldn't open file");
+        assert_eq!(editor.active_content.as_slice(), "BESTFILECONTENTS".as_bytes());
+        assert!(editor.redo().is_ok());
+        assert_eq!(editor.active_content.as_slice(), "BESTFILECONTENTS!".as_bytes());
+        editor.undo().ok();
+        assert!(editor.undo().is_ok());
+        assert_eq!(editor.active_content.as_slice(), "TESTFILECONTENTS".as_bytes());
+
+        // History is ignored once the file changes outside of the editor
+        std::fs::write(path, "BESTFILECONTENTZ").ok();
+        let mut editor = Editor::new();
+        editor.load_file(path).expect("Couldn't open file");
+        assert!(editor.undo().is_err());
+
+        std::fs::remove_file(path).ok();
+        std::fs::remove_file(&sidecar).ok();
+    }
+
+    #[test]
+    fn test_save_safely() {
+        let dir = std::env::temp_dir().join("sbyte_test_save_safely");
+        std::fs::remove_dir_all(&dir).ok();
+        std::fs::create_dir_all(&dir).expect("Couldn't create test directory");
+        let path = dir.join("file");
+        let path = path.to_str().unwrap();
+        std::fs::copy("src/testfiles/00", path).expect("Couldn't copy test file");
+
+        let mut permissions = std::fs::metadata(path).unwrap().permissions();
+        permissions.set_readonly(true);
+        std::fs::set_permissions(path, permissions).ok();
+
+        let mut editor = Editor::new();
+        editor.load_file(path).expect("Couldn't open file");
+        assert!(!editor.is_changed_on_disk());
+
+        editor.set_backup(Backup::Simple);
+        editor.overwrite_bytes(0, b"B").ok();
+        assert!(editor.save().is_ok());
+        assert_eq!(std::fs::read(path).unwrap(), "BESTFILECONTENTS".as_bytes());
+        assert_eq!(std::fs::read(dir.join("file~")).unwrap(), "TESTFILECONTENTS".as_bytes());
+        assert!(std::fs::metadata(path).unwrap().permissions().readonly(), "Permissions weren't kept");
+        assert!(!dir.join("file.sbyte-tmp").exists());
+        // The old version is still readable after being replaced
+        assert_eq!(editor.get_chunk(1, 4).as_slice(), "ESTF".as_bytes());
+
+        editor.set_backup(Backup::Numbered);
+        editor.save().ok();
+        editor.save().ok();
+        assert_eq!(std::fs::read(dir.join("file.~1~")).unwrap(), "BESTFILECONTENTS".as_bytes());
+        assert!(dir.join("file.~2~").exists());
+        assert!(!dir.join("file.~3~").exists());
+
+        assert!(!editor.is_changed_on_disk());
+        std::fs::remove_file(path).ok();
+        std::fs::write(path, "CHANGED").ok();
+        assert!(editor.is_changed_on_disk());
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_read_only() {
+        let mut editor = Editor::new();
+        editor.load_file("src/testfiles/00").expect("Couldn't open file");
+        editor.overwrite_bytes(0, b"B").ok();
+        editor.set_read_only(true);
+
+        assert_eq!(editor.overwrite_bytes(0, b"R"), Err(SbyteError::ReadOnly));
+        assert_eq!(editor.insert_bytes(0, b"R"), Err(SbyteError::ReadOnly));
+        assert_eq!(editor.remove_bytes(0, 1), Err(SbyteError::ReadOnly));
+        assert_eq!(editor.increment_byte(0, 1), Err(SbyteError::ReadOnly));
+        assert_eq!(editor.replace("E", b"e"), Err(SbyteError::ReadOnly));
+        assert_eq!(editor.bitwise_not(), Err(SbyteError::ReadOnly));
+        assert_eq!(editor.undo(), Err(SbyteError::ReadOnly));
+        assert_eq!(editor.save(), Err(SbyteError::ReadOnly));
+        assert_eq!(editor.active_content.as_slice(), "BESTFILECONTENTS".as_bytes());
+
+        editor.set_read_only(false);
+        assert!(editor.undo().is_ok());
+    }
+
+    #[test]
+    fn test_locked_ranges() {
+        let mut editor = Editor::new();
+        editor.load_file("src/testfiles/00").expect("Couldn't open file");
+        editor.lock_range(4, 4);
+        editor.lock_range(6, 4);
+        editor.lock_range(12, 2);
+        assert_eq!(editor.get_locked_ranges(), &[(4, 10), (12, 14)]);
+
+        assert_eq!(editor.overwrite_bytes(2, b"xxx"), Err(SbyteError::LockedRange(4, 10)));
+        assert_eq!(editor.insert_bytes(5, b"x"), Err(SbyteError::LockedRange(4, 10)));
+        assert_eq!(editor.remove_bytes(9, 2), Err(SbyteError::LockedRange(4, 10)));
+        editor.set_cursor_offset(13).ok();
+        assert_eq!(editor.apply_or_mask(&[0x20]), Err(SbyteError::LockedRange(12, 14)));
+        // Nothing is replaced when any match is locked
+        assert_eq!(editor.replace("E", b"e"), Err(SbyteError::LockedRange(12, 14)));
+        assert_eq!(editor.active_content.as_slice(), "TESTFILECONTENTS".as_bytes());
+
+        // Edits around a locked range move it along with its bytes
+        assert!(editor.insert_bytes(4, b"--").is_ok());
+        assert!(editor.remove_bytes(0, 1).is_ok());
+        assert_eq!(editor.get_locked_ranges(), &[(5, 11), (13, 15)]);
+        assert_eq!(editor.get_chunk(5, 6).as_slice(), "FILECO".as_bytes());
+
+        editor.unlock_range(7, 7);
+        assert_eq!(editor.get_locked_ranges(), &[(5, 7), (14, 15)]);
+        assert!(editor.overwrite_bytes(7, b"xx").is_ok());
+    }
+
+    #[test]
+    fn test_reader_and_writer() {
+        let mut editor = Editor::new();
+        editor.open_buffer("src/testfiles/00").expect("Couldn't open file");
+        editor.open_buffer_from_reader(&mut "PIPED".as_bytes()).expect("Couldn't read");
+        assert_eq!(editor.get_buffer_count(), 2);
+        assert_eq!(editor.get_active_file_path(), None);
+        assert_eq!(editor.save(), Err(SbyteError::PathNotSet));
+
+        editor.insert_bytes(5, b"!").ok();
+        let mut output = Vec::new();
+        assert!(editor.write_to(&mut output).is_ok());
+        assert_eq!(output.as_slice(), "PIPED!".as_bytes());
+    }
+
+    #[test]
+    fn test_replace_range() {
+        let mut editor = Editor::new();
+        editor.load_file("src/testfiles/00").expect("Couldn't open file");
+
+        assert_eq!(editor.replace_range(0, 4, b"XY"), Ok(b"TEST".to_vec()));
+        assert_eq!(editor.active_content.as_slice(), "XYFILECONTENTS".as_bytes());
+        // Keep the edits from being undone as one
+        thread::sleep(time::Duration::from_millis(60));
+        assert_eq!(editor.replace_range(2, 4, b"WHOLE"), Ok(b"FILE".to_vec()));
+        assert_eq!(editor.active_content.as_slice(), "XYWHOLECONTENTS".as_bytes());
+
+        editor.undo().ok();
+        assert_eq!(editor.active_content.as_slice(), "XYFILECONTENTS".as_bytes());
+        editor.undo().ok();
+        assert_eq!(editor.active_content.as_slice(), "TESTFILECONTENTS".as_bytes());
+
+        editor.lock_range(4, 2);
+        assert_eq!(editor.replace_range(3, 2, b""), Err(SbyteError::LockedRange(4, 6)));
+        assert_eq!(editor.active_content.as_slice(), "TESTFILECONTENTS".as_bytes());
+    }
+
+    #[test]
+    fn test_replace_captures() {
+        let mut editor = Editor::new();
+        editor.insert_bytes(0, &[0x12, 0x34, 0xAA, 0x56, 0x78, 0xAA]).ok();
+
+        let substitution = Substitution::parse("$2\\x00$1").unwrap();
+        assert_eq!(
+            editor.find_replacements("(.)(.)\\xAA", &substitution, (0, 6)),
+            Ok(vec![(0, 3, vec![0x34, 0x00, 0x12]), (3, 6, vec![0x78, 0x00, 0x56])])
+        );
+
+        let substitution = Substitution::parse("${low}${high}$$").unwrap();
+        assert_eq!(
+            editor.find_replacements("(?P<high>\\x.2|\\x.6)(?P<low>\\b0.11....)", &substitution, (0, 6)),
+            Ok(vec![(0, 2, vec![0x34, 0x12, b'$']), (3, 5, vec![0x78, 0x56, b'$'])])
+        );
+
+        assert_eq!(Substitution::parse("${1"), Err(SbyteError::InvalidSubstitution("${1".to_string())));
+
+        // Only the second match
+        let replacements = editor.find_replacements("(.)(.)\\xAA", &Substitution::parse("$2$1").unwrap(), (0, 6)).unwrap();
+        assert_eq!(editor.replace_matches(&replacements[1 ..]), Ok(vec![3]));
+        assert_eq!(editor.active_content.as_slice(), &[0x12, 0x34, 0xAA, 0x78, 0x56]);
+    }
+
+    #[test]
+    fn test_typed_values() {
+        assert_eq!(parse_typed_value("u32le:0xDEADBEEF"), Some(Ok(vec![0xEF, 0xBE, 0xAD, 0xDE])));
+        assert_eq!(parse_typed_value("u32be:0xDEADBEEF"), Some(Ok(vec![0xDE, 0xAD, 0xBE, 0xEF])));
+        assert_eq!(parse_typed_value("i16be:-2"), Some(Ok(vec![0xFF, 0xFE])));
+        assert_eq!(parse_typed_value("u8:0b101"), Some(Ok(vec![5])));
+        assert_eq!(parse_typed_value("f32:2.5"), Some(Ok(2.5f32.to_le_bytes().to_vec())));
+        assert_eq!(parse_typed_value("f64be:-0.5"), Some(Ok((-0.5f64).to_be_bytes().to_vec())));
+        assert_eq!(parse_typed_value("utf16le:\"Hi\""), Some(Ok(vec![b'H', 0, b'i', 0])));
+        assert_eq!(parse_typed_value("utf16be:Hi"), Some(Ok(vec![0, b'H', 0, b'i'])));
+        assert_eq!(parse_typed_value("utf8:'é'"), Some(Ok(vec![0xC3, 0xA9])));
+
+        assert_eq!(parse_typed_value("u8:256"), Some(Err(TypedValueError::OutOfRange("256".to_string()))));
+        assert_eq!(parse_typed_value("u16:-1"), Some(Err(TypedValueError::OutOfRange("-1".to_string()))));
+        assert_eq!(parse_typed_value("i8:-129"), Some(Err(TypedValueError::OutOfRange("-129".to_string()))));
+        assert_eq!(parse_typed_value("i32:0x-1"), Some(Err(TypedValueError::InvalidValue("0x-1".to_string()))));
+        assert_eq!(parse_typed_value("f32:pi"), Some(Err(TypedValueError::InvalidValue("pi".to_string()))));
+        // Anything else is a regex
+        assert_eq!(parse_typed_value("u24:1"), None);
+        assert_eq!(parse_typed_value("(?i:abc)"), None);
+
+        let mut editor = Editor::new();
+        editor.insert_bytes(0, &[0x00, 0xEF, 0xBE, 0xAD, 0xDE, 0xFE, 0xFF, b'H', 0, b'i', 0]).ok();
+        assert_eq!(editor.find_all("u32:0xDEADBEEF"), Ok(vec![(1, 5)]));
+        assert_eq!(editor.find_all("i16:-2"), Ok(vec![(5, 7)]));
+        assert_eq!(editor.find_all("utf16le:Hi"), Ok(vec![(7, 11)]));
+        assert_eq!(editor.find_all("u16:65536"), Err(SbyteError::InvalidTypedValue(TypedValueError::OutOfRange("65536".to_string()))));
+    }
+
+    #[test]
+    fn test_registers() {
+        let mut editor = Editor::new();
+        assert_eq!(editor.get_register('a'), None);
+
+        editor.copy_to_register('a', b"AB");
+        editor.copy_to_register('A', b"CD");
+        assert_eq!(editor.get_register('a'), Some(b"ABCD".to_vec()));
+        assert_eq!(editor.get_register('A'), Some(b"ABCD".to_vec()));
+
+        editor.copy_to_register('-', b"EF");
+        editor.copy_to_register('a', b"GH");
+        assert_eq!(editor.get_register('a'), Some(b"GH".to_vec()));
+        assert_eq!(editor.get_register_names(), vec!['-', 'a']);
+
+        editor.copy_to_register('"', b"IJ");
+        assert_eq!(editor.get_clipboard(), b"IJ".to_vec());
+        assert_eq!(editor.get_register('"'), Some(b"IJ".to_vec()));
+        assert_eq!(editor.get_register_names(), vec!['-', 'a']);
+    }
+
+    #[test]
+    fn test_records() {
+        let hex = ":10010000214601360121470136007EFE09D2190140\n:100110002146017E17C20001FF5F16002148011928\n:00000001FF\n";
+        let path = std::env::temp_dir().join("sbyte_test_records.hex");
+        let path = path.to_str().unwrap();
+        std::fs::write(path, hex).expect("Couldn't write test file");
+
+        let mut editor = Editor::new();
+        editor.load_file(path).expect("Couldn't open file");
+        assert_eq!(editor.get_file_format(), FileFormat::IntelHex);
+        assert_eq!(editor.get_base_address(), 0x100);
+        assert_eq!(editor.len(), 32);
+        assert_eq!(editor.get_chunk(0, 3).as_slice(), &[0x21, 0x46, 0x01]);
+
+        // Saved as it was loaded, checksums and all
+        assert!(editor.save().is_ok());
+        assert_eq!(std::fs::read_to_string(path).unwrap(), hex);
+
+        // Re-encoded by the name it's saved as
+        let srec_path = std::env::temp_dir().join("sbyte_test_records.s19");
+        let srec_path = srec_path.to_str().unwrap();
+        assert!(editor.save_as(srec_path).is_ok());
+        let mut editor = Editor::new();
+        editor.load_file(srec_path).expect("Couldn't open file");
+        assert_eq!(editor.get_file_format(), FileFormat::SRecord);
+        assert_eq!(editor.get_base_address(), 0x100);
+        assert_eq!(editor.get_chunk(30, 2).as_slice(), &[0x01, 0x19]);
+
+        // Gaps are left out, and later records write over earlier ones
+        let image = records::decode(FileFormat::IntelHex, b":0100000011EE\n:0100040022D9\n").ok().unwrap();
+        assert_eq!(image.runs, vec![(0, vec![0x11]), (4, vec![0x22])]);
+        let image = records::decode(FileFormat::IntelHex, b":0100010044BA\n:0300000011223397\n:0100040022D9\n").ok().unwrap();
+        assert_eq!(image.runs, vec![(0, vec![0x11, 0x22, 0x33]), (4, vec![0x22])]);
+        assert_eq!(records::decode(FileFormat::IntelHex, b":0100000011EF\n").err(), Some(RecordError::BadChecksum(1)));
+        assert_eq!(records::decode(FileFormat::SRecord, b"S00300\n").err(), Some(RecordError::InvalidLine(1)));
+
+        // Records crossing 64K and 16M boundaries come back as they went in
+        for format in [FileFormat::IntelHex, FileFormat::SRecord].iter() {
+            let bytes: Vec<u8> = (0 .. 40).collect();
+            let mut output = Vec::new();
+            records::encode(*format, &[(0xFFFFF0, bytes.clone())], Some(0x1234), &mut output).ok();
+            let image = records::decode(*format, &output).ok().unwrap();
+            assert_eq!(image.runs, vec![(0xFFFFF0, bytes)]);
+            assert_eq!(image.base_address, 0xFFFFF0);
+            assert_eq!(image.start_address, Some(0x1234));
+        }
+
+        // A name that only looks like records is opened as it is, unless the format was asked for
+        std::fs::write(path, b"not records").expect("Couldn't write test file");
+        let mut editor = Editor::new();
+        editor.load_file(path).expect("Couldn't open file");
+        assert_eq!(editor.get_file_format(), FileFormat::Raw);
+        assert_eq!(editor.get_chunk(0, 3).as_slice(), b"not");
+        let mut editor = Editor::new();
+        editor.open_buffer(path).expect("Couldn't open file");
+        assert_eq!(editor.get_file_format(), FileFormat::Raw);
+        let mut editor = Editor::new();
+        assert!(editor.load_file_as(path, FileFormat::IntelHex).is_err());
+        let mut editor = Editor::new();
+        assert!(editor.open_buffer_as(path, FileFormat::IntelHex).is_err());
+
+        std::fs::remove_file(path).ok();
+        std::fs::remove_file(srec_path).ok();
+    }
+
+    #[test]
+    fn test_record_gaps() {
+        let hex = ":0100000055AA\n:02000004FFFFFC\n:01000000AA55\n:00000001FF\n";
+        let path = std::env::temp_dir().join("sbyte_test_record_gaps.hex");
+        let path = path.to_str().unwrap();
+        std::fs::write(path, hex).expect("Couldn't write test file");
+
+        // Nearly 4G of gap is shown as the pad byte without being held in memory
+        let mut editor = Editor::new();
+        editor.set_pad_byte(0xEE);
+        editor.load_file(path).expect("Couldn't open file");
+        assert_eq!(editor.len(), 0xFFFF0001);
+        assert_eq!(editor.get_chunk(0, 3).as_slice(), &[0x55, 0xEE, 0xEE]);
+        assert_eq!(editor.get_chunk(0xFFFEFFFF, 2).as_slice(), &[0xEE, 0xAA]);
+
+        // Only the bytes the records had, and ones edited since, are saved
+        assert!(editor.save().is_ok());
+        assert_eq!(std::fs::read_to_string(path).unwrap(), hex);
+        editor.overwrite_bytes(2, &[0x33]).ok();
+        assert!(editor.save().is_ok());
+        let image = records::decode(FileFormat::IntelHex, &std::fs::read(path).unwrap()).ok().unwrap();
+        assert_eq!(image.runs, vec![(0, vec![0x55]), (2, vec![0x33]), (0xFFFF0000, vec![0xAA])]);
+
+        std::fs::remove_file(path).ok();
+    }
+
+    #[test]
+    fn test_export_import() {
+        let hello = b"Hello\n";
+        assert_eq!(export::export(ExportFormat::Xxd, hello, 0), "00000000: 4865 6c6c 6f0a                           Hello.\n");
+        assert_eq!(export::export(ExportFormat::PythonBytes, hello, 0), "b'Hello\\n'\n");
+        assert_eq!(export::export(ExportFormat::Base64, hello, 0), "SGVsbG8K\n");
+        assert_eq!(export::export(ExportFormat::CArray, &hello[0 .. 2], 0), "uint8_t data[2] = {\n    0x48, 0x65,\n};\n");
+
+        let bytes: Vec<u8> = (0 ..= 255).rev().collect();
+        for format in [ExportFormat::CArray, ExportFormat::RustArray, ExportFormat::PythonBytes, ExportFormat::Base64, ExportFormat::Xxd].iter() {
+            let text = export::export(*format, &bytes, 0x100);
+            assert_eq!(export::import(*format, &text), Ok(bytes.clone()), "{:?} didn't come back the same", format);
+        }
+
+        // Written by hand rather than by sbyte
+        assert_eq!(export::import(ExportFormat::RustArray, "let x: [u8; 3] = [1, 0x02u8, 0b11];"), Ok(vec![1, 2, 3]));
+        assert_eq!(export::import(ExportFormat::PythonBytes, "b'\\x00A' b\"\\'\\101\""), Ok(vec![0, 65, 39, 65]));
+        assert_eq!(export::import(ExportFormat::Base64, "SGVs\nbG8K"), Ok(hello.to_vec()));
+        assert_eq!(export::import(ExportFormat::Xxd, "00000010: 4142  AB\n00000014: 43  C\n"), Ok(vec![0x41, 0x42, 0, 0, 0x43]));
+        assert_eq!(export::import(ExportFormat::CArray, "{ 0x100 }"), Err(ImportError::InvalidToken("0x100".to_string())));
+        assert_eq!(export::import(ExportFormat::Xxd, "nonsense"), Err(ImportError::InvalidLine(1)));
+    }
+
+    #[test]
+    fn test_buffers() {
+        let mut editor = Editor::new();
+        editor.open_buffer("src/testfiles/00").expect("Couldn't open file");
+        assert_eq!(editor.get_buffer_count(), 1, "Blank buffer wasn't reused");
+
+        editor.set_cursor_offset(4).ok();
+        assert!(!editor.is_modified());
+        editor.insert_bytes(0, b"!").ok();
+        assert!(editor.is_modified());
+
+        editor.open_buffer("src/testfiles/01").expect("Couldn't open file");
+        assert_eq!(editor.get_buffer_count(), 2);
+        assert_eq!(editor.get_active_buffer_index(), 1);
+        assert_eq!(editor.get_chunk(0, 6).as_slice(), "SECOND".as_bytes());
+        assert_eq!(editor.get_cursor_offset(), 0);
+        assert!(editor.undo().is_err(), "Undo stack leaked between buffers");
+        assert!(!editor.is_modified());
+
+        editor.prev_buffer().ok();
+        assert_eq!(editor.get_active_file_path(), Some(&"src/testfiles/00".to_string()));
+        assert_eq!(editor.get_cursor_offset(), 4);
+        assert!(editor.is_modified());
+        assert!(editor.undo().is_ok());
+        assert_eq!(editor.get_chunk(0, 4).as_slice(), "TEST".as_bytes());
+        assert!(!editor.is_modified(), "Undoing back to the loaded state still counts as modified");
+
+        // Opening an already open file switches to it
+        editor.open_buffer("src/testfiles/01").ok();
+        assert_eq!(editor.get_buffer_count(), 2);
+        assert_eq!(editor.get_active_buffer_index(), 1);
+
+        assert!(editor.open_buffer("src/testfiles/does_not_exist").is_err());
+        assert_eq!(editor.get_buffer_count(), 2);
+        assert_eq!(editor.get_active_buffer_index(), 1);
+
+        assert_eq!(editor.switch_to_buffer(2), Err(SbyteError::InvalidBuffer(2)));
+
+        editor.close_buffer();
+        assert_eq!(editor.get_buffer_paths(), vec![Some("src/testfiles/00".to_string())]);
+        editor.close_buffer();
+        assert_eq!(editor.get_buffer_paths(), vec![None]);
+        assert_eq!(editor.len(), 0);
+    }
+
+    #[test]
+    fn test_diff() {
+        let mut editor = Editor::new();
+        editor.open_buffer("src/testfiles/00").ok();
+        editor.open_buffer("src/testfiles/01").ok();
+        editor.switch_to_buffer(0).ok();
+
+        assert!(editor.set_diff_buffer(0).is_err());
+        assert!(editor.set_diff_buffer(1).is_ok());
+
+        // TESTFILECONTENTS vs SECONDFILE
+        assert_eq!(editor.get_difference_at(0), Some((0, 1)));
+        assert_eq!(editor.get_difference_at(1), None);
+        assert_eq!(editor.find_next_difference(0), Some((2, 16)));
+        assert_eq!(editor.find_next_difference(5), None);
+        assert_eq!(editor.find_prev_difference(5), Some((0, 1)));
+        assert_eq!(editor.find_prev_difference(0), None);
+
+        editor.set_cursor_offset(0).ok();
+        assert!(editor.diff_put().is_ok());
+        assert_eq!(editor.get_diff_chunk(0, 10), Some("TECONDFILE".as_bytes().to_vec()));
+        assert_eq!(editor.get_difference_at(0), None);
+
+        editor.set_cursor_offset(2).ok();
+        assert_eq!(editor.diff_get(), Ok((2, 16)));
+        assert_eq!(editor.active_content.as_slice(), "TECONDFILE".as_bytes());
+        assert_eq!(editor.find_next_difference(0), None);
+        assert!(editor.diff_get().is_err());
+
+        // The put landed on the other buffer's own undo stack
+        editor.switch_to_buffer(1).ok();
+        assert_eq!(editor.get_diff_buffer_index(), Some(0));
+        editor.undo().ok();
+        assert_eq!(editor.active_content.as_slice(), "SECONDFILE".as_bytes());
+
+        editor.unset_diff_buffer();
+        assert_eq!(editor.get_diff_chunk(0, 1), None);
+    }
+
+    #[test]
+    fn test_diff_across_blocks() {
+        // A run straddling a block boundary, and a longer second buffer whose tail is one run
+        let first = vec![0u8; 10000];
+        let mut second = first.clone();
+        for byte in second[4000 .. 4200].iter_mut() {
+            *byte = 1;
+        }
+        second.extend(vec![0u8; 5000]);
+
+        let mut editor = Editor::new();
+        editor.open_buffer_from_reader(&mut first.as_slice()).ok();
+        editor.open_buffer_from_reader(&mut second.as_slice()).ok();
+        editor.switch_to_buffer(0).ok();
+        assert!(editor.set_diff_buffer(1).is_ok());
+
+        assert_eq!(editor.get_difference_at(4100), Some((4000, 4200)));
+        assert_eq!(editor.find_next_difference(0), Some((4000, 4200)));
+        assert_eq!(editor.find_next_difference(4000), Some((10000, 15000)));
+        assert_eq!(editor.get_difference_at(12000), Some((10000, 15000)));
+        assert_eq!(editor.find_next_difference(12000), None);
+        assert_eq!(editor.find_prev_difference(12000), Some((4000, 4200)));
+        assert_eq!(editor.find_prev_difference(15000), Some((10000, 15000)));
+    }
+
+    const TEST_TEMPLATE: &str = "
+        # Two entries and a name
+        struct header {
+            magic: char[2]
+            count: u8
+            entries: entry[count]
+            name: blob:be2
+        }
+
+        struct entry {
+            id: u8
+            value: i16be
+        }
+    ";
+
+    #[test]
+    fn test_template_parse_errors() {
+        assert_eq!(Template::parse("").err(), Some(TemplateError::NoStructs));
+        assert_eq!(Template::parse("struct a {\n x: u8").err(), Some(TemplateError::InvalidLine(2)));
+        assert_eq!(Template::parse("struct a\n").err(), Some(TemplateError::InvalidLine(1)));
+        assert_eq!(Template::parse("struct a {\n x: blob:be9\n}").err(), Some(TemplateError::UnknownType(2, "blob:be9".to_string())));
+        assert_eq!(Template::parse("struct a {\n x: u8[y]\n y: u8\n}").err(), Some(TemplateError::UnknownCount(2, "y".to_string())));
+        assert_eq!(Template::parse("struct a {\n x: b\n}").err(), Some(TemplateError::UnknownStruct("b".to_string())));
+        assert!(Template::parse(TEST_TEMPLATE).is_ok());
+    }
+
+    #[test]
+    fn test_template() {
+        let path = std::env::temp_dir().join("sbyte_test_template");
+        let path = path.to_str().unwrap();
+        std::fs::write(path, TEST_TEMPLATE).expect("Couldn't write template");
+
+        let mut editor = Editor::new();
+        editor.insert_bytes(0, b"AB\x02\x01\xFF\xFE\x02\x00\x10\x00\x03xyz").ok();
+        assert_eq!(editor.get_template_fields().len(), 0);
+        assert!(editor.load_template("/nonexistent/template", 0).is_err());
+        editor.load_template(path, 0).expect("Couldn't load template");
+        std::fs::remove_file(path).ok();
+
+        let fields = editor.get_template_fields();
+        let summary: Vec<(&str, usize, usize, Option<&str>)> = fields.iter().map(|field| {
+            (field.name.as_str(), field.offset, field.length, field.value.as_deref())
+        }).collect();
+
+        assert_eq!(summary, vec![
+            ("header", 0, 14, None),
+            ("header.magic", 0, 2, Some("\"AB\"")),
+            ("header.count", 2, 1, Some("2")),
+            ("header.entries", 3, 6, None),
+            ("header.entries[0]", 3, 3, None),
+            ("header.entries[0].id", 3, 1, Some("1")),
+            ("header.entries[0].value", 4, 2, Some("-2")),
+            ("header.entries[1]", 6, 3, None),
+            ("header.entries[1].id", 6, 1, Some("2")),
+            ("header.entries[1].value", 7, 2, Some("16")),
+            ("header.name", 9, 5, Some("3 bytes: 78 79 7A"))
+        ]);
+        assert_eq!(fields[4].depth, 2);
+
+        assert_eq!(editor.get_template_field_index_at(4), Some(6));
+        assert_eq!(editor.get_template_field_index_at(14), None);
+        assert_eq!(editor.get_template_mask(0, 4), vec![Some(0), Some(0), Some(1), Some(2)]);
+
+        // Fields follow edits. With one entry, the name's prefix runs past the end of the content.
+        editor.overwrite_bytes(2, &[1]).ok();
+        let fields = editor.get_template_fields();
+        assert_eq!(fields.last().unwrap().name, "header.entries[0].value");
+
+        editor.unset_template();
+        assert_eq!(editor.get_template_fields().len(), 0);
+        assert_eq!(editor.get_template_mask(0, 4), vec![]);
+    }
+
+    #[test]
+    fn test_inspect() {
+        let values: std::collections::HashMap<&str, Option<String>> = inspect(&[0xE5, 0x8E, 0x26, 0x40, 0x00, 0x00, 0x00, 0x00]).into_iter().collect();
+        let get = |label: &str| values.get(label).unwrap().clone();
+
+        assert_eq!(get("binary"), Some("11100101".to_string()));
+        assert_eq!(get("i8"), Some("-27".to_string()));
+        assert_eq!(get("u8"), Some("229".to_string()));
+        assert_eq!(get("u16le"), Some("36581".to_string()));
+        assert_eq!(get("u16be"), Some("58766".to_string()));
+        assert_eq!(get("i32be"), Some("-443668928".to_string()));
+        assert_eq!(get("u64le"), Some("1076268773".to_string()));
+        assert_eq!(get("uleb128"), Some("624485 (3B)".to_string()));
+        assert_eq!(get("unix32le"), Some("2004-02-08 19:32:53".to_string()));
+
+        // Not enough bytes for the wider types
+        let values = inspect(&[0x41]);
+        assert!(values.contains(&("utf-8", Some("U+0041 'A'".to_string()))));
+        assert!(inspect("\u{00E9}".as_bytes()).contains(&("utf-8", Some("U+00E9 '\u{00E9}'".to_string()))));
+        assert!(inspect(&[0x3D, 0xD8, 0x00, 0xDE]).contains(&("utf-16le", Some("U+1F600 '\u{1F600}'".to_string()))));
+        assert!(values.contains(&("u16le", None)));
+        assert!(values.contains(&("f64be", None)));
+        assert!(inspect(&[]).iter().all(|(_, value)| value.is_none()));
+
+        assert_eq!(read_sleb128(&[0xC0, 0xBB, 0x78]), Some((-123456, 3)));
+        assert_eq!(read_sleb128(&[0x7F]), Some((-1, 1)));
+        assert_eq!(read_uleb128(&[0x80, 0x80]), None);
+
+        let mut editor = Editor::new();
+        editor.insert_bytes(0, &[0x00, 0x00, 0x80, 0x3F]).ok();
+        assert!(editor.inspect_at_cursor().contains(&("f32le", Some("1".to_string()))));
+    }
 
     #[test]
     fn test_insert_bytes() {
@@ -59,9 +658,9 @@ mod tests {
         let mut editor = Editor::new();
         editor.insert_bytes(0, &[65]);
 
-        assert_eq!(editor.remove_bytes(0, 1), &[65]);
+        assert_eq!(editor.remove_bytes(0, 1), Ok(vec![65]));
         assert_eq!(editor.active_content.as_slice(), []);
-        assert_eq!(editor.remove_bytes(1000, 300), &[]);
+        assert_eq!(editor.remove_bytes(1000, 300), Ok(vec![]));
 
     }
     #[test]
@@ -69,7 +668,7 @@ mod tests {
         let mut editor = Editor::new();
         editor.insert_bytes(0, &[65]);
         editor.set_cursor_offset(0);
-        assert_eq!(editor.remove_bytes_at_cursor(), &[65]);
+        assert_eq!(editor.remove_bytes_at_cursor(), Ok(vec![65]));
         assert_eq!(editor.active_content.as_slice(), []);
     }
 
@@ -105,7 +704,7 @@ mod tests {
             Some(vec![(2,3), (3,4), (8,9)])
         );
 
-        editor.remove_bytes(0, 9);
+        editor.remove_bytes(0, 9).ok();
 
         editor.insert_bytes(
             0,
@@ -132,6 +731,60 @@ mod tests {
         assert!(editor.find_all("\\b00000.0b").is_err());
     }
 
+    #[test]
+    fn test_find_in_range() {
+        let mut editor = Editor::new();
+        editor.insert_bytes(0, b"ABxABxABxAB").ok();
+        assert_eq!(editor.find_all_in_range("AB", (2, 9)), Ok(vec![(3, 5), (6, 8)]));
+        assert_eq!(editor.find_all_in_range("AB", (4, 10)), Ok(vec![(6, 8)]));
+        // Searching wraps around within the range
+        assert_eq!(editor.find_nth_after("AB", 6, 0, (2, 9)), Ok(Some((3, 5))));
+        assert_eq!(editor.find_nth_before("AB", 3, 0, (2, 9)), Ok(Some((6, 8))));
+
+        assert_eq!(editor.replace_in_range("AB", b"ab", (3, 8)), Ok(vec![6, 3]));
+        assert_eq!(editor.active_content.as_slice(), "ABxabxabxAB".as_bytes());
+    }
+
+    #[test]
+    fn test_spawn_find() {
+        let mut editor = Editor::new();
+        editor.insert_bytes(0, b"ABxABxABxAB").ok();
+
+        let mut job = editor.spawn_find("AB", (2, 9)).unwrap();
+        // Changes made while it runs don't reach the worker's snapshot
+        editor.insert_bytes(0, b"AB").ok();
+        while !job.is_finished() {
+            job.wait();
+        }
+        assert_eq!(job.get_percent(), 100);
+        assert_eq!(job.into_hits(), vec![(3, 5, ()), (6, 8, ())]);
+
+        let substitution = Substitution::parse("$1$1").unwrap();
+        let mut job = editor.spawn_find_replacements("(A)B", substitution, (0, 4)).unwrap();
+        while !job.is_finished() {
+            job.wait();
+        }
+        assert_eq!(job.into_hits(), vec![(0, 2, b"AA".to_vec()), (2, 4, b"AA".to_vec())]);
+
+        assert_eq!(editor.spawn_find("(", (0, 4)).err(), Some(SbyteError::InvalidRegex("(".to_string())));
+    }
+
+    #[test]
+    fn test_search_highlights() {
+        let mut editor = Editor::new();
+        editor.insert_bytes(0, b"ABxABxABxAB").ok();
+        assert_eq!(editor.get_search_mask(0, 4), vec![]);
+
+        let matches = editor.find_all("AB").unwrap();
+        editor.set_search_highlights(matches);
+        assert_eq!(editor.get_search_mask(2, 5), vec![false, true, true, false, true]);
+        // Past the end of the buffer nothing is highlighted
+        assert_eq!(editor.get_search_mask(9, 4), vec![true, true, false, false]);
+
+        editor.clear_search_highlights();
+        assert_eq!(editor.get_search_mask(2, 5), vec![]);
+    }
+
     #[test]
     fn test_increment_byte() {
         let mut editor = Editor::new();
@@ -150,7 +803,7 @@ mod tests {
         editor.insert_bytes(0, &[1]);
         assert!(editor.decrement_byte(0, 1).is_ok(), "Failed to decrement byte");
 
-        let task = editor.undo_stack.last();
+        let task = Some(editor.undo_tree.get_task(editor.undo_tree.get_current()));
         assert!(task.is_some());
         assert_eq!(task.unwrap().0, 0);
         assert_eq!(task.unwrap().1, 1);
@@ -181,15 +834,15 @@ mod tests {
         editor.insert_bytes(0, &[0]);
         editor.insert_bytes(0, &[0]);
         editor.insert_bytes(0, &[0]);
-        assert_eq!(editor.undo_stack.len(), 3);
+        assert_eq!(editor.undo_tree.depth(), 3);
         assert!(editor.undo().is_ok());
-        assert_eq!(editor.undo_stack.len(), 0, "Sequential tasks aren't getting undone");
+        assert_eq!(editor.undo_tree.depth(), 0, "Sequential tasks aren't getting undone");
 
         editor.insert_bytes(0, &[0]);
         thread::sleep(time::Duration::from_nanos(100_000_000));
         editor.insert_bytes(0, &[0]);
         assert!(editor.undo().is_ok());
-        assert_eq!(editor.undo_stack.len(), 1, "Undo is merging unrelated tasks");
+        assert_eq!(editor.undo_tree.depth(), 1, "Undo is merging unrelated tasks");
 
 
     }
@@ -204,21 +857,125 @@ mod tests {
 
         assert!(editor.redo().is_ok(), "Failed to redo");
 
-        assert_eq!(editor.redo_stack.len(), 0);
+        assert_eq!(editor.undo_tree.redo_depth(), 0);
 
         thread::sleep(time::Duration::from_nanos(60_000_000));
 
         editor.insert_bytes(0, &[0]);
         editor.insert_bytes(0, &[0]);
         editor.undo();
-        assert_eq!(editor.redo_stack.len(), 2);
+        assert_eq!(editor.undo_tree.redo_depth(), 2);
         editor.undo();
-        assert_eq!(editor.redo_stack.len(), 3);
+        assert_eq!(editor.undo_tree.redo_depth(), 3);
         editor.redo();
-        assert_eq!(editor.redo_stack.len(), 1);
+        assert_eq!(editor.undo_tree.redo_depth(), 2, "Redo is merging unrelated tasks");
         editor.redo();
-        assert_eq!(editor.redo_stack.len(), 0);
+        assert_eq!(editor.undo_tree.redo_depth(), 0);
+
+    }
 
+    #[test]
+    fn test_undo_tree() {
+        let mut editor = Editor::new();
+        editor.insert_bytes(0, b"A").ok();
+        thread::sleep(time::Duration::from_millis(60));
+        editor.insert_bytes(0, b"B").ok();
+        thread::sleep(time::Duration::from_millis(60));
+        editor.undo().ok();
+        editor.insert_bytes(0, b"C").ok();
+        assert_eq!(editor.active_content.as_slice(), "CA".as_bytes());
+        assert!(editor.redo().is_err());
+
+        // The undone "B" is kept on its own branch
+        let branches = editor.get_undo_branches();
+        assert_eq!(branches.len(), 2);
+        assert_eq!((branches[0].0, branches[0].1), (2, 2));
+        assert_eq!((branches[1].0, branches[1].1), (3, 2));
+
+        assert_eq!(editor.undo_chronological(false).ok(), Some(2));
+        assert_eq!(editor.active_content.as_slice(), "BA".as_bytes());
+        assert_eq!(editor.undo_chronological(false).ok(), Some(1));
+        assert_eq!(editor.active_content.as_slice(), "A".as_bytes());
+        assert_eq!(editor.undo_chronological(true).ok(), Some(2));
+        assert_eq!(editor.undo_chronological(true).ok(), Some(3));
+        assert_eq!(editor.active_content.as_slice(), "CA".as_bytes());
+        assert!(editor.undo_chronological(true).is_err());
+
+        assert!(editor.undo_goto(0).is_ok());
+        assert_eq!(editor.active_content.as_slice(), []);
+        assert!(editor.undo_goto(2).is_ok());
+        assert_eq!(editor.active_content.as_slice(), "BA".as_bytes());
+        assert_eq!(editor.get_undo_position(), 2);
+        assert!(editor.undo_goto(4).is_err());
+
+        // Redo follows the branch last moved along
+        editor.undo().ok();
+        editor.redo().ok();
+        assert_eq!(editor.active_content.as_slice(), "BA".as_bytes());
+
+        let hour = time::Duration::from_secs(3600);
+        assert_eq!(editor.undo_by_time(hour, false).ok(), Some(0));
+        assert_eq!(editor.active_content.as_slice(), []);
+        assert_eq!(editor.undo_by_time(hour, true).ok(), Some(3));
+        assert_eq!(editor.active_content.as_slice(), "CA".as_bytes());
+    }
+
+    #[test]
+    fn test_undo_transaction() {
+        let mut editor = Editor::new();
+        editor.load_file("src/testfiles/00").expect("Couldn't open file");
+
+        editor.begin_transaction();
+        editor.overwrite_bytes(0, b"B").ok();
+        thread::sleep(time::Duration::from_millis(60));
+        editor.begin_transaction();
+        editor.overwrite_bytes(4, b"P").ok();
+        editor.end_transaction();
+        editor.end_transaction();
+
+        // Immediately after, but a separate action
+        editor.begin_transaction();
+        editor.overwrite_bytes(8, b"K").ok();
+        editor.end_transaction();
+
+        assert_eq!(editor.undo().ok(), Some(1));
+        assert_eq!(editor.active_content.as_slice(), "BESTPILECONTENTS".as_bytes());
+        assert_eq!(editor.undo().ok(), Some(2), "Transaction was split by time");
+        assert_eq!(editor.active_content.as_slice(), "TESTFILECONTENTS".as_bytes());
+        assert_eq!(editor.redo().ok(), Some(2));
+        assert_eq!(editor.active_content.as_slice(), "BESTPILECONTENTS".as_bytes());
+
+        // Replacing undoes as one, however many matches
+        editor.replace("T", b"tt").ok();
+        assert_eq!(editor.active_content.as_slice(), "BESttPILECONttENttS".as_bytes());
+        assert_eq!(editor.undo().ok(), Some(3));
+        assert_eq!(editor.active_content.as_slice(), "BESTPILECONTENTS".as_bytes());
+
+        // Bytes typed one command at a time undo together
+        editor.begin_transaction();
+        editor.insert_bytes(0, b"A").ok();
+        editor.end_transaction();
+        editor.begin_transaction();
+        editor.insert_bytes(1, b"B").ok();
+        editor.end_transaction();
+        assert_eq!(editor.undo().ok(), Some(1));
+        assert_eq!(editor.active_content.as_slice(), "BESTPILECONTENTS".as_bytes());
+
+        // A command carrying on the typing is undone with it whole, while the next one is its own
+        editor.begin_transaction();
+        editor.insert_bytes(0, b"A").ok();
+        editor.end_transaction();
+        editor.begin_transaction();
+        editor.insert_bytes(1, b"B").ok();
+        editor.overwrite_bytes(6, b"X").ok();
+        editor.end_transaction();
+        editor.begin_transaction();
+        editor.overwrite_bytes(9, b"Y").ok();
+        editor.end_transaction();
+        assert_eq!(editor.undo().ok(), Some(1));
+        assert_eq!(editor.active_content.as_slice(), "ABBESTXILECONTENTS".as_bytes());
+        assert_eq!(editor.undo().ok(), Some(2));
+        assert_eq!(editor.active_content.as_slice(), "BESTPILECONTENTS".as_bytes());
     }
 
     #[test]
@@ -0,0 +1,162 @@
+// Values written with their type, like u32le:0xDEADBEEF or utf16le:"Hello", and the bytes they're stored as
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum TypedValueError {
+    InvalidValue(String),
+    // The value is a number, but not one the type can hold
+    OutOfRange(String)
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum ValueType {
+    // Width in bytes, is big endian
+    Unsigned(usize, bool),
+    Signed(usize, bool),
+    Float(usize, bool),
+    Utf8,
+    Utf16(bool)
+}
+
+/// The bytes of a value written as `TYPE:VALUE`, or None if `text` doesn't start with a type.
+///
+/// Integers are u8 .. u64 and i8 .. i64, and floats are f32 and f64, each with an optional le/be suffix
+/// (little endian by default). Integers can be written in hex, binary or octal with 0x, 0b or 0o.
+/// Strings are utf8, utf16le and utf16be, with or without quotes around them.
+pub fn parse_typed_value(text: &str) -> Option<Result<Vec<u8>, TypedValueError>> {
+    let (type_name, value) = text.split_once(':')?;
+    let value_type = parse_type(&type_name.to_lowercase())?;
+
+    Some(encode(value_type, value))
+}
+
+fn parse_type(type_name: &str) -> Option<ValueType> {
+    match type_name {
+        "utf8" | "utf-8" => {
+            return Some(ValueType::Utf8);
+        }
+        "utf16" | "utf16le" | "utf-16le" => {
+            return Some(ValueType::Utf16(false));
+        }
+        "utf16be" | "utf-16be" => {
+            return Some(ValueType::Utf16(true));
+        }
+        _ => {}
+    }
+
+    let (name, big_endian) = if let Some(name) = type_name.strip_suffix("be") {
+        (name, true)
+    } else if let Some(name) = type_name.strip_suffix("le") {
+        (name, false)
+    } else {
+        (type_name, false)
+    };
+
+    match name {
+        "u8" => Some(ValueType::Unsigned(1, big_endian)),
+        "u16" => Some(ValueType::Unsigned(2, big_endian)),
+        "u32" => Some(ValueType::Unsigned(4, big_endian)),
+        "u64" => Some(ValueType::Unsigned(8, big_endian)),
+        "i8" => Some(ValueType::Signed(1, big_endian)),
+        "i16" => Some(ValueType::Signed(2, big_endian)),
+        "i32" => Some(ValueType::Signed(4, big_endian)),
+        "i64" => Some(ValueType::Signed(8, big_endian)),
+        "f32" => Some(ValueType::Float(4, big_endian)),
+        "f64" => Some(ValueType::Float(8, big_endian)),
+        _ => None
+    }
+}
+
+fn encode(value_type: ValueType, value: &str) -> Result<Vec<u8>, TypedValueError> {
+    let mut bytes = match value_type {
+        ValueType::Unsigned(width, _) | ValueType::Signed(width, _) => {
+            let number = parse_integer(value)?;
+            let bits = width as u32 * 8;
+            let (min, max) = match value_type {
+                ValueType::Signed(..) => (-(1i128 << (bits - 1)), (1i128 << (bits - 1)) - 1),
+                _ => (0, (1i128 << bits) - 1)
+            };
+            if number < min || number > max {
+                return Err(TypedValueError::OutOfRange(value.to_string()));
+            }
+
+            // Little endian, two's complement
+            number.to_le_bytes()[0 .. width].to_vec()
+        }
+        ValueType::Float(width, _) => {
+            let number: f64 = match value.trim().parse() {
+                Ok(number) => number,
+                Err(_) => {
+                    return Err(TypedValueError::InvalidValue(value.to_string()));
+                }
+            };
+
+            if width == 4 {
+                (number as f32).to_le_bytes().to_vec()
+            } else {
+                number.to_le_bytes().to_vec()
+            }
+        }
+        ValueType::Utf8 => {
+            unquote(value).as_bytes().to_vec()
+        }
+        ValueType::Utf16(big_endian) => {
+            let mut bytes = vec![];
+            for unit in unquote(value).encode_utf16() {
+                if big_endian {
+                    bytes.extend_from_slice(&unit.to_be_bytes());
+                } else {
+                    bytes.extend_from_slice(&unit.to_le_bytes());
+                }
+            }
+            bytes
+        }
+    };
+
+    match value_type {
+        ValueType::Unsigned(_, true) | ValueType::Signed(_, true) | ValueType::Float(_, true) => {
+            bytes.reverse();
+        }
+        _ => {}
+    }
+
+    Ok(bytes)
+}
+
+fn parse_integer(value: &str) -> Result<i128, TypedValueError> {
+    let trimmed = value.trim().to_lowercase();
+    let (negative, digits) = match trimmed.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, trimmed.as_str())
+    };
+
+    let (digits, radix) = if let Some(digits) = digits.strip_prefix("0x") {
+        (digits, 16)
+    } else if let Some(digits) = digits.strip_prefix("0b") {
+        (digits, 2)
+    } else if let Some(digits) = digits.strip_prefix("0o") {
+        (digits, 8)
+    } else {
+        (digits, 10)
+    };
+
+    // Leave signs to the check above, so "--1" and "0x-1" aren't taken
+    if digits.starts_with(['-', '+']) {
+        return Err(TypedValueError::InvalidValue(value.to_string()));
+    }
+
+    match i128::from_str_radix(&digits.replace('_', ""), radix) {
+        Ok(number) if negative => Ok(-number),
+        Ok(number) => Ok(number),
+        Err(_) => Err(TypedValueError::InvalidValue(value.to_string()))
+    }
+}
+
+fn unquote(value: &str) -> &str {
+    for quote in ['"', '\''] {
+        if value.len() >= 2 && value.starts_with(quote) && value.ends_with(quote) {
+            return &value[1 .. value.len() - 1];
+        }
+    }
+
+    value
+}
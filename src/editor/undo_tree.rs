@@ -0,0 +1,240 @@
+use super::UndoTask;
+
+struct UndoNode {
+    parent: usize,
+    children: Vec<usize>,
+    // The child redo moves to: the one most recently created or moved back from
+    current_child: Option<usize>,
+    // Undoes the change if this node is applied (on the path from the root to the current node),
+    // otherwise redoes it
+    task: UndoTask,
+    // The first node of the transaction this change was made in, if it was made in one
+    transaction: Option<usize>
+}
+
+/// Every state the content has been in, as a tree of changes.
+/// Making a change after undoing starts a new branch rather than discarding what was undone.
+/// Node 0 is the unchanged content; the rest are numbered in the order they were made.
+pub struct UndoTree {
+    nodes: Vec<UndoNode>,
+    current: usize,
+    // The first node of the transaction changes are currently being made in
+    open_transaction: Option<usize>,
+    // The node whose state was last loaded or saved
+    saved: usize
+}
+
+impl UndoTree {
+    pub fn new() -> UndoTree {
+        UndoTree {
+            nodes: vec![UndoNode {
+                parent: 0,
+                children: Vec::new(),
+                current_child: None,
+                task: (0, 0, Vec::new(), std::time::Instant::now()),
+                transaction: None
+            }],
+            current: 0,
+            open_transaction: None,
+            saved: 0
+        }
+    }
+
+    /// Rebuild a tree from its nodes' (parent, current_child, task, transaction), as given by `get_nodes`
+    pub fn from_nodes(nodes: Vec<(usize, Option<usize>, UndoTask, Option<usize>)>, current: usize) -> Option<UndoTree> {
+        let mut tree = UndoTree {
+            nodes: Vec::new(),
+            current,
+            open_transaction: None,
+            saved: current
+        };
+
+        for (i, (parent, current_child, task, transaction)) in nodes.into_iter().enumerate() {
+            // Parents always come before their children
+            if i > 0 {
+                if parent >= i {
+                    return None;
+                }
+                tree.nodes[parent].children.push(i);
+            }
+
+            tree.nodes.push(UndoNode {
+                parent,
+                children: Vec::new(),
+                current_child,
+                task,
+                transaction
+            });
+        }
+
+        for (i, node) in tree.nodes.iter().enumerate() {
+            if let Some(child) = node.current_child {
+                if !node.children.contains(&child) {
+                    return None;
+                }
+            }
+            if i == 0 && node.parent != 0 {
+                return None;
+            }
+        }
+
+        if current < tree.nodes.len() {
+            Some(tree)
+        } else {
+            None
+        }
+    }
+
+    /// Every node, the root first, as (parent, current_child, task, transaction)
+    pub fn get_nodes(&self) -> Vec<(usize, Option<usize>, &UndoTask, Option<usize>)> {
+        self.nodes.iter().map(|node| {
+            (node.parent, node.current_child, &node.task, node.transaction)
+        }).collect()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.len() == 1
+    }
+
+    pub fn get_current(&self) -> usize {
+        self.current
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Number of changes applied to get to the current state
+    pub fn depth(&self) -> usize {
+        self.get_path(self.current).len()
+    }
+
+    /// Number of changes that can be redone along the current branch
+    pub fn redo_depth(&self) -> usize {
+        let mut depth = 0;
+        let mut node = self.current;
+        while let Some(child) = self.nodes[node].current_child {
+            depth += 1;
+            node = child;
+        }
+
+        depth
+    }
+
+    /// The task undoing the latest change, if a change made `in_transaction` or not can be merged into it.
+    /// It can't be changed once it's been saved or there are other states branching from it. Changes made in a
+    /// transaction merge with the ones already made in that same transaction, or, as a new transaction's first,
+    /// with a change that was all of the one before, like a byte typed after another.
+    pub fn get_current_task_mut(&mut self, in_transaction: bool) -> Option<&mut UndoTask> {
+        let transaction = if in_transaction {
+            Some(self.open_transaction.unwrap_or(self.current))
+        } else {
+            None
+        };
+
+        let node = &mut self.nodes[self.current];
+        if self.current == 0 || self.current == self.saved || !node.children.is_empty() || node.transaction != transaction {
+            None
+        } else {
+            Some(&mut node.task)
+        }
+    }
+
+    /// Carry on the latest change's transaction, once the first change of a new one has been merged into it
+    pub fn resume_transaction(&mut self) {
+        if self.open_transaction.is_none() {
+            self.open_transaction = self.nodes[self.current].transaction;
+        }
+    }
+
+    pub fn get_task(&self, node: usize) -> &UndoTask {
+        &self.nodes[node].task
+    }
+
+    pub fn get_transaction(&self, node: usize) -> Option<usize> {
+        self.nodes[node].transaction
+    }
+
+    pub fn get_redo_child(&self) -> Option<usize> {
+        self.nodes[self.current].current_child
+    }
+
+    /// Add a change made in the current state, as part of the open transaction if `in_transaction`
+    pub fn push(&mut self, task: UndoTask, in_transaction: bool) {
+        let index = self.nodes.len();
+        let transaction = if in_transaction {
+            Some(*self.open_transaction.get_or_insert(index))
+        } else {
+            None
+        };
+
+        self.nodes.push(UndoNode {
+            parent: self.current,
+            children: Vec::new(),
+            current_child: None,
+            task,
+            transaction
+        });
+
+        let current = &mut self.nodes[self.current];
+        current.children.push(index);
+        current.current_child = Some(index);
+        self.current = index;
+    }
+
+    /// Note the current state as the one on disk. Later changes won't be merged into it.
+    pub fn mark_saved(&mut self) {
+        self.saved = self.current;
+    }
+
+    /// Whether the current state is the one last loaded or saved
+    pub fn is_saved(&self) -> bool {
+        self.current == self.saved
+    }
+
+    /// Changes pushed after this belong to a new transaction
+    pub fn close_transaction(&mut self) {
+        self.open_transaction = None;
+    }
+
+    /// Move to the parent state once the current node's change has been undone by its task,
+    /// leaving `redo_task` in its place.
+    pub fn step_back(&mut self, redo_task: UndoTask) {
+        let node = self.current;
+        self.nodes[node].task = redo_task;
+        self.current = self.nodes[node].parent;
+        self.nodes[self.current].current_child = Some(node);
+    }
+
+    /// Move to the child state once its change has been redone, leaving `undo_task` in its place.
+    pub fn step_forward(&mut self, child: usize, undo_task: UndoTask) {
+        self.nodes[self.current].current_child = Some(child);
+        self.nodes[child].task = undo_task;
+        self.current = child;
+    }
+
+    /// The nodes from (not including) the root down to `node`
+    pub fn get_path(&self, node: usize) -> Vec<usize> {
+        let mut path = Vec::new();
+        let mut working_node = node;
+        while working_node != 0 {
+            path.push(working_node);
+            working_node = self.nodes[working_node].parent;
+        }
+        path.reverse();
+
+        path
+    }
+
+    /// The states no later change was made from, the ends of each branch
+    pub fn get_leaves(&self) -> Vec<usize> {
+        let mut leaves = Vec::new();
+        for (i, node) in self.nodes.iter().enumerate().skip(1) {
+            if node.children.is_empty() {
+                leaves.push(i);
+            }
+        }
+
+        leaves
+    }
+}
@@ -224,6 +224,10 @@ impl Inputter {
         self.active_node = self.get_mode_root(new_context);
     }
 
+    pub fn get_context(&self) -> &str {
+        &self.context
+    }
+
     pub fn is_alive(&self) -> bool {
         ! self.killed
     }
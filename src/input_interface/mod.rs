@@ -6,6 +6,7 @@ use std::sync::{Mutex, Arc};
 pub mod tests;
 pub mod inputter;
 mod platform;
+pub use platform::reattach_terminal;
 
 use super::shell::{Shell, parse_words};
 use super::editor::SbyteError;
@@ -43,6 +44,10 @@ impl InputInterface {
         interface
     }
 
+    pub fn get_shell_mut(&mut self) -> &mut Shell {
+        &mut self.shell
+    }
+
     fn setup_default_controls(&mut self) -> Result<(), SbyteError> {
         // Default Controls
         self.hook_assign_mode_input(&["DEFAULT", "TOGGLE_FORMATTER", "EQUALS"]);
@@ -71,6 +76,8 @@ impl InputInterface {
         self.hook_assign_mode_input(&["DEFAULT", "PASTE", "P_LOWER"]);
         self.hook_assign_mode_input(&["DEFAULT", "UNDO", "U_LOWER"]);
         self.hook_assign_mode_input(&["DEFAULT", "REDO", "CTRL+R"]);
+        self.hook_assign_mode_input(&["DEFAULT", "UNDO_OLDER", "G_LOWER,DASH"]);
+        self.hook_assign_mode_input(&["DEFAULT", "UNDO_NEWER", "G_LOWER,PLUS"]);
         self.hook_assign_mode_input(&["DEFAULT", "CLEAR_REGISTER", "ESCAPE"]);
         self.hook_assign_mode_input(&["DEFAULT", "INCREMENT", "PLUS"]);
         self.hook_assign_mode_input(&["DEFAULT", "DECREMENT", "DASH"]);
@@ -90,6 +97,11 @@ impl InputInterface {
         self.hook_assign_mode_input(&["DEFAULT", "MODE_SET_PLAYBACK_KEY", "AT"]);
         self.hook_assign_mode_input(&["DEFAULT", "BITWISE_NOT", "TILDE"]);
 
+        self.hook_assign_mode_input(&["DEFAULT", "DIFF_NEXT", "BRACKET_CLOSE,C_LOWER"]);
+        self.hook_assign_mode_input(&["DEFAULT", "DIFF_PREV", "BRACKET_OPEN,C_LOWER"]);
+        self.hook_assign_mode_input(&["DEFAULT", "DIFF_GET", "D_LOWER,O_LOWER"]);
+        self.hook_assign_mode_input(&["DEFAULT", "DIFF_PUT", "D_LOWER,P_LOWER"]);
+
         self.hook_assign_mode_input(&["OVERWRITE_BIN", "SUBCURSOR_LEFT", "H_LOWER"]);
         self.hook_assign_mode_input(&["OVERWRITE_BIN", "SUBCURSOR_RIGHT", "L_LOWER"]);
         self.hook_assign_mode_input(&["OVERWRITE_DEC", "SUBCURSOR_LEFT", "H_LOWER"]);
@@ -126,6 +138,13 @@ impl InputInterface {
             self.hook_assign_mode_input(&["DEFAULT", "APPEND_TO_REGISTER", &keycode, &strrep]);
         }
 
+        // "a through "z pick a register for the next yank, paste or delete, and "A through "Z append to one
+        for c in (b'a' ..= b'z').chain(b'A' ..= b'Z').chain(b"-\"".iter().copied()) {
+            let strrep = std::str::from_utf8(&[c]).unwrap().to_string();
+            let keycode = ascii_map.get(&vec![c]).unwrap();
+            self.hook_assign_mode_input(&["DEFAULT", "SELECT_REGISTER", &format!("QUOTE,{}", keycode), &strrep]);
+        }
+
         for c in b"0123456789abcdef".iter() {
             let strrep = std::str::from_utf8(&[*c]).unwrap().to_string();
             let keycode = ascii_map.get(&vec![*c]).unwrap();
@@ -152,6 +171,15 @@ impl InputInterface {
         self.hook_assign_mode_input(&["CMD", "CMDLINE_CURSOR_BACK", "ARROW_LEFT"]);
         self.hook_assign_mode_input(&["CMD", "CMDLINE_CURSOR_FWD", "ARROW_RIGHT"]);
 
+        self.hook_assign_mode_input(&["REPLACE_CONFIRM", "REPLACE_ANSWER", "Y_LOWER", "y"]);
+        self.hook_assign_mode_input(&["REPLACE_CONFIRM", "REPLACE_ANSWER", "N_LOWER", "n"]);
+        self.hook_assign_mode_input(&["REPLACE_CONFIRM", "REPLACE_ANSWER", "A_LOWER", "a"]);
+        self.hook_assign_mode_input(&["REPLACE_CONFIRM", "REPLACE_ANSWER", "Q_LOWER", "q"]);
+        self.hook_assign_mode_input(&["REPLACE_CONFIRM", "REPLACE_ANSWER", "ESCAPE", "q"]);
+
+        self.hook_assign_mode_input(&["SEARCHING", "SEARCH_CANCEL", "ESCAPE"]);
+        self.hook_assign_mode_input(&["SEARCHING", "SEARCH_CANCEL", "ETX"]);
+
         Ok(())
     }
 
@@ -196,7 +224,12 @@ impl InputInterface {
             while !ok {
                 match signal_mutex.try_lock() {
                     Ok(ref mut mutex) => {
-                        mutex.kill();
+                        // A search is called off, as if Ctrl+C had come in as a key, rather than the editor
+                        if mutex.get_context() == "SEARCHING" {
+                            mutex.input(b'\x03');
+                        } else {
+                            mutex.kill();
+                        }
                         ok = true;
                     }
                     Err(_e) => ()
@@ -223,6 +256,12 @@ impl InputInterface {
         let mut result = Ok(());
 
         while self.running {
+            // Searches run on a worker thread, handing over what they find a bit at a time
+            if self.shell.poll_search() {
+                self.leave_search_context();
+            }
+            self.shell.poll_incremental_search();
+
             self.frontend.tick(&mut self.shell).ok();
 
             let mut funcpair = None;
@@ -500,6 +539,11 @@ impl InputInterface {
                 self.shell.buffer_push("xor ");
             }
 
+            "SEARCH_CANCEL" => {
+                self.shell.cancel_search();
+                self.set_context("DEFAULT");
+            }
+
             "MODE_SET_SEARCH" => {
                 self.set_context("CMD");
                 self.shell.buffer_push("find ");
@@ -523,7 +567,18 @@ impl InputInterface {
             something_else => {
                 output = match self.shell.try_command(something_else, arguments) {
                     Ok(()) => {
-                        if something_else == "RUN_CUSTOM_COMMAND" {
+                        if self.shell.is_searching() {
+                            // Until the search is done, keys other than those calling it off are dropped
+                            self.set_context("SEARCHING");
+                        } else if self.shell.is_confirming_replace() {
+                            // Only answers are taken until every match has one
+                            if something_else != "REPLACE_ANSWER" {
+                                self.set_context("REPLACE_CONFIRM");
+                                if let Some(prompt) = self.shell.get_replace_prompt() {
+                                    self.shell.log_feedback(&prompt);
+                                }
+                            }
+                        } else if something_else == "RUN_CUSTOM_COMMAND" || something_else == "REPLACE_ANSWER" {
                             self.set_context("DEFAULT");
                         }
                         Ok(())
@@ -542,6 +597,16 @@ impl InputInterface {
                         self.set_context("DEFAULT");
                         Ok(())
                     }
+                    Err(SbyteError::ReadOnly) => {
+                        self.shell.log_error("file is read-only");
+                        self.set_context("DEFAULT");
+                        Ok(())
+                    }
+                    Err(SbyteError::LockedRange(start, end)) => {
+                        self.shell.log_error(&format!("bytes {}-{} are locked", start, end - 1));
+                        self.set_context("DEFAULT");
+                        Ok(())
+                    }
                     Err(e) => {
                         Err(e)
                     }
@@ -635,17 +700,7 @@ impl InputInterface {
                 let working_cmds: Vec<&str> = std::str::from_utf8(buffer.as_slice()).unwrap().split("\n").collect();
 
                 for query in working_cmds.iter() {
-                    let mut words = parse_words(query);
-
-                    if !words.is_empty() {
-                        let cmd = words.remove(0);
-                        let mut args = vec![];
-                        for word in words.iter() {
-                            args.push(word.as_str());
-                        }
-                        self.send_command(&cmd, args.as_slice())?;
-                    }
-
+                    self.run_command_line(query)?;
                 }
             }
             Err(_e) => ()
@@ -655,10 +710,38 @@ impl InputInterface {
     }
 
 
+    /// Run a line as it would be written in the rc file
+    pub fn run_command_line(&mut self, query: &str) -> Result<(), SbyteError> {
+        let mut words = parse_words(query);
+
+        if !words.is_empty() {
+            let cmd = words.remove(0);
+            let mut args = vec![];
+            for word in words.iter() {
+                args.push(word.as_str());
+            }
+            self.send_command(&cmd, args.as_slice())?;
+        }
+
+        Ok(())
+    }
+
     fn auto_resize(&mut self) {
         self.frontend.auto_resize(&mut self.shell);
     }
 
+    // Go on from a finished search to whatever it left to do
+    fn leave_search_context(&mut self) {
+        if self.shell.is_confirming_replace() {
+            self.set_context("REPLACE_CONFIRM");
+            if let Some(prompt) = self.shell.get_replace_prompt() {
+                self.shell.log_feedback(&prompt);
+            }
+        } else {
+            self.set_context("DEFAULT");
+        }
+    }
+
     fn set_context(&mut self, new_context: &str) {
         self.shell.buffer_clear();
 
@@ -6,3 +6,35 @@ pub fn get_input_reader() -> Reader {
     stdin()
 }
 
+
+/// If stdin or stdout were redirected (sbyte in a pipeline), point them back at the terminal
+/// so it can still be drawn to and read keys from.
+/// Gives the original stdout, if it was redirected, for the content to be written out to.
+pub fn reattach_terminal() -> std::io::Result<Option<std::fs::File>> {
+    use std::fs::{File, OpenOptions};
+    use std::os::unix::io::{AsRawFd, FromRawFd};
+
+    let stdin_is_tty = unsafe { libc::isatty(libc::STDIN_FILENO) } == 1;
+    let stdout_is_tty = unsafe { libc::isatty(libc::STDOUT_FILENO) } == 1;
+    if stdin_is_tty && stdout_is_tty {
+        return Ok(None);
+    }
+
+    let tty = OpenOptions::new().read(true).write(true).open("/dev/tty")?;
+    let mut original_stdout = None;
+    if !stdout_is_tty {
+        let fd = unsafe { libc::dup(libc::STDOUT_FILENO) };
+        if fd < 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        original_stdout = Some(unsafe { File::from_raw_fd(fd) });
+        if unsafe { libc::dup2(tty.as_raw_fd(), libc::STDOUT_FILENO) } < 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+    }
+    if !stdin_is_tty && unsafe { libc::dup2(tty.as_raw_fd(), libc::STDIN_FILENO) } < 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+
+    Ok(original_stdout)
+}
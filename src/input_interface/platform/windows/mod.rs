@@ -11,6 +11,11 @@ pub fn get_input_reader() -> Reader {
     Reader::new()
 }
 
+/// Redirected stdin and stdout aren't pointed back at the console here, so the original stdout isn't given
+pub fn reattach_terminal() -> std::io::Result<Option<std::fs::File>> {
+    Ok(None)
+}
+
 //DEBUG
 use std::fs::OpenOptions;
 use std::io::prelude::*;
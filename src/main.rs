@@ -1,49 +1,364 @@
 use std::env;
+use std::io;
 use std::path::Path;
 use std::error::Error;
+use std::process;
 use dirs::home_dir;
 
 pub mod editor;
 pub mod input_interface;
 pub mod console_displayer;
 pub mod shell;
+pub mod batch;
+pub mod dump;
+pub mod clipboard;
+#[cfg(test)]
+mod tests;
 
-use input_interface::InputInterface;
+use input_interface::{InputInterface, reattach_terminal};
 use console_displayer::FrontEnd;
 use shell::Shell;
+use editor::SbyteError;
+use editor::formatter::FormatterRef;
+use editor::string_to_integer;
+use editor::records::FileFormat;
 
-fn result_catcher() -> Result<(), Box<dyn Error>> {
-    let args: Vec<String> = env::args().collect();
+const USAGE: &str = "\
+Usage: sbyte [OPTIONS] [FILE]...
+       sbyte dump [-r] [OPTIONS] [INFILE [OUTFILE]]  (see sbyte dump --help)
 
+A FILE of - reads from stdin. ':w -' writes to stdout and ':w !CMD' pipes to CMD.
+
+Options:
+  --offset N         Start with the cursor at offset N
+  --width N          Show N bytes per row
+  --format FORMAT    Show bytes as hex, dec or bin
+  --file-format FMT  Read files as raw, ihex or srec instead of guessing from the name or content
+  --base N           Show and save the files as starting at address N
+  --pad N            Show gaps between ihex or srec records as byte N (default \\xFF)
+  -c CMD             Run CMD after loading, as if typed on the command line (repeatable)
+  --batch SCRIPT     Run each line of SCRIPT as a command without a terminal, then save and exit
+  --rc PATH          Load config from PATH instead of ~/.sbyterc
+  --no-rc            Don't load any config
+  -R, --readonly     Don't allow any changes
+  --version          Print the version and exit
+  -h, --help         Print this message and exit";
+
+struct Options {
+    paths: Vec<String>,
+    offset: Option<usize>,
+    width: Option<usize>,
+    format: Option<FormatterRef>,
+    file_format: Option<FileFormat>,
+    base_address: Option<usize>,
+    pad_byte: Option<u8>,
+    commands: Vec<String>,
+    batch_script: Option<String>,
+    rc_path: Option<String>,
+    no_rc: bool,
+    readonly: bool,
+    show_version: bool,
+    show_help: bool
+}
+
+fn parse_args(args: &[String]) -> Result<Options, String> {
+    let mut options = Options {
+        paths: Vec::new(),
+        offset: None,
+        width: None,
+        format: None,
+        file_format: None,
+        base_address: None,
+        pad_byte: None,
+        commands: Vec::new(),
+        batch_script: None,
+        rc_path: None,
+        no_rc: false,
+        readonly: false,
+        show_version: false,
+        show_help: false
+    };
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--offset" | "--width" | "--format" | "--file-format" | "--base" | "--pad" | "-c" | "--batch" | "--rc" => {
+                let value = match args.next() {
+                    Some(value) => value,
+                    None => {
+                        return Err(format!("{} needs a value", arg));
+                    }
+                };
+
+                match arg.as_str() {
+                    "--offset" => {
+                        options.offset = Some(parse_number(arg, value)?);
+                    }
+                    "--width" => {
+                        match parse_number(arg, value)? {
+                            0 => {
+                                return Err("--width must be at least 1".to_string());
+                            }
+                            width => {
+                                options.width = Some(width);
+                            }
+                        }
+                    }
+                    "--format" => {
+                        options.format = match value.to_lowercase().as_str() {
+                            "hex" => Some(FormatterRef::HEX),
+                            "dec" => Some(FormatterRef::DEC),
+                            "bin" => Some(FormatterRef::BIN),
+                            _ => {
+                                return Err(format!("unknown format: {} (expected hex, dec or bin)", value));
+                            }
+                        };
+                    }
+                    "--file-format" => {
+                        options.file_format = match FileFormat::from_name(value) {
+                            Some(file_format) => Some(file_format),
+                            None => {
+                                return Err(format!("unknown file format: {} (expected raw, ihex or srec)", value));
+                            }
+                        };
+                    }
+                    "--base" => {
+                        options.base_address = Some(parse_number(arg, value)?);
+                    }
+                    "--pad" => {
+                        match parse_number(arg, value)? {
+                            pad_byte if pad_byte <= 0xFF => {
+                                options.pad_byte = Some(pad_byte as u8);
+                            }
+                            _ => {
+                                return Err("--pad must be a single byte".to_string());
+                            }
+                        }
+                    }
+                    "-c" => {
+                        options.commands.push(value.to_string());
+                    }
+                    "--batch" => {
+                        options.batch_script = Some(value.to_string());
+                    }
+                    _ => {
+                        options.rc_path = Some(value.to_string());
+                    }
+                }
+            }
+            "--no-rc" => {
+                options.no_rc = true;
+            }
+            "-R" | "--readonly" => {
+                options.readonly = true;
+            }
+            "--version" => {
+                options.show_version = true;
+            }
+            "-h" | "--help" => {
+                options.show_help = true;
+            }
+            "--" => {
+                options.paths.extend(args.by_ref().cloned());
+            }
+            _ => {
+                if arg.starts_with('-') && arg.len() > 1 {
+                    return Err(format!("unknown option: {}", arg));
+                }
+                options.paths.push(arg.to_string());
+            }
+        }
+    }
+
+    Ok(options)
+}
+
+fn parse_number(option: &str, value: &str) -> Result<usize, String> {
+    match string_to_integer(value) {
+        Ok(number) => Ok(number),
+        Err(_) => Err(format!("{} needs a number, got {}", option, value))
+    }
+}
+
+// The shell with the files open, ready for the rc file and commands
+fn setup_shell(options: &Options) -> Result<Shell, SbyteError> {
     let mut shell = Shell::new();
-    match args.get(1) {
-        Some(path) => {
-            shell.get_editor_mut().load_file(path)?;
+    if let Some(pad_byte) = options.pad_byte {
+        shell.get_editor_mut().set_pad_byte(pad_byte);
+    }
+
+    for path in options.paths.iter() {
+        let editor = shell.get_editor_mut();
+        if path == "-" {
+            editor.open_buffer_from_reader(&mut io::stdin())?;
+        } else if let Some(file_format) = options.file_format {
+            editor.open_buffer_as(path, file_format)?;
+        } else {
+            editor.open_buffer(path)?;
+        }
+
+        if let Some(base_address) = options.base_address {
+            editor.set_base_address(base_address);
         }
-        None => { }
     }
+    if options.paths.len() > 1 {
+        shell.get_editor_mut().switch_to_buffer(0)?;
+    }
+
+    if let Some(offset) = options.offset {
+        shell.get_editor_mut().set_cursor_offset(offset)?;
+    }
+
+    Ok(shell)
+}
+
+// Options that the rc file can also set, applied after it so the command line wins
+fn apply_display_options(shell: &mut Shell, options: &Options) {
+    if options.readonly {
+        shell.set_option_flag("readonly", true);
+    }
+
+    let editor = shell.get_editor_mut();
+    if let Some(format) = options.format {
+        editor.set_active_formatter(format);
+    }
+    if options.width.is_some() {
+        editor.set_fixed_viewport_width(options.width);
+    }
+}
+
+// The rc file to load, if any. One given with --rc has to exist, while ~/.sbyterc is optional.
+fn get_rc_path(options: &Options) -> Result<Option<String>, String> {
+    if options.no_rc {
+        return Ok(None);
+    }
+
+    match &options.rc_path {
+        Some(path) if Path::new(path).exists() => Ok(Some(path.clone())),
+        Some(path) => Err(format!("{}: no such rc file", path)),
+        None => {
+            let path = home_dir().map(|home| home.join(".sbyterc"));
+            Ok(path.filter(|path| path.exists()).map(|path| path.to_string_lossy().to_string()))
+        }
+    }
+}
+
+fn result_catcher(options: Options, rc_path: Option<String>) -> Result<(), Box<dyn Error>> {
+    let mut shell = setup_shell(&options)?;
+    // Anything read from stdin has been read by now, so the terminal can take its place
+    if let Some(stdout) = reattach_terminal()? {
+        shell.set_output(Box::new(stdout));
+    }
+
     let frontend = FrontEnd::new();
     let mut input_interface = InputInterface::new(shell, frontend);
 
     // commands like setcmd run in custom_rc will overwrite whatever was set in the default
-    match home_dir() {
-        Some(home) => {
-            let custom_rc_path = &format!("{:?}/.sbyterc", home);
-            if Path::new(custom_rc_path).exists() {
-                input_interface.load_config(custom_rc_path)?;
+    if let Some(rc_path) = rc_path {
+        input_interface.load_config(&rc_path)?;
+    }
+    apply_display_options(input_interface.get_shell_mut(), &options);
+
+    for command in options.commands.iter() {
+        input_interface.run_command_line(command)?;
+    }
+
+    input_interface.main()?;
+
+    Ok(())
+}
+
+// Run the -c commands and the script with no terminal, saving at the end
+fn run_batch(options: Options, script_path: &str) -> Result<(), String> {
+    let mut shell = match setup_shell(&options) {
+        Ok(shell) => shell,
+        Err(e) => {
+            return Err(format!("{:?}", e));
+        }
+    };
+    shell.set_output(Box::new(io::stdout()));
+    apply_display_options(&mut shell, &options);
+
+    for command in options.commands.iter() {
+        match batch::run_line(&mut shell, command) {
+            Ok(true) => (),
+            Ok(false) => {
+                return Ok(());
+            }
+            Err(msg) => {
+                return Err(format!("-c {}: {}", command, msg));
             }
         }
-        None => {}
     }
 
-    input_interface.main()?;
+    if batch::run_script(&mut shell, script_path)? {
+        batch::save_all(&mut shell)?;
+    }
 
     Ok(())
 }
 
+fn run_dump(args: &[String]) {
+    let options = match dump::parse_args(args) {
+        Ok(options) => options,
+        Err(msg) => {
+            eprintln!("sbyte dump: {}\n\n{}", msg, dump::USAGE);
+            process::exit(2);
+        }
+    };
+
+    if options.show_help {
+        println!("{}", dump::USAGE);
+        return;
+    }
+
+    if let Err(msg) = dump::run(&options) {
+        eprintln!("sbyte dump: {}", msg);
+        process::exit(1);
+    }
+}
 
 fn main() {
-    match result_catcher() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.first().map(|arg| arg.as_str()) == Some("dump") {
+        run_dump(&args[1 ..]);
+        return;
+    }
+
+    let options = match parse_args(&args) {
+        Ok(options) => options,
+        Err(msg) => {
+            eprintln!("sbyte: {}\n\n{}", msg, USAGE);
+            process::exit(2);
+        }
+    };
+
+    if options.show_help {
+        println!("{}", USAGE);
+        return;
+    }
+    if options.show_version {
+        println!("sbyte {}", env!("CARGO_PKG_VERSION"));
+        return;
+    }
+
+    if let Some(script_path) = options.batch_script.clone() {
+        if let Err(msg) = run_batch(options, &script_path) {
+            eprintln!("sbyte: {}", msg);
+            process::exit(1);
+        }
+        return;
+    }
+
+    let rc_path = match get_rc_path(&options) {
+        Ok(rc_path) => rc_path,
+        Err(msg) => {
+            eprintln!("sbyte: {}", msg);
+            process::exit(2);
+        }
+    };
+
+    match result_catcher(options, rc_path) {
         Ok(_) => {}
         Err(error) => {
             eprintln!("Fatal error {}", error);
@@ -1,10 +1,27 @@
 #![allow(dead_code)]
-use std::collections::HashMap;
-use std::cmp::min;
+use std::collections::{HashMap, VecDeque};
+use std::fs;
+use std::cmp::{min, max};
+use std::io::{self, Read, Write};
+use std::process::{ChildStdin, Command, ExitStatus, Stdio};
+use std::thread;
 type R = Result<(), SbyteError>;
 type Callback = fn(&mut Shell, &[&str]) -> R;
-
-use super::editor::{Editor, SbyteError, string_to_bytes};
+// The range at the start of a line, if any, and the rest of the line
+type RangeSplit<'a> = (Option<(usize, usize)>, &'a str);
+// A command's exit status, stdout and stderr, and how writing its input went
+type Piped = (ExitStatus, Vec<u8>, Vec<u8>, io::Result<()>);
+
+use super::editor::{Editor, SbyteError, string_to_bytes, string_to_integer, nth_match_after, nth_match_before};
+use super::editor::template::TemplateError;
+use super::editor::save::Backup;
+use super::editor::records::{FileFormat, RecordError};
+use super::editor::substitute::{Replacement, Substitution};
+use super::editor::search_job::SearchJob;
+use super::editor::content::{MAX_MATCH_LENGTH, SEARCH_WINDOW};
+use super::editor::typed::TypedValueError;
+use super::editor::export::{self, ExportFormat, ImportError};
+use super::clipboard::{self, Encoding};
 pub struct Shell {
     hook_map: HashMap<String, Callback>,
     alias_map: HashMap<String, String>,
@@ -16,11 +33,107 @@ pub struct Shell {
     feedback: Option<String>,
     error: Option<String>,
     register: Option<usize>,
+    // Named register picked for the next yank, paste or delete
+    selected_register: Option<char>,
     record_map: HashMap<String, Vec<Vec<String>>>,
     record_key: Option<String>,
     in_playback: bool,
     cursor: usize,
-    option_flags: HashMap<String, bool>
+    option_flags: HashMap<String, bool>,
+    // Where `SAVE -` writes to: stdout, if it isn't the terminal being drawn on
+    output: Option<Box<dyn Write>>,
+    // Commands that put text on the system clipboard and get it back, like xclip or wl-copy
+    clipboard_copy_command: Option<String>,
+    clipboard_paste_command: Option<String>,
+    replace_session: Option<ReplaceSession>,
+    // Bytes the command being run is to search within, if its line gave a range
+    command_range: Option<(usize, usize)>,
+    // Whether the command being run was given with a ! after its name, to go ahead despite unsaved changes
+    command_forced: bool,
+    incremental_search: Option<IncrementalSearch>,
+    pending_search: Option<PendingSearch>
+}
+
+// A REPLACE_CONFIRM waiting on an answer for each match
+struct ReplaceSession {
+    // Matches not yet answered, at the offsets they were found at
+    remaining: VecDeque<Replacement>,
+    // How far the replacements made so far have moved the matches after them
+    shift: isize,
+    replaced: usize
+}
+
+// A search running on a worker thread, and what's to be done with what it finds
+enum PendingSearch {
+    // Jump to the nth match after or before `from`
+    Jump {
+        job: SearchJob<()>,
+        pattern: String,
+        from: usize,
+        n: usize,
+        is_next: bool
+    },
+    // Replace the matches counted from `limit.0` to `limit.1`, starting at 1, or ask about each first
+    Replace {
+        job: SearchJob<Vec<u8>>,
+        pattern: String,
+        limit: (usize, usize),
+        confirm: bool
+    }
+}
+
+impl PendingSearch {
+    fn poll(&mut self) -> bool {
+        match self {
+            PendingSearch::Jump { job, .. } => job.poll(),
+            PendingSearch::Replace { job, .. } => job.poll()
+        }
+    }
+
+    fn wait(&mut self) {
+        match self {
+            PendingSearch::Jump { job, .. } => job.wait(),
+            PendingSearch::Replace { job, .. } => job.wait()
+        }
+    }
+
+    fn is_finished(&self) -> bool {
+        match self {
+            PendingSearch::Jump { job, .. } => job.is_finished(),
+            PendingSearch::Replace { job, .. } => job.is_finished()
+        }
+    }
+
+    // Whether what's been found is all that's needed, so the rest of the range can be left unsearched
+    fn has_enough(&self) -> bool {
+        match self {
+            PendingSearch::Jump { job, from, n, is_next, .. } => {
+                let hits = job.get_hits();
+                *is_next && hits.len() - hits.partition_point(|(start, _, _)| start <= from) > *n
+            }
+            PendingSearch::Replace { job, limit, .. } => {
+                job.get_hits().len() >= limit.1
+            }
+        }
+    }
+
+    fn get_progress(&self) -> String {
+        let (pattern, percent, found) = match self {
+            PendingSearch::Jump { job, pattern, .. } => (pattern, job.get_percent(), job.get_hits().len()),
+            PendingSearch::Replace { job, pattern, .. } => (pattern, job.get_percent(), job.get_hits().len())
+        };
+
+        format!("searching for '{}': {}%, {} found", pattern, percent, found)
+    }
+}
+
+// A find being typed at the command line, previewed as it changes
+struct IncrementalSearch {
+    // Offset and length of the cursor before the preview moved it
+    origin: (usize, usize),
+    status: Option<String>,
+    // Finding the matches on a worker thread, so typing isn't held up by a big buffer
+    job: Option<SearchJob<()>>
 }
 
 impl Shell {
@@ -35,12 +148,21 @@ impl Shell {
             feedback: None,
             error: None,
             register: None,
+            selected_register: None,
             record_map: HashMap::new(),
             record_key: None,
             in_playback: false,
             history_cursor: None,
             cursor: 0,
-            option_flags: HashMap::new()
+            option_flags: HashMap::new(),
+            output: None,
+            clipboard_copy_command: None,
+            clipboard_paste_command: None,
+            replace_session: None,
+            command_range: None,
+            command_forced: false,
+            incremental_search: None,
+            pending_search: None
         };
 
         output.map_command("TOGGLE_FORMATTER", hook_toggle_formatter);
@@ -72,6 +194,12 @@ impl Shell {
         output.map_command("PASTE", hook_paste);
         output.map_command("UNDO", hook_undo);
         output.map_command("REDO", hook_redo);
+        output.map_command("UNDO_OLDER", hook_undo_older);
+        output.map_command("UNDO_NEWER", hook_undo_newer);
+        output.map_command("UNDO_EARLIER", hook_undo_earlier);
+        output.map_command("UNDO_LATER", hook_undo_later);
+        output.map_command("UNDO_GOTO", hook_undo_goto);
+        output.map_command("UNDO_LIST", hook_undo_list);
         output.map_command("CLEAR_REGISTER", hook_clear_register);
         output.map_command("APPEND_TO_REGISTER", hook_push_to_register);
         output.map_command("INCREMENT", hook_increment);
@@ -99,6 +227,8 @@ impl Shell {
 
         output.map_command("RUN_CUSTOM_COMMAND", hook_query);
         output.map_command("REPLACE_ALL", hook_replace_pattern);
+        output.map_command("REPLACE_CONFIRM", hook_replace_confirm);
+        output.map_command("REPLACE_ANSWER", hook_replace_answer);
 
         output.map_command("MASK_NOT", hook_bitwise_not);
         output.map_command("MASK_AND", hook_bitwise_and_mask);
@@ -119,6 +249,42 @@ impl Shell {
 
         output.map_command("SETFLAG", hook_set_option_flag);
 
+        output.map_command("BUFFER_OPEN", hook_buffer_open);
+        output.map_command("BUFFER_NEXT", hook_buffer_next);
+        output.map_command("BUFFER_PREV", hook_buffer_prev);
+        output.map_command("BUFFER_LIST", hook_buffer_list);
+        output.map_command("BUFFER_CLOSE", hook_buffer_close);
+
+        output.map_command("DIFF", hook_diff);
+        output.map_command("DIFF_OFF", hook_diff_off);
+        output.map_command("DIFF_NEXT", hook_diff_next);
+        output.map_command("DIFF_PREV", hook_diff_prev);
+        output.map_command("DIFF_GET", hook_diff_get);
+        output.map_command("DIFF_PUT", hook_diff_put);
+
+        output.map_command("TEMPLATE", hook_template);
+        output.map_command("TEMPLATE_OFF", hook_template_off);
+
+        output.map_command("DATA_INSPECTOR_TOGGLE", hook_data_inspector_toggle);
+
+        output.map_command("LOCK", hook_lock);
+        output.map_command("UNLOCK", hook_unlock);
+
+        output.map_command("FILTER", hook_filter);
+
+        output.map_command("FILE_FORMAT", hook_file_format);
+        output.map_command("BASE_ADDRESS", hook_base_address);
+        output.map_command("PAD_BYTE", hook_pad_byte);
+
+        output.map_command("EXPORT", hook_export);
+        output.map_command("IMPORT", hook_import);
+
+        output.map_command("SELECT_REGISTER", hook_select_register);
+        output.map_command("REGISTERS", hook_registers);
+
+        output.map_command("CLIPBOARD_COPY_COMMAND", hook_clipboard_copy_command);
+        output.map_command("CLIPBOARD_PASTE_COMMAND", hook_clipboard_paste_command);
+
         output.map_alias("set", "SETFLAG");
 
         output.map_alias("rec", "RECORD_TOGGLE").ok();
@@ -139,6 +305,47 @@ impl Shell {
         output.map_alias("xor", "MASK_XOR").ok();
         output.map_alias("not", "BITWISE_NOT").ok();
         output.map_alias("rep", "REPLACE_ALL").ok();
+        output.map_alias("frc", "REPLACE_CONFIRM").ok();
+        output.map_alias("repc", "REPLACE_CONFIRM").ok();
+
+        output.map_alias("e", "BUFFER_OPEN").ok();
+        output.map_alias("bn", "BUFFER_NEXT").ok();
+        output.map_alias("bp", "BUFFER_PREV").ok();
+        output.map_alias("ls", "BUFFER_LIST").ok();
+        output.map_alias("bd", "BUFFER_CLOSE").ok();
+
+        output.map_alias("diff", "DIFF").ok();
+        output.map_alias("diffoff", "DIFF_OFF").ok();
+        output.map_alias("diffget", "DIFF_GET").ok();
+        output.map_alias("diffput", "DIFF_PUT").ok();
+
+        output.map_alias("template", "TEMPLATE").ok();
+        output.map_alias("notemplate", "TEMPLATE_OFF").ok();
+
+        output.map_alias("inspect", "DATA_INSPECTOR_TOGGLE").ok();
+
+        output.map_alias("lock", "LOCK").ok();
+        output.map_alias("unlock", "UNLOCK").ok();
+
+        output.map_alias("filter", "FILTER").ok();
+
+        output.map_alias("fileformat", "FILE_FORMAT").ok();
+        output.map_alias("base", "BASE_ADDRESS").ok();
+        output.map_alias("pad", "PAD_BYTE").ok();
+
+        output.map_alias("export", "EXPORT").ok();
+        output.map_alias("import", "IMPORT").ok();
+
+        output.map_alias("registers", "REGISTERS").ok();
+        output.map_alias("reg", "REGISTERS").ok();
+
+        output.map_alias("copycmd", "CLIPBOARD_COPY_COMMAND").ok();
+        output.map_alias("pastecmd", "CLIPBOARD_PASTE_COMMAND").ok();
+
+        output.map_alias("earlier", "UNDO_EARLIER").ok();
+        output.map_alias("later", "UNDO_LATER").ok();
+        output.map_alias("undo", "UNDO_GOTO").ok();
+        output.map_alias("undolist", "UNDO_LIST").ok();
 
 
        // output.map_command("", );
@@ -167,6 +374,8 @@ impl Shell {
     pub fn buffer_clear(&mut self) {
         self.query_buffer = None;
         self.cursor = 0;
+        // Leaving the line undoes the preview. A find run from it jumps to the same match anyway.
+        self.end_incremental_search();
     }
 
     pub fn buffer_get(&self) -> Option<String> {
@@ -322,21 +531,356 @@ impl Shell {
         match self.buffer_fetch() {
             Some(buffer_string) => {
                 self.history_cursor = None;
-                let mut words = parse_words(&buffer_string);
-                if words.len() > 0 {
-                    let cmd = words.remove(0);
-                    let mut args = vec![];
-                    for word in words.iter() {
-                        args.push(word.as_str());
+                self.run_line(&buffer_string)
+            }
+            None => {
+                Err(SbyteError::NoCommandGiven)
+            }
+        }
+    }
+
+    /// Run a line as it's typed at the command line or written in a script.
+    /// It can start with a range of bytes for the command to search within: two offsets with a comma
+    /// between them, the second included, `%` for the whole buffer or `*` for the selection.
+    /// Hex offsets run until the first character that isn't a hex digit, so `0x10,0xFF find` needs its space.
+    /// `s/PATTERN/REPLACEMENT/` is REPLACE_ALL, or with a `c` after it, REPLACE_CONFIRM. A limit can follow.
+    pub fn run_line(&mut self, line: &str) -> R {
+        let (range, rest) = match self.split_range(line) {
+            Ok(split) => split,
+            Err(msg) => {
+                self.log_error(&msg);
+                return Ok(());
+            }
+        };
+
+        let (cmd, words) = match parse_substitute(rest) {
+            Some(Ok((cmd, args))) => (cmd.to_string(), args),
+            Some(Err(msg)) => {
+                self.log_error(&msg);
+                return Ok(());
+            }
+            None => {
+                let mut words = parse_words(rest);
+                if words.is_empty() {
+                    return Err(SbyteError::InvalidCommand(line.to_string()));
+                }
+                (words.remove(0), words)
+            }
+        };
+
+        let mut args = vec![];
+        for word in words.iter() {
+            args.push(word.as_str());
+        }
+
+        self.command_range = range;
+        let result = self.try_command(&cmd, args.as_slice());
+        self.command_range = None;
+
+        result
+    }
+
+    // The range at the start of a line, as the start and end of the bytes in it, and the rest of the line
+    fn split_range<'a>(&self, line: &'a str) -> Result<RangeSplit<'a>, String> {
+        let line = line.trim_start();
+        if let Some(rest) = line.strip_prefix('%') {
+            return Ok((Some((0, self.editor.len())), rest.trim_start()));
+        }
+
+        if let Some(rest) = line.strip_prefix('*') {
+            let offset = self.editor.get_cursor_offset();
+            let length = self.editor.get_cursor_length();
+            return Ok((Some((offset, offset + length)), rest.trim_start()));
+        }
+
+        match take_offset(line) {
+            Some((start, rest)) if rest.starts_with(',') => {
+                match take_offset(&rest[1 ..]) {
+                    Some((last, rest)) if last >= start => {
+                        // Ranges are clamped to the content later, so one ending at the largest offset can stop short of it
+                        Ok((Some((start, last.saturating_add(1))), rest.trim_start()))
                     }
-                    self.try_command(&cmd, args.as_slice())
-                } else {
-                   Err(SbyteError::InvalidCommand(buffer_string))
+                    Some(_) => {
+                        Err(format!("range goes backwards: {}", line))
+                    }
+                    None => {
+                        Err(format!("range needs an end: {}", line))
+                    }
+                }
+            }
+            _ => Ok((None, line))
+        }
+    }
+
+    /// The bytes searches are kept to: the range given with the command, if there was one, otherwise the whole buffer
+    pub fn get_search_range(&self) -> (usize, usize) {
+        self.clamp_range(self.command_range)
+    }
+
+    fn clamp_range(&self, range: Option<(usize, usize)>) -> (usize, usize) {
+        let length = self.editor.len();
+        match range {
+            Some((start, end)) => (min(start, length), min(end, length)),
+            None => (0, length)
+        }
+    }
+
+    /// Preview the find on the command line, if it holds one: highlight every match and move the cursor
+    /// to the one the find would jump to. Once the line holds something else, the cursor goes back.
+    pub fn update_incremental_search(&mut self) {
+        // Start over from where the cursor was, so `*` still means the selection made before the search
+        if let Some(search) = &self.incremental_search {
+            let (offset, length) = search.origin;
+            self.editor.set_cursor_length(length as isize);
+            self.editor.set_cursor_offset(offset).ok();
+        }
+
+        let line = self.query_buffer.clone().unwrap_or_default();
+        let (range, rest) = match self.split_range(&line) {
+            Ok(split) => split,
+            Err(_) => (None, line.as_str())
+        };
+
+        let words = parse_words(rest);
+        let is_find = match words.first() {
+            Some(key) => self.is_command(key, "JUMP_TO_PATTERN"),
+            None => false
+        };
+        if !is_find {
+            self.end_incremental_search();
+            return;
+        }
+
+        let origin = (self.editor.get_cursor_offset(), self.editor.get_cursor_length());
+        let search = self.incremental_search.get_or_insert(IncrementalSearch { origin, status: None, job: None });
+
+        // Whatever was being looked for before this change to the line is stopped by dropping it
+        search.job = None;
+        search.status = None;
+        self.editor.clear_search_highlights();
+
+        let pattern = match words.get(1) {
+            Some(pattern) if !pattern.is_empty() => pattern,
+            _ => {
+                return;
+            }
+        };
+
+        let result = self.editor.spawn_find(pattern, self.clamp_range(range));
+        if let Some(search) = self.incremental_search.as_mut() {
+            match result {
+                Ok(job) => {
+                    search.status = Some(format!("searching, {}%", job.get_percent()));
+                    search.job = Some(job);
                 }
+                Err(_) => {
+                    search.status = Some("invalid pattern".to_string());
+                }
+            }
+        }
+
+        self.poll_incremental_search();
+    }
+
+    /// Take in what the find being typed has turned up so far: highlight it, and move the cursor
+    /// to the match the find would jump to once that's known
+    pub fn poll_incremental_search(&mut self) {
+        let search = match self.incremental_search.as_mut() {
+            Some(search) => search,
+            None => {
+                return;
             }
+        };
+        let origin = search.origin;
+        let job = match search.job.as_mut() {
+            Some(job) => job,
             None => {
-                Err(SbyteError::NoCommandGiven)
+                return;
+            }
+        };
+        if !job.poll() {
+            return;
+        }
+
+        let matches: Vec<(usize, usize)> = job.get_hits().iter().map(|(start, end, _)| (*start, *end)).collect();
+
+        // The match the find would jump to: the first after the cursor, or once there's none, the first, wrapping around
+        let index = match matches.iter().position(|(start, _)| *start > origin.0) {
+            Some(index) => Some(index),
+            None if job.is_finished() && !matches.is_empty() => Some(0),
+            None => None
+        };
+
+        search.status = Some(match index {
+            Some(index) if job.is_finished() => format!("match {} of {}", index + 1, matches.len()),
+            Some(index) => format!("match {} of {}+, {}%", index + 1, matches.len(), job.get_percent()),
+            None if job.is_finished() => "no matches".to_string(),
+            None => format!("searching, {}%", job.get_percent())
+        });
+
+        if let Some(index) = index {
+            let (start, end) = matches[index];
+            self.editor.set_cursor_length((end - start) as isize);
+            self.editor.set_cursor_offset(start).ok();
+        }
+        self.editor.set_search_highlights(matches);
+    }
+
+    /// Put the cursor back where it was before the find being typed moved it, and clear its highlights
+    pub fn end_incremental_search(&mut self) {
+        if let Some(search) = self.incremental_search.take() {
+            let (offset, length) = search.origin;
+            self.editor.set_cursor_length(length as isize);
+            self.editor.set_cursor_offset(offset).ok();
+            self.editor.clear_search_highlights();
+        }
+    }
+
+    /// Whether a search is running on a worker thread
+    pub fn is_searching(&self) -> bool {
+        self.pending_search.is_some()
+    }
+
+    /// Take in what the running search has found, and once it's done, or has found all it needs, act on it.
+    /// Returns whether it finished.
+    pub fn poll_search(&mut self) -> bool {
+        let search = match self.pending_search.as_mut() {
+            Some(search) => search,
+            None => {
+                return false;
+            }
+        };
+
+        let changed = search.poll();
+        if !search.is_finished() && !search.has_enough() {
+            if changed {
+                let progress = search.get_progress();
+                self.log_feedback(&progress);
             }
+            return false;
+        }
+
+        // Dropping the job stops the worker, if it's still going
+        let search = self.pending_search.take().unwrap();
+        self.complete_search(search);
+
+        true
+    }
+
+    /// Block until the running search is done, then act on it
+    pub fn finish_search(&mut self) {
+        if let Some(mut search) = self.pending_search.take() {
+            while !search.is_finished() && !search.has_enough() {
+                search.wait();
+            }
+            self.complete_search(search);
+        }
+    }
+
+    /// Stop the running search, leaving whatever it found. Returns whether there was one.
+    pub fn cancel_search(&mut self) -> bool {
+        match self.pending_search.take() {
+            Some(_) => {
+                self.log_feedback("search cancelled");
+                true
+            }
+            None => {
+                false
+            }
+        }
+    }
+
+    fn start_search(&mut self, search: PendingSearch) {
+        // One search at a time, so one started while another runs, like by `find A B`, waits its turn
+        self.finish_search();
+        self.log_feedback(&search.get_progress());
+        self.pending_search = Some(search);
+    }
+
+    fn complete_search(&mut self, search: PendingSearch) {
+        match search {
+            PendingSearch::Jump { job, pattern, from, n, is_next } => {
+                let matches: Vec<(usize, usize)> = job.into_hits().into_iter().map(|(start, end, _)| (start, end)).collect();
+                let found = if is_next {
+                    nth_match_after(&matches, from, n)
+                } else {
+                    nth_match_before(&matches, from, n)
+                };
+
+                // Big buffers are searched a window at a time, which a long enough match can fall between
+                let note = if self.editor.len() > SEARCH_WINDOW {
+                    format!(" (matches over {} bytes may be missed)", MAX_MATCH_LENGTH)
+                } else {
+                    String::new()
+                };
+
+                match found {
+                    Some((start, end)) => {
+                        self.editor.set_cursor_length((end - start) as isize);
+                        self.editor.set_cursor_offset(start).ok();
+
+                        self.log_feedback(&format!("found '{}' at {:#02x}{}", pattern, start, note));
+                    }
+                    None => {
+                        self.log_feedback(&format!("no match found: {}{}", pattern, note));
+                    }
+                }
+            }
+            PendingSearch::Replace { job, pattern, limit: (first, last), confirm } => {
+                let hits = job.into_hits();
+                if hits.is_empty() {
+                    self.log_error(&format!("Pattern \"{}\" not found", pattern));
+                    return;
+                }
+
+                let replacements: Vec<Replacement> = hits.into_iter().enumerate().filter(|(i, _)| {
+                    (first ..= last).contains(&(i + 1))
+                }).map(|(_, replacement)| replacement).collect();
+
+                if confirm {
+                    // The replacements made are undone together, once the last is answered
+                    self.editor.begin_transaction();
+                    self.replace_session = Some(ReplaceSession {
+                        remaining: replacements.into(),
+                        shift: 0,
+                        replaced: 0
+                    });
+                    if let Err(e) = show_next_replacement(self) {
+                        self.log_error(&format!("{:?}", e));
+                    }
+                } else {
+                    match self.editor.replace_matches(&replacements) {
+                        Ok(indeces) => {
+                            self.log_feedback(&format!("Replaced {} instances", indeces.len()));
+                        }
+                        Err(SbyteError::ReadOnly) => {
+                            self.log_error("file is read-only");
+                        }
+                        Err(SbyteError::LockedRange(start, end)) => {
+                            self.log_error(&format!("bytes {}-{} are locked", start, end - 1));
+                        }
+                        Err(e) => {
+                            self.log_error(&format!("{:?}", e));
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Where the find being typed has got to, like "match 2 of 5"
+    pub fn get_search_status(&self) -> Option<String> {
+        self.incremental_search.as_ref()?.status.clone()
+    }
+
+    // Whether `key` is `command` or an alias of it, as try_command would look it up
+    fn is_command(&self, key: &str, command: &str) -> bool {
+        if key == command {
+            true
+        } else if self.hook_map.contains_key(key) {
+            false
+        } else {
+            self.alias_map.get(key).map(|real_key| real_key.as_str()) == Some(command)
         }
     }
 
@@ -402,23 +946,26 @@ impl Shell {
 
     fn record_playback(&mut self, record_key: &str) -> R {
         let playback_list = self.get_recorded_commands(record_key);
+        let was_in_playback = self.in_playback;
         self.in_playback = true;
+        // The whole recording is undone as one
+        self.editor.begin_transaction();
+        let mut result = Ok(());
         for arglist in playback_list.iter() {
             let cmd = arglist[0].as_str();
             let mut args = vec![];
             for arg in &arglist[1..] {
                 args.push(arg.as_str());
             }
-            match self.try_command(cmd, &args) {
-                Ok(_) => {}
-                Err(e) => {
-                    self.in_playback = false;
-                    Err(e)?;
-                }
+            if let Err(e) = self.try_command(cmd, &args) {
+                result = Err(e);
+                break;
             }
         }
+        self.editor.end_transaction();
+        self.in_playback = was_in_playback;
 
-        Ok(())
+        result
     }
 
     fn record_enable(&mut self, record_key: &str) {
@@ -474,9 +1021,25 @@ impl Shell {
     }
 
     pub fn try_command(&mut self, key: &str, args: &[&str]) -> R {
+        // Commands go in the order they're given, so one after a search sees where it left the cursor
+        self.finish_search();
+
         let mut use_key = key;
         self.record_command(use_key, args);
 
+        // "bd!" is "bd", forced
+        let mut key = key;
+        let mut forced = false;
+        if !self.is_known_key(key) {
+            if let Some(stripped) = key.strip_suffix('!') {
+                if self.is_known_key(stripped) {
+                    key = stripped;
+                    use_key = stripped;
+                    forced = true;
+                }
+            }
+        }
+
         if ! self.hook_map.contains_key(&key.to_string()) {
             match self.alias_map.get(&key.to_string()) {
                 Some(real_key) => {
@@ -489,7 +1052,14 @@ impl Shell {
 
         match self.hook_map.get(use_key) {
             Some(f) => {
-                f(self, args)
+                // Whatever the command changes is undone as one
+                self.editor.begin_transaction();
+                self.command_forced = forced;
+                let result = f(self, args);
+                self.command_forced = false;
+                self.editor.end_transaction();
+
+                result
             }
             None => {
                 let output = use_key.to_string();
@@ -499,6 +1069,18 @@ impl Shell {
         }
     }
 
+    fn is_known_key(&self, key: &str) -> bool {
+        self.hook_map.contains_key(key) || self.alias_map.contains_key(key)
+    }
+
+    // Whether the command being run was forced, either with a ! or a "force" argument, and the rest of its arguments
+    fn take_force<'a, 'b>(&self, args: &'a [&'b str]) -> (bool, &'a [&'b str]) {
+        match args.split_first() {
+            Some((&"force", rest)) => (true, rest),
+            _ => (self.command_forced, args)
+        }
+    }
+
     pub fn get_editor(&self) -> &Editor {
         &self.editor
     }
@@ -525,6 +1107,17 @@ impl Shell {
         self.feedback = Some(msg.to_string())
     }
 
+    pub fn is_confirming_replace(&self) -> bool {
+        self.replace_session.is_some()
+    }
+
+    /// What to ask about the match REPLACE_CONFIRM is waiting on
+    pub fn get_replace_prompt(&self) -> Option<String> {
+        let session = self.replace_session.as_ref()?;
+        let (_, _, bytes) = session.remaining.front()?;
+        Some(format!("replace with {}? (y/n/a/q)", preview_bytes(bytes)))
+    }
+
     pub fn is_recording(&mut self) -> bool {
         match self.record_key {
             Some(_) => true,
@@ -544,6 +1137,26 @@ impl Shell {
         self.option_flags.entry(optionname.to_string())
             .and_modify(|e| { *e = value })
             .or_insert(value);
+
+        // Flags the editor needs to know about itself
+        if optionname == "persistent_undo" {
+            self.editor.set_persistent_undo(value);
+        } else if optionname == "readonly" {
+            self.editor.set_read_only(value);
+        } else if optionname == "backup" || optionname == "backup_numbered" {
+            let backup = if self.get_option_flag("backup_numbered") {
+                Backup::Numbered
+            } else if self.get_option_flag("backup") {
+                Backup::Simple
+            } else {
+                Backup::Off
+            };
+            self.editor.set_backup(backup);
+        }
+    }
+
+    pub fn set_output(&mut self, output: Box<dyn Write>) {
+        self.output = Some(output);
     }
 
     pub fn get_option_flag(&mut self, optionname: &str) -> bool {
@@ -563,6 +1176,7 @@ impl Shell {
 
 fn hook_clear_register(shell: &mut Shell, _args: &[&str]) -> R {
     shell.register_clear();
+    shell.selected_register = None;
     shell.log_feedback(&format!("")); // Forces an empty feedback msg in the console_displayer
     Ok(())
 }
@@ -605,6 +1219,7 @@ fn hook_insert_to_buffer(shell: &mut Shell, args: &[&str]) -> R {
     for arg in args.iter() {
         shell.buffer_insert(arg);
     }
+    shell.update_incremental_search();
     Ok(())
 }
 
@@ -615,6 +1230,7 @@ fn hook_pop_from_buffer(shell: &mut Shell, _args: &[&str]) -> R {
                 Err(SbyteError::BufferEmpty)
             } else {
                 shell.buffer_pop();
+                shell.update_incremental_search();
                 Ok(())
             }
         }
@@ -656,10 +1272,12 @@ fn hook_cmd_cursor_right(shell: &mut Shell, _args: &[&str]) -> R {
 
 fn hook_history_next(shell: &mut Shell, _args: &[&str]) -> R {
     shell.history_set_next();
+    shell.update_incremental_search();
     Ok(())
 }
 fn hook_history_prev(shell: &mut Shell, _args: &[&str]) -> R {
     shell.history_set_prev();
+    shell.update_incremental_search();
     Ok(())
 }
 
@@ -786,63 +1404,294 @@ fn hook_subcursor_right(shell: &mut Shell, _args: &[&str]) -> R {
 }
 
 fn hook_replace_pattern(shell: &mut Shell, args: &[&str]) -> R {
-    if args.len() >= 2 {
-        match string_to_bytes(args[1]) {
-            Ok(replacer) => {
-                match shell.get_editor_mut().replace(args[0], &replacer) {
-                    Ok(indeces) => {
-                        if indeces.len() == 0 {
-                            shell.log_error(&format!("Pattern \"{}\" not found", args[0]));
-                        } else {
-                            shell.log_feedback(&format!("Replaced {} instances", indeces.len()));
-                        }
-                    }
-                    Err(e) => {
-                        shell.log_error(&format!("{:?}", e));
-                        Err(e)?;
+    start_replace(shell, args, false)
+}
+
+// Like REPLACE_ALL, but stopping at each match for REPLACE_ANSWER to say whether to replace it
+fn hook_replace_confirm(shell: &mut Shell, args: &[&str]) -> R {
+    start_replace(shell, args, true)
+}
+
+// y replaces the match waiting on an answer, n skips it, a replaces it and every one after it, and q stops
+fn hook_replace_answer(shell: &mut Shell, args: &[&str]) -> R {
+    let mut session = match shell.replace_session.take() {
+        Some(session) => session,
+        None => {
+            shell.log_error("no replace is waiting on an answer");
+            return Ok(());
+        }
+    };
+
+    let answer = args.first().copied().unwrap_or("q");
+    let result = match answer {
+        "y" | "n" => {
+            let (start, end, bytes) = session.remaining.pop_front().unwrap();
+            if answer == "y" {
+                let start = (start as isize + session.shift) as usize;
+                match shell.get_editor_mut().replace_range(start, end - start, &bytes) {
+                    Ok(_) => {
+                        session.shift += bytes.len() as isize - (end - start) as isize;
+                        session.replaced += 1;
+                        Ok(())
                     }
+                    Err(e) => Err(e)
                 }
+            } else {
+                Ok(())
             }
-            Err(e) => {
-                shell.log_error(&format!("{:?}", e));
+        }
+        "a" => {
+            let shift = session.shift;
+            let remaining: Vec<Replacement> = session.remaining.drain(..).map(|(start, end, bytes)| {
+                ((start as isize + shift) as usize, (end as isize + shift) as usize, bytes)
+            }).collect();
+            match shell.get_editor_mut().replace_matches(&remaining) {
+                Ok(indeces) => {
+                    session.replaced += indeces.len();
+                    Ok(())
+                }
+                Err(e) => Err(e)
             }
         }
+        _ => {
+            session.remaining.clear();
+            Ok(())
+        }
+    };
+
+    if result.is_err() {
+        // Stop at whatever couldn't be replaced, keeping what already was
+        session.remaining.clear();
     }
+    shell.replace_session = Some(session);
+    show_next_replacement(shell)?;
 
-    Ok(())
+    result
 }
 
-fn hook_overwrite_digit(shell: &mut Shell, args: &[&str]) -> R {
-    for arg in args.iter() {
-        for c in arg.chars() {
-            shell.get_editor_mut().replace_digit(c)?;
-            shell.get_editor_mut().subcursor_next_digit();
-            if shell.get_editor_mut().get_subcursor_offset() == 0
-            && shell.get_editor_mut().get_cursor_length() == 1 {
-                shell.get_editor_mut().cursor_next_byte()?;
-            }
+// Select the next match waiting on an answer, or if there are none left, finish the replace
+fn show_next_replacement(shell: &mut Shell) -> R {
+    let next = match &shell.replace_session {
+        Some(session) => {
+            session.remaining.front().map(|(start, end, _)| {
+                ((*start as isize + session.shift) as usize, end - start)
+            })
         }
-    }
-    Ok(())
-}
+        None => {
+            return Ok(());
+        }
+    };
 
-fn hook_jump_to_position(shell: &mut Shell, _args: &[&str]) -> R {
-    let default = shell.get_editor().len();
-    let new_offset = shell.register_fetch(default);
-    if ! shell.get_option_flag("manual_cursor_size") {
-        shell.get_editor_mut().set_cursor_length(1);
+    match next {
+        Some((offset, length)) => {
+            shell.get_editor_mut().make_selection(offset, max(length, 1))?;
+            if let Some(prompt) = shell.get_replace_prompt() {
+                shell.log_feedback(&prompt);
+            }
+        }
+        None => {
+            let session = shell.replace_session.take().unwrap();
+            shell.get_editor_mut().end_transaction();
+            shell.log_feedback(&format!("Replaced {} instances", session.replaced));
+        }
     }
-    shell.get_editor_mut().set_cursor_offset(new_offset)?;
 
     Ok(())
 }
 
-fn hook_jump_big_endian(shell: &mut Shell, _args: &[&str]) -> R {
-    let new_offset = shell.get_editor_mut().get_selected_as_big_endian();
-    if ! shell.get_option_flag("manual_cursor_size") {
-        shell.get_editor_mut().set_cursor_length(1);
-    }
-    shell.get_editor_mut().set_cursor_offset(new_offset)?;
+// Start looking for the matches of the pattern in the arguments, with what replaces each.
+// Those the limit allows are replaced, or asked about, once the search is done. Anything wrong with the arguments is logged.
+fn start_replace(shell: &mut Shell, args: &[&str], confirm: bool) -> R {
+    let range = shell.get_search_range();
+    if args.len() < 2 {
+        shell.log_error("need a pattern and a replacement");
+        return Ok(());
+    }
+
+    let substitution = match Substitution::parse(args[1]) {
+        Ok(substitution) => substitution,
+        Err(SbyteError::InvalidHexidecimal(bad_string)) |
+        Err(SbyteError::InvalidDecimal(bad_string)) |
+        Err(SbyteError::InvalidBinary(bad_string)) |
+        Err(SbyteError::InvalidSubstitution(bad_string)) => {
+            shell.log_error(&format!("invalid replacement: {}", bad_string));
+            return Ok(());
+        }
+        Err(e) => {
+            shell.log_error(&format!("{:?}", e));
+            return Ok(());
+        }
+    };
+
+    // Which matches to replace, counting from 1
+    let limit = match args.get(2) {
+        Some(limit) => match parse_match_limit(limit) {
+            Some(range) => range,
+            None => {
+                shell.log_error(&format!("invalid limit: {} (expected a count, or a range like 2-5)", limit));
+                return Ok(());
+            }
+        },
+        None => (1, usize::MAX)
+    };
+
+    match shell.get_editor().spawn_find_replacements(args[0], substitution, range) {
+        Ok(job) => {
+            if confirm {
+                shell.get_editor_mut().add_search_history(args[0].to_string());
+            }
+            shell.start_search(PendingSearch::Replace {
+                job,
+                pattern: args[0].to_string(),
+                limit,
+                confirm
+            });
+        }
+        Err(SbyteError::InvalidHexidecimal(bad_string)) |
+        Err(SbyteError::InvalidDecimal(bad_string)) |
+        Err(SbyteError::InvalidBinary(bad_string)) |
+        Err(SbyteError::InvalidRegex(bad_string)) => {
+            shell.log_error(&format!("invalid pattern: {}", &bad_string));
+        }
+        Err(SbyteError::InvalidTypedValue(error)) => {
+            shell.log_error(&typed_value_error_message(&error));
+        }
+        Err(e) => {
+            shell.log_error(&format!("{:?}", e));
+        }
+    }
+
+    Ok(())
+}
+
+// s/PATTERN/REPLACEMENT/FLAGS LIMIT, as the command and arguments it stands for, or None if it isn't one.
+// Any character that isn't a letter, digit or space can stand in for the slashes, and a backslash keeps it
+// from ending the part it's in. The c flag asks before each replacement and g is taken, though it changes nothing.
+fn parse_substitute(text: &str) -> Option<Result<(&'static str, Vec<String>), String>> {
+    let mut chars = text.strip_prefix('s')?.chars();
+    let delimiter = match chars.next() {
+        Some(c) if !c.is_alphanumeric() && !c.is_whitespace() && c != '\\' => c,
+        _ => {
+            return None;
+        }
+    };
+
+    let mut parts = vec![String::new()];
+    while let Some(c) = chars.next() {
+        if parts.len() == 3 {
+            parts[2].push(c);
+        } else if c == delimiter {
+            parts.push(String::new());
+        } else if c == '\\' {
+            let part = parts.last_mut().unwrap();
+            match chars.next() {
+                Some(escaped) if escaped == delimiter => {
+                    part.push(escaped);
+                }
+                // Anything else escaped is for the pattern or the replacement to read
+                Some(escaped) => {
+                    part.push(c);
+                    part.push(escaped);
+                }
+                None => {
+                    part.push(c);
+                }
+            }
+        } else {
+            parts.last_mut().unwrap().push(c);
+        }
+    }
+
+    let pattern = parts[0].clone();
+    if pattern.is_empty() {
+        return Some(Err("need a pattern".to_string()));
+    }
+    let replacement = parts.get(1).cloned().unwrap_or_default();
+    let rest = parts.get(2).cloned().unwrap_or_default();
+
+    let (flags, limit) = match rest.split_once(char::is_whitespace) {
+        Some((flags, limit)) => (flags, limit.trim()),
+        None => (rest.as_str(), "")
+    };
+    let mut command = "REPLACE_ALL";
+    for flag in flags.chars() {
+        match flag {
+            'c' => {
+                command = "REPLACE_CONFIRM";
+            }
+            'g' => { }
+            _ => {
+                return Some(Err(format!("unknown flag: {}", flag)));
+            }
+        }
+    }
+
+    let mut args = vec![pattern, replacement];
+    if !limit.is_empty() {
+        args.push(limit.to_string());
+    }
+
+    Some(Ok((command, args)))
+}
+
+// An offset at the start of `text`, in decimal or in hex after 0x or \x, and what follows it
+fn take_offset(text: &str) -> Option<(usize, &str)> {
+    let (digits, radix) = match text.strip_prefix("0x").or_else(|| text.strip_prefix("\\x")) {
+        Some(digits) => (digits, 16),
+        None => (text, 10)
+    };
+
+    let length = digits.find(|c: char| !c.is_digit(radix)).unwrap_or(digits.len());
+    let offset = usize::from_str_radix(&digits[0 .. length], radix).ok()?;
+
+    Some((offset, &digits[length ..]))
+}
+
+// A count N, meaning matches 1 to N, or a range M-N
+fn parse_match_limit(limit: &str) -> Option<(usize, usize)> {
+    let (first, last) = match limit.split_once('-') {
+        Some((first, last)) => (string_to_integer(first).ok()?, string_to_integer(last).ok()?),
+        None => (1, string_to_integer(limit).ok()?)
+    };
+
+    if first == 0 || last < first {
+        None
+    } else {
+        Some((first, last))
+    }
+}
+
+fn hook_overwrite_digit(shell: &mut Shell, args: &[&str]) -> R {
+    for arg in args.iter() {
+        for c in arg.chars() {
+            shell.get_editor_mut().replace_digit(c)?;
+            shell.get_editor_mut().subcursor_next_digit();
+            if shell.get_editor_mut().get_subcursor_offset() == 0
+            && shell.get_editor_mut().get_cursor_length() == 1 {
+                shell.get_editor_mut().cursor_next_byte()?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn hook_jump_to_position(shell: &mut Shell, _args: &[&str]) -> R {
+    let default = shell.get_editor().len();
+    let new_offset = shell.register_fetch(default);
+    if ! shell.get_option_flag("manual_cursor_size") {
+        shell.get_editor_mut().set_cursor_length(1);
+    }
+    shell.get_editor_mut().set_cursor_offset(new_offset)?;
+
+    Ok(())
+}
+
+fn hook_jump_big_endian(shell: &mut Shell, _args: &[&str]) -> R {
+    let new_offset = shell.get_editor_mut().get_selected_as_big_endian();
+    if ! shell.get_option_flag("manual_cursor_size") {
+        shell.get_editor_mut().set_cursor_length(1);
+    }
+    shell.get_editor_mut().set_cursor_offset(new_offset)?;
 
     Ok(())
 }
@@ -947,20 +1796,59 @@ fn hook_bitwise_xor_mask(shell: &mut Shell, args: &[&str]) -> R {
     Ok(())
 }
 
-fn hook_yank(shell: &mut Shell, _args: &[&str]) -> R {
+fn hook_yank(shell: &mut Shell, args: &[&str]) -> R {
+    let register = take_register(shell, args)?;
     shell.get_editor_mut().copy_selection();
+    let yanked_len = shell.get_editor().get_clipboard().len();
+    match register {
+        Some(name) => {
+            // The clipboard gets the whole register, as it is after appending
+            let editor = shell.get_editor_mut();
+            let selected = editor.get_selected();
+            editor.copy_to_register(name, &selected);
+            let register_bytes = editor.get_register(name).unwrap_or_default();
+            editor.copy_to_clipboard(register_bytes);
+        }
+        None => {
+            copy_to_system_clipboard(shell);
+        }
+    }
     if ! shell.get_option_flag("manual_cursor_size") {
         shell.get_editor_mut().set_cursor_length(1);
     }
 
-    let clipboard_len = shell.get_editor().get_clipboard().len();
-    shell.log_feedback(&format!("Yanked {} bytes", clipboard_len));
+    shell.log_feedback(&format!("Yanked {} bytes", yanked_len));
     Ok(())
 }
 
 
-fn hook_paste(shell: &mut Shell, _args: &[&str]) -> R {
-    let to_paste = shell.get_editor_mut().get_clipboard();
+fn hook_paste(shell: &mut Shell, args: &[&str]) -> R {
+    let register = take_register(shell, args)?;
+    let to_paste = match (register, shell.clipboard_paste_command.clone()) {
+        (Some(name), _) => {
+            match shell.get_editor().get_register(name) {
+                Some(bytes) => bytes,
+                None => {
+                    shell.log_error(&format!("register \"{} is empty", name));
+                    return Ok(());
+                }
+            }
+        }
+        (None, Some(command)) => {
+            match paste_from_system_clipboard(shell, &command) {
+                Some(bytes) => {
+                    shell.get_editor_mut().copy_to_clipboard(bytes.clone());
+                    bytes
+                }
+                None => {
+                    return Ok(());
+                }
+            }
+        }
+        (None, None) => {
+            shell.get_editor_mut().get_clipboard()
+        }
+    };
 
     for _ in 0 .. shell.register_fetch(1) {
         shell.get_editor_mut().insert_bytes_at_cursor(&to_paste)?;
@@ -972,12 +1860,13 @@ fn hook_paste(shell: &mut Shell, _args: &[&str]) -> R {
     Ok(())
 }
 
-fn hook_delete(shell: &mut Shell, _args: &[&str]) -> R {
+fn hook_delete(shell: &mut Shell, args: &[&str]) -> R {
+    let register = take_register(shell, args)?;
     let mut removed_bytes = Vec::new();
     for _ in 0 .. shell.register_fetch(1) {
-        removed_bytes.extend(shell.get_editor_mut().remove_bytes_at_cursor().iter().copied());
+        removed_bytes.extend(shell.get_editor_mut().remove_bytes_at_cursor()?.iter().copied());
     }
-    shell.get_editor_mut().copy_to_clipboard(removed_bytes);
+    keep_deleted_bytes(shell, register, removed_bytes);
     if ! shell.get_option_flag("manual_cursor_size") {
         shell.get_editor_mut().set_cursor_length(1);
     }
@@ -994,6 +1883,7 @@ fn hook_backspace(shell: &mut Shell, args: &[&str]) -> R {
     if original_cursor_size > 1 {
         hook_delete(shell, args)?;
     } else {
+        let register = take_register(shell, args)?;
         let mut offset = shell.get_editor_mut().get_cursor_offset();
 
         // Move the cursor left as far as it can go
@@ -1013,9 +1903,9 @@ fn hook_backspace(shell: &mut Shell, args: &[&str]) -> R {
 
         // Save all removed bytes
         let mut removed_bytes = Vec::new();
-        removed_bytes.extend(shell.get_editor_mut().remove_bytes_at_cursor().iter().copied());
+        removed_bytes.extend(shell.get_editor_mut().remove_bytes_at_cursor()?.iter().copied());
 
-        shell.get_editor_mut().copy_to_clipboard(removed_bytes);
+        keep_deleted_bytes(shell, register, removed_bytes);
 
         // set the cursor length back to 1
         shell.get_editor_mut().set_cursor_length(1);
@@ -1067,6 +1957,166 @@ fn hook_redo(shell: &mut Shell, _args: &[&str]) -> R {
     Ok(())
 }
 
+fn hook_undo_older(shell: &mut Shell, _args: &[&str]) -> R {
+    let count = shell.register_fetch(1);
+    undo_chronological(shell, false, count)
+}
+
+fn hook_undo_newer(shell: &mut Shell, _args: &[&str]) -> R {
+    let count = shell.register_fetch(1);
+    undo_chronological(shell, true, count)
+}
+
+fn hook_undo_earlier(shell: &mut Shell, args: &[&str]) -> R {
+    undo_by_argument(shell, args, false)
+}
+
+fn hook_undo_later(shell: &mut Shell, args: &[&str]) -> R {
+    undo_by_argument(shell, args, true)
+}
+
+// Step through the undo tree in the order the changes were made
+fn undo_chronological(shell: &mut Shell, is_newer: bool, count: usize) -> R {
+    for i in 0 .. count {
+        match shell.get_editor_mut().undo_chronological(is_newer) {
+            Ok(_) => (),
+            Err(SbyteError::EmptyStack) => {
+                if i == 0 {
+                    if is_newer {
+                        shell.log_error("Already at newest change");
+                    } else {
+                        shell.log_error("Already at oldest change");
+                    }
+                    return Ok(());
+                }
+                break;
+            }
+            Err(e) => {
+                Err(e)?;
+            }
+        }
+    }
+    log_undo_position(shell);
+
+    Ok(())
+}
+
+// A plain number is a number of changes, otherwise a time like 10s, 5m, 2h or 1d
+fn undo_by_argument(shell: &mut Shell, args: &[&str], is_later: bool) -> R {
+    let arg = match args.first() {
+        Some(arg) => *arg,
+        None => "1"
+    };
+
+    if let Ok(count) = arg.parse::<usize>() {
+        return undo_chronological(shell, is_later, count);
+    }
+
+    let duration = match parse_duration(arg) {
+        Some(duration) => duration,
+        None => {
+            shell.log_error(&format!("Invalid time: {}", arg));
+            return Ok(());
+        }
+    };
+
+    match shell.get_editor_mut().undo_by_time(duration, is_later) {
+        Ok(_) => {
+            log_undo_position(shell);
+        }
+        Err(SbyteError::EmptyStack) => {
+            shell.log_error("Already at that change");
+        }
+        Err(e) => {
+            Err(e)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn hook_undo_goto(shell: &mut Shell, args: &[&str]) -> R {
+    let target = match args.first() {
+        Some(arg) => {
+            match arg.parse::<usize>() {
+                Ok(n) => n,
+                Err(_) => {
+                    shell.log_error(&format!("Invalid change number: {}", arg));
+                    return Ok(());
+                }
+            }
+        }
+        None => {
+            shell.log_error("Need a change number");
+            return Ok(());
+        }
+    };
+
+    match shell.get_editor_mut().undo_goto(target) {
+        Ok(_) => {
+            log_undo_position(shell);
+        }
+        Err(SbyteError::EmptyStack) => {
+            shell.log_error(&format!("No change number {}", target));
+        }
+        Err(e) => {
+            Err(e)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn hook_undo_list(shell: &mut Shell, _args: &[&str]) -> R {
+    let branches = shell.get_editor().get_undo_branches();
+    if branches.is_empty() {
+        shell.log_feedback("Nothing to undo");
+        return Ok(());
+    }
+
+    let mut entries = vec!["number changes when".to_string()];
+    for (number, changes, age) in branches.iter() {
+        entries.push(format!("{} {} {} ago", number, changes, format_age(*age)));
+    }
+    shell.log_feedback(&entries.join("  "));
+
+    Ok(())
+}
+
+fn log_undo_position(shell: &mut Shell) {
+    let position = shell.get_editor().get_undo_position();
+    let (depth, redo_depth) = shell.get_editor().get_undo_depth();
+    shell.log_feedback(&format!("At change {} ({} to undo, {} to redo)", position, depth, redo_depth));
+}
+
+fn parse_duration(arg: &str) -> Option<std::time::Duration> {
+    let unit = match arg.chars().last()? {
+        's' => 1,
+        'm' => 60,
+        'h' => 60 * 60,
+        'd' => 60 * 60 * 24,
+        _ => {
+            return None;
+        }
+    };
+
+    let amount = arg[0 .. arg.len() - 1].parse::<u64>().ok()?;
+    Some(std::time::Duration::from_secs(amount * unit))
+}
+
+fn format_age(age: std::time::Duration) -> String {
+    let seconds = age.as_secs();
+    if seconds < 60 {
+        format!("{}s", seconds)
+    } else if seconds < 60 * 60 {
+        format!("{}m", seconds / 60)
+    } else if seconds < 60 * 60 * 24 {
+        format!("{}h", seconds / (60 * 60))
+    } else {
+        format!("{}d", seconds / (60 * 60 * 24))
+    }
+}
+
 fn hook_insert_string(shell: &mut Shell, args: &[&str]) -> R {
     for _ in 0 .. shell.register_fetch(1) {
         for arg in args.iter() {
@@ -1169,17 +2219,51 @@ fn hook_decrement(shell: &mut Shell, _args: &[&str]) -> R {
 
 
 fn hook_save(shell: &mut Shell, args: &[&str]) -> R {
+    save(shell, args)?;
+    Ok(())
+}
+
+// Save as `args` ask, logging how it went. Gives whether everything was written.
+fn save(shell: &mut Shell, args: &[&str]) -> Result<bool, SbyteError> {
+    let (forced, args) = shell.take_force(args);
+
+    // "w !cmd" pipes to a command and "w -" writes to stdout rather than saving
+    if let Some(command) = args.first().and_then(|arg| arg.strip_prefix('!')) {
+        let mut words = vec![command];
+        words.extend(args[1 ..].iter());
+        return save_to_command(shell, words.join(" ").trim());
+    } else if args == ["-"] {
+        return save_to_output(shell);
+    }
+
+    let mut saved = true;
     if !args.is_empty() {
         for arg in args.iter() {
+            // Naming the open file is no different from saving it
+            if shell.get_editor().is_active_file(arg) && refuse_changed_on_disk(shell, forced) {
+                saved = false;
+                continue;
+            }
+
             match shell.get_editor_mut().save_as(arg) {
                 Ok(_) => {
                     shell.log_feedback(&format!("saved '{}'", arg));
                 }
+                Err(SbyteError::InvalidRecords(error)) => {
+                    shell.log_error(&format!("failed to save: {}", record_error_message(&error)));
+                    saved = false;
+                }
+                Err(SbyteError::OriginalChanged) => {
+                    shell.log_error("failed to save: the file changed on disk while it was open");
+                    saved = false;
+                }
                 Err(e) => {
                     Err(e)?;
                 }
             }
         }
+    } else if refuse_changed_on_disk(shell, forced) {
+        saved = false;
     } else {
         match shell.get_editor_mut().save() {
             Ok(_) => {
@@ -1187,6 +2271,15 @@ fn hook_save(shell: &mut Shell, args: &[&str]) -> R {
             }
             Err(SbyteError::PathNotSet) => {
                 shell.log_error("failed to save: no path set");
+                saved = false;
+            }
+            Err(SbyteError::InvalidRecords(error)) => {
+                shell.log_error(&format!("failed to save: {}", record_error_message(&error)));
+                saved = false;
+            }
+            Err(SbyteError::OriginalChanged) => {
+                shell.log_error("failed to save: the file changed on disk while it was open");
+                saved = false;
             }
             Err(e) => {
                 Err(e)?;
@@ -1194,12 +2287,169 @@ fn hook_save(shell: &mut Shell, args: &[&str]) -> R {
         }
     }
 
+    Ok(saved)
+}
+
+// Whether saving over the active file would clobber changes made to it since it was loaded, saying so if it would
+fn refuse_changed_on_disk(shell: &mut Shell, forced: bool) -> bool {
+    if shell.get_editor().is_changed_on_disk() && !forced {
+        shell.log_error("the file changed on disk since it was loaded (add ! to save over it anyway)");
+        true
+    } else {
+        false
+    }
+}
+
+fn save_to_output(shell: &mut Shell) -> Result<bool, SbyteError> {
+    let mut output = match shell.output.take() {
+        Some(output) => output,
+        None => {
+            shell.log_error("stdout is the terminal");
+            return Ok(false);
+        }
+    };
+
+    let result = shell.get_editor().write_to(&mut output);
+    shell.output = Some(output);
+    result?;
+
+    let length = shell.get_editor().len();
+    shell.log_feedback(&format!("wrote {} bytes to stdout", length));
+
+    Ok(true)
+}
+
+fn save_to_command(shell: &mut Shell, command: &str) -> Result<bool, SbyteError> {
+    if command.is_empty() {
+        shell.log_error("need a command");
+        return Ok(false);
+    }
+
+    let piped = run_piped(command, |stdin| shell.get_editor().write_to(stdin));
+    let written = match piped {
+        // A command that stops reading early closes the pipe, which isn't a failure of its own
+        Ok((status, _, _, Err(e))) if status.success() && e.kind() != io::ErrorKind::BrokenPipe => {
+            shell.log_error(&format!("couldn't write to {}: {}", command, e));
+            false
+        }
+        Ok((status, stdout, _, _)) if status.success() => {
+            let stdout = String::from_utf8_lossy(&stdout).trim().to_string();
+            if stdout.is_empty() {
+                let length = shell.get_editor().len();
+                shell.log_feedback(&format!("wrote {} bytes to {}", length, command));
+            } else {
+                shell.log_feedback(&stdout);
+            }
+            true
+        }
+        Ok((status, _, stderr, _)) => {
+            let stderr = String::from_utf8_lossy(&stderr).trim().to_string();
+            shell.log_error(&format!("{} failed ({}): {}", command, status, stderr));
+            false
+        }
+        Err(e) => {
+            shell.log_error(&format!("couldn't run {}: {}", command, e));
+            false
+        }
+    };
+
+    Ok(written)
+}
+
+// Run `command` with `write_input` feeding its stdin
+fn run_piped<F>(command: &str, write_input: F) -> io::Result<Piped>
+where F: FnOnce(&mut ChildStdin) -> io::Result<()> {
+    let mut child = shell_command(command).stdin(Stdio::piped()).stdout(Stdio::piped()).stderr(Stdio::piped()).spawn()?;
+
+    // The output is read while the input is written, so neither side can fill its pipe and stall
+    let stdout_pipe = child.stdout.take();
+    let stderr_pipe = child.stderr.take();
+    let (write_result, stdout, stderr) = thread::scope(|scope| {
+        let stdout_reader = scope.spawn(move || read_pipe(stdout_pipe));
+        let stderr_reader = scope.spawn(move || read_pipe(stderr_pipe));
+
+        let write_result = match child.stdin.take() {
+            // Dropped once written, so the command sees the end of its input
+            Some(mut stdin) => write_input(&mut stdin),
+            None => Ok(())
+        };
+
+        (write_result, stdout_reader.join().unwrap_or_default(), stderr_reader.join().unwrap_or_default())
+    });
+    let status = child.wait()?;
+
+    Ok((status, stdout, stderr, write_result))
+}
+
+fn hook_filter(shell: &mut Shell, args: &[&str]) -> R {
+    let command = args.join(" ");
+    let command = command.trim();
+    if command.is_empty() {
+        shell.log_error("need a command");
+        return Ok(());
+    }
+
+    let offset = shell.get_editor().get_cursor_offset();
+    let selected = shell.get_editor_mut().get_selected();
+
+    let piped = run_piped(command, |stdin| stdin.write_all(&selected));
+    // A command may stop reading once it has what it needs, so how the writing went doesn't matter
+    match piped {
+        Ok((status, stdout, stderr, _)) if status.success() => {
+            shell.get_editor_mut().replace_range(offset, selected.len(), &stdout)?;
+            // Nothing to select when the output was empty and there's nothing after it
+            shell.get_editor_mut().make_selection(offset, max(stdout.len(), 1)).ok();
+
+            // Whatever the command had to say about it beats a byte count
+            let stderr = String::from_utf8_lossy(&stderr).trim().to_string();
+            if stderr.is_empty() {
+                shell.log_feedback(&format!("filtered {} bytes into {} through {}", selected.len(), stdout.len(), command));
+            } else {
+                shell.log_feedback(&stderr);
+            }
+        }
+        Ok((status, _, stderr, _)) => {
+            let stderr = String::from_utf8_lossy(&stderr).trim().to_string();
+            shell.log_error(&format!("{} failed ({}): {}", command, status, stderr));
+        }
+        Err(e) => {
+            shell.log_error(&format!("couldn't run {}: {}", command, e));
+        }
+    }
+
     Ok(())
 }
 
+fn read_pipe<P: Read>(pipe: Option<P>) -> Vec<u8> {
+    let mut output = Vec::new();
+    if let Some(mut pipe) = pipe {
+        pipe.read_to_end(&mut output).ok();
+    }
+
+    output
+}
+
+// A command line run by the system's shell
+fn shell_command(command: &str) -> Command {
+    let mut output;
+    if cfg!(windows) {
+        output = Command::new("cmd");
+        output.arg("/C").arg(command);
+    } else {
+        output = Command::new("sh");
+        output.arg("-c").arg(command);
+    }
+
+    output
+}
+
 fn hook_save_quit(shell: &mut Shell, args: &[&str]) -> R {
-    hook_save(shell, args)?;
-    Err(SbyteError::KillSignal)
+    // Stay open to keep the changes when they couldn't be written
+    if save(shell, args)? {
+        Err(SbyteError::KillSignal)
+    } else {
+        Ok(())
+    }
 }
 
 fn hook_toggle_formatter(shell: &mut Shell, _args: &[&str]) -> R {
@@ -1328,16 +2578,294 @@ fn hook_set_option_flag(shell: &mut Shell, args: &[&str]) -> R {
     }
     Ok(())
 }
-////////////////////////////////////////////////////////////////////////////
-////////////////////////////////////////////////////////////////////////////
 
-/// Move to the next (or previous) instance of a given pattern
-fn jump_to_next_or_previous(shell: &mut Shell, argument: Option<&str>, is_next: bool) -> R {
-    let repeat: usize = shell.register_fetch(0);
-    let editor = shell.get_editor_mut();
+fn hook_buffer_open(shell: &mut Shell, args: &[&str]) -> R {
+    if args.is_empty() {
+        shell.log_error("need a file path");
+    } else {
+        for arg in args.iter() {
+            match shell.get_editor_mut().open_buffer(arg) {
+                Ok(_) => {
+                    log_active_buffer(shell);
+                }
+                Err(SbyteError::FileNotFound(path)) => {
+                    shell.log_error(&format!("file not found: {}", path));
+                }
+                Err(SbyteError::InvalidRecords(error)) => {
+                    shell.log_error(&format!("{}: {}", arg, record_error_message(&error)));
+                }
+                Err(e) => {
+                    Err(e)?;
+                }
+            }
+        }
+    }
 
-    let current_offset = editor.get_cursor_offset();
-    let option_pattern: Option<String> = match argument {
+    Ok(())
+}
+
+fn hook_buffer_next(shell: &mut Shell, _args: &[&str]) -> R {
+    for _ in 0 .. shell.register_fetch(1) {
+        shell.get_editor_mut().next_buffer()?;
+    }
+    log_active_buffer(shell);
+
+    Ok(())
+}
+
+fn hook_buffer_prev(shell: &mut Shell, _args: &[&str]) -> R {
+    for _ in 0 .. shell.register_fetch(1) {
+        shell.get_editor_mut().prev_buffer()?;
+    }
+    log_active_buffer(shell);
+
+    Ok(())
+}
+
+fn hook_buffer_list(shell: &mut Shell, _args: &[&str]) -> R {
+    let active_index = shell.get_editor().get_active_buffer_index();
+    let mut entries = vec![];
+    for (i, path) in shell.get_editor().get_buffer_paths().iter().enumerate() {
+        let marker = if i == active_index { "%" } else { " " };
+        entries.push(format!("{}{} \"{}\"", i + 1, marker, buffer_name(path)));
+    }
+
+    shell.log_feedback(&entries.join("  "));
+
+    Ok(())
+}
+
+fn hook_buffer_close(shell: &mut Shell, args: &[&str]) -> R {
+    let (forced, _args) = shell.take_force(args);
+    if shell.get_editor().is_modified() && !forced {
+        shell.log_error("buffer has unsaved changes (add ! to close it anyway)");
+        return Ok(());
+    }
+
+    shell.get_editor_mut().close_buffer();
+    log_active_buffer(shell);
+
+    Ok(())
+}
+
+fn buffer_name(path: &Option<String>) -> String {
+    match path {
+        Some(path) => path.clone(),
+        None => "[No Name]".to_string()
+    }
+}
+
+fn log_active_buffer(shell: &mut Shell) {
+    let editor = shell.get_editor();
+    let msg = format!(
+        "[{}/{}] \"{}\" {} bytes",
+        editor.get_active_buffer_index() + 1,
+        editor.get_buffer_count(),
+        buffer_name(&editor.get_active_file_path().cloned()),
+        editor.len()
+    );
+    shell.log_feedback(&msg);
+}
+
+fn hook_diff(shell: &mut Shell, args: &[&str]) -> R {
+    let editor = shell.get_editor();
+    let index = match args.first() {
+        // Buffers are numbered from 1, as in BUFFER_LIST
+        Some(arg) => {
+            match arg.parse::<usize>() {
+                Ok(n) if n > 0 => {
+                    n - 1
+                }
+                _ => {
+                    shell.log_error(&format!("Invalid buffer: {}", arg));
+                    return Ok(());
+                }
+            }
+        }
+        None => {
+            (editor.get_active_buffer_index() + 1) % editor.get_buffer_count()
+        }
+    };
+
+    match shell.get_editor_mut().set_diff_buffer(index) {
+        Ok(_) => {
+            let path = shell.get_editor().get_buffer_paths()[index].clone();
+            shell.log_feedback(&format!("diff against [{}] \"{}\"", index + 1, buffer_name(&path)));
+        }
+        Err(SbyteError::InvalidBuffer(_)) => {
+            shell.log_error("need another buffer to compare against");
+        }
+        Err(e) => {
+            Err(e)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn hook_diff_off(shell: &mut Shell, _args: &[&str]) -> R {
+    shell.get_editor_mut().unset_diff_buffer();
+
+    Ok(())
+}
+
+fn hook_diff_next(shell: &mut Shell, _args: &[&str]) -> R {
+    jump_to_difference(shell, true)
+}
+
+fn hook_diff_prev(shell: &mut Shell, _args: &[&str]) -> R {
+    jump_to_difference(shell, false)
+}
+
+fn hook_diff_get(shell: &mut Shell, _args: &[&str]) -> R {
+    match shell.get_editor_mut().diff_get() {
+        Ok((start, end)) => {
+            shell.log_feedback(&format!("got {} bytes", end - start));
+        }
+        Err(SbyteError::NoDifference) => {
+            shell.log_error("no difference at cursor");
+        }
+        Err(e) => {
+            Err(e)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn hook_diff_put(shell: &mut Shell, _args: &[&str]) -> R {
+    match shell.get_editor_mut().diff_put() {
+        Ok((start, end)) => {
+            shell.log_feedback(&format!("put {} bytes", end - start));
+        }
+        Err(SbyteError::NoDifference) => {
+            shell.log_error("no difference at cursor");
+        }
+        Err(e) => {
+            Err(e)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn hook_template(shell: &mut Shell, args: &[&str]) -> R {
+    if args.is_empty() {
+        shell.log_error("need a template path");
+        return Ok(());
+    }
+
+    // Lay the template at the given offset, the register or the start of the file
+    let offset = match args.get(1) {
+        Some(arg) => {
+            match string_to_integer(arg) {
+                Ok(offset) => offset,
+                Err(_) => {
+                    shell.log_error(&format!("Invalid offset: {}", arg));
+                    return Ok(());
+                }
+            }
+        }
+        None => {
+            shell.register_fetch(0)
+        }
+    };
+
+    match shell.get_editor_mut().load_template(args[0], offset) {
+        Ok(_) => {
+            let field_count = shell.get_editor().get_template_fields().len();
+            shell.log_feedback(&format!("template \"{}\" at {}: {} fields", args[0], offset, field_count));
+        }
+        Err(SbyteError::FileNotFound(path)) => {
+            shell.log_error(&format!("file not found: {}", path));
+        }
+        Err(SbyteError::InvalidTemplate(error)) => {
+            let msg = match error {
+                TemplateError::InvalidLine(line) => format!("invalid template, line {}", line),
+                TemplateError::UnknownType(line, name) => format!("unknown type \"{}\", line {}", name, line),
+                TemplateError::UnknownCount(line, name) => format!("unknown array length \"{}\", line {}", name, line),
+                TemplateError::UnknownStruct(name) => format!("unknown struct \"{}\"", name),
+                TemplateError::DuplicateStruct(line, name) => format!("struct \"{}\" redefined, line {}", name, line),
+                TemplateError::NoStructs => "template defines no structs".to_string()
+            };
+            shell.log_error(&msg);
+        }
+        Err(e) => {
+            Err(e)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn hook_template_off(shell: &mut Shell, _args: &[&str]) -> R {
+    shell.get_editor_mut().unset_template();
+
+    Ok(())
+}
+
+// Shown by the frontend while the "data_inspector" flag is set
+fn hook_data_inspector_toggle(shell: &mut Shell, _args: &[&str]) -> R {
+    let is_shown = shell.get_option_flag("data_inspector");
+    shell.set_option_flag("data_inspector", !is_shown);
+
+    Ok(())
+}
+
+/// Move the cursor to the next (or previous) run of bytes that differ from the compared buffer
+fn jump_to_difference(shell: &mut Shell, is_next: bool) -> R {
+    if shell.get_editor().get_diff_buffer_index().is_none() {
+        shell.log_error("not in diff mode");
+        return Ok(());
+    }
+
+    let repeat = shell.register_fetch(1);
+    let editor = shell.get_editor_mut();
+    let mut found = None;
+    for _ in 0 .. repeat {
+        let offset = editor.get_cursor_offset();
+        let next = if is_next {
+            editor.find_next_difference(offset)
+        } else {
+            editor.find_prev_difference(offset)
+        };
+
+        match next {
+            Some((start, end)) => {
+                // A difference past the end of this buffer can't hold the cursor
+                let start = min(start, editor.len());
+                editor.set_cursor_offset(start)?;
+                found = Some(end - start);
+            }
+            None => {
+                break;
+            }
+        }
+    }
+
+    match found {
+        Some(length) => {
+            shell.log_feedback(&format!("{} bytes differ", length));
+        }
+        None => {
+            shell.log_error("no more differences");
+        }
+    }
+
+    Ok(())
+}
+
+////////////////////////////////////////////////////////////////////////////
+////////////////////////////////////////////////////////////////////////////
+
+/// Move to the next (or previous) instance of a given pattern
+fn jump_to_next_or_previous(shell: &mut Shell, argument: Option<&str>, is_next: bool) -> R {
+    let repeat: usize = shell.register_fetch(0);
+    let range = shell.get_search_range();
+    let editor = shell.get_editor_mut();
+
+    let current_offset = editor.get_cursor_offset();
+    let option_pattern: Option<String> = match argument {
         Some(pattern) => { // arugment was given. use it.
             Some(pattern.to_string())
         }
@@ -1349,29 +2877,17 @@ fn jump_to_next_or_previous(shell: &mut Shell, argument: Option<&str>, is_next:
     match option_pattern {
         Some(string_rep) => {
             editor.add_search_history(string_rep.clone());
-            // This is the only difference in between jumping forward and backwards, so using a boolean
-            // unless I think of a cleaner way that also doesn't use a large chunk of duplication
-            let jump_result = match is_next {
-                true => {
-                    editor.find_nth_after(&string_rep, current_offset, repeat)
-                }
-                false => {
-                    editor.find_nth_before(&string_rep, current_offset, repeat)
-                }
-            };
-            match jump_result {
-                Ok(result) => {
-                    match result {
-                        Some(new_offset) => {
-                            editor.set_cursor_length((new_offset.1 - new_offset.0) as isize);
-                            editor.set_cursor_offset(new_offset.0)?;
-
-                            shell.log_feedback(&format!("found '{}' at {:#02x}", string_rep, new_offset.0));
-                        }
-                        None => {
-                            shell.log_feedback(&format!("no match found: {}", string_rep));
-                        }
-                    }
+            // The worker finds every match in the range, and which one to jump to is worked out
+            // once it's done, so jumping forward and backwards only differ at the end
+            match editor.spawn_find(&string_rep, range) {
+                Ok(job) => {
+                    shell.start_search(PendingSearch::Jump {
+                        job,
+                        pattern: string_rep,
+                        from: current_offset,
+                        n: repeat,
+                        is_next
+                    });
                 }
                 Err(SbyteError::InvalidHexidecimal(bad_string)) |
                 Err(SbyteError::InvalidDecimal(bad_string)) |
@@ -1379,6 +2895,9 @@ fn jump_to_next_or_previous(shell: &mut Shell, argument: Option<&str>, is_next:
                 Err(SbyteError::InvalidRegex(bad_string)) => {
                     shell.log_error(&format!("invalid pattern: {}", &bad_string));
                 }
+                Err(SbyteError::InvalidTypedValue(error)) => {
+                    shell.log_error(&typed_value_error_message(&error));
+                }
                 Err(e) => {
                     Err(e)?;
                 }
@@ -1392,7 +2911,6 @@ fn jump_to_next_or_previous(shell: &mut Shell, argument: Option<&str>, is_next:
     Ok(())
 }
 
-/// Move cursor to the previous instance of a pattern
 fn jump_to_previous(shell: &mut Shell, argument: Option<&str>) -> R {
     jump_to_next_or_previous(shell, argument, false)
 }
@@ -1483,3 +3001,429 @@ pub fn parse_words(input_string: &str) -> Vec<String> {
     output
 }
 
+fn hook_lock(shell: &mut Shell, args: &[&str]) -> R {
+    if let Some((offset, length)) = range_from_args(shell, args) {
+        shell.get_editor_mut().lock_range(offset, length);
+        log_locked_ranges(shell);
+    }
+
+    Ok(())
+}
+
+fn hook_unlock(shell: &mut Shell, args: &[&str]) -> R {
+    if let Some((offset, length)) = range_from_args(shell, args) {
+        shell.get_editor_mut().unlock_range(offset, length);
+        log_locked_ranges(shell);
+    }
+
+    Ok(())
+}
+
+// Offset and length given as arguments, or the selection if there are none
+fn range_from_args(shell: &mut Shell, args: &[&str]) -> Option<(usize, usize)> {
+    if args.is_empty() {
+        let editor = shell.get_editor();
+        return Some((editor.get_cursor_offset(), editor.get_cursor_length()));
+    }
+
+    let mut numbers = vec![];
+    for arg in args.iter().take(2) {
+        match string_to_integer(arg) {
+            Ok(number) => {
+                numbers.push(number);
+            }
+            Err(_) => {
+                shell.log_error(&format!("Invalid number: {}", arg));
+                return None;
+            }
+        }
+    }
+
+    match numbers.as_slice() {
+        [offset, length] => Some((*offset, *length)),
+        _ => {
+            shell.log_error("need an offset and a length");
+            None
+        }
+    }
+}
+
+fn log_locked_ranges(shell: &mut Shell) {
+    let mut entries = vec![];
+    for (start, end) in shell.get_editor().get_locked_ranges().iter() {
+        entries.push(format!("{}-{}", start, end - 1));
+    }
+
+    if entries.is_empty() {
+        shell.log_feedback("no locked bytes");
+    } else {
+        shell.log_feedback(&format!("locked: {}", entries.join(", ")));
+    }
+}
+
+fn hook_file_format(shell: &mut Shell, args: &[&str]) -> R {
+    if let Some(name) = args.first() {
+        match FileFormat::from_name(name) {
+            Some(format) => {
+                shell.get_editor_mut().set_file_format(format);
+            }
+            None => {
+                shell.log_error(&format!("unknown file format: {} (expected raw, ihex or srec)", name));
+                return Ok(());
+            }
+        }
+    }
+
+    let editor = shell.get_editor();
+    let msg = format!("{}, base address \\x{:X}", editor.get_file_format().name(), editor.get_base_address());
+    shell.log_feedback(&msg);
+
+    Ok(())
+}
+
+fn hook_base_address(shell: &mut Shell, args: &[&str]) -> R {
+    if let Some(arg) = args.first() {
+        match string_to_integer(arg) {
+            Ok(base_address) => {
+                shell.get_editor_mut().set_base_address(base_address);
+            }
+            Err(_) => {
+                shell.log_error(&format!("Invalid number: {}", arg));
+                return Ok(());
+            }
+        }
+    }
+
+    let base_address = shell.get_editor().get_base_address();
+    shell.log_feedback(&format!("base address \\x{:X}", base_address));
+
+    Ok(())
+}
+
+fn hook_pad_byte(shell: &mut Shell, args: &[&str]) -> R {
+    if let Some(arg) = args.first() {
+        match string_to_integer(arg) {
+            Ok(pad_byte) if pad_byte <= 0xFF => {
+                shell.get_editor_mut().set_pad_byte(pad_byte as u8);
+            }
+            _ => {
+                shell.log_error(&format!("Invalid byte: {}", arg));
+                return Ok(());
+            }
+        }
+    }
+
+    let pad_byte = shell.get_editor().get_pad_byte();
+    shell.log_feedback(&format!("gaps between records are shown as \\x{:02X}", pad_byte));
+
+    Ok(())
+}
+
+fn record_error_message(error: &RecordError) -> String {
+    match error {
+        RecordError::InvalidLine(line) => format!("invalid record, line {}", line),
+        RecordError::BadChecksum(line) => format!("bad checksum, line {}", line),
+        RecordError::UnknownRecord(line) => format!("unknown record type, line {}", line),
+        RecordError::AddressTooLarge => "content goes past the 32 bit address limit".to_string()
+    }
+}
+
+fn typed_value_error_message(error: &TypedValueError) -> String {
+    match error {
+        TypedValueError::InvalidValue(value) => format!("invalid value: {}", value),
+        TypedValueError::OutOfRange(value) => format!("value out of range for its type: {}", value)
+    }
+}
+
+fn hook_export(shell: &mut Shell, args: &[&str]) -> R {
+    let format = match args.first() {
+        Some(name) => match ExportFormat::from_name(name) {
+            Some(format) => format,
+            None => {
+                shell.log_error(&format!("unknown export format: {} (expected c, rust, python, base64 or xxd)", name));
+                return Ok(());
+            }
+        },
+        None => {
+            shell.log_error("need a format");
+            return Ok(());
+        }
+    };
+
+    // The selection, or everything if there's nothing more than the cursor selected
+    let editor = shell.get_editor_mut();
+    let (offset, bytes) = if editor.get_cursor_length() > 1 {
+        (editor.get_cursor_offset(), editor.get_selected())
+    } else {
+        (0, editor.get_chunk(0, editor.len()))
+    };
+    let text = export::export(format, &bytes, editor.get_base_address() + offset);
+
+    match args.get(1) {
+        Some(path) => {
+            if let Err(e) = fs::write(path, text) {
+                shell.log_error(&format!("couldn't write {}: {}", path, e));
+                return Ok(());
+            }
+            shell.log_feedback(&format!("exported {} bytes to {}", bytes.len(), path));
+        }
+        None => {
+            // Already text, so it goes on the system clipboard as it is
+            share_with_system_clipboard(shell, text.as_bytes());
+            shell.get_editor_mut().copy_to_clipboard(text.into_bytes());
+            shell.log_feedback(&format!("exported {} bytes to the clipboard", bytes.len()));
+        }
+    }
+
+    Ok(())
+}
+
+fn hook_import(shell: &mut Shell, args: &[&str]) -> R {
+    let format = match args.first() {
+        Some(name) => match ExportFormat::from_name(name) {
+            Some(format) => format,
+            None => {
+                shell.log_error(&format!("unknown import format: {} (expected c, rust, python, base64 or xxd)", name));
+                return Ok(());
+            }
+        },
+        None => {
+            shell.log_error("need a format");
+            return Ok(());
+        }
+    };
+
+    let text = match args.get(1) {
+        Some(path) => match fs::read(path) {
+            Ok(text) => text,
+            Err(e) => {
+                shell.log_error(&format!("couldn't read {}: {}", path, e));
+                return Ok(());
+            }
+        },
+        None => shell.get_editor().get_clipboard()
+    };
+
+    match export::import(format, &String::from_utf8_lossy(&text)) {
+        Ok(bytes) => {
+            shell.get_editor_mut().insert_bytes_at_cursor(&bytes)?;
+            shell.log_feedback(&format!("imported {} bytes", bytes.len()));
+        }
+        Err(ImportError::InvalidToken(token)) => {
+            shell.log_error(&format!("couldn't import: invalid value \"{}\"", token));
+        }
+        Err(ImportError::InvalidLine(line)) => {
+            shell.log_error(&format!("couldn't import: invalid line {}", line));
+        }
+    }
+
+    Ok(())
+}
+
+fn hook_clipboard_copy_command(shell: &mut Shell, args: &[&str]) -> R {
+    shell.clipboard_copy_command = command_from_args(args);
+    match &shell.clipboard_copy_command {
+        Some(command) => {
+            let msg = format!("yanking to {}", command);
+            shell.log_feedback(&msg);
+        }
+        None => {
+            shell.log_feedback("yanking to sbyte's clipboard only");
+        }
+    }
+
+    Ok(())
+}
+
+fn hook_clipboard_paste_command(shell: &mut Shell, args: &[&str]) -> R {
+    shell.clipboard_paste_command = command_from_args(args);
+    match &shell.clipboard_paste_command {
+        Some(command) => {
+            let msg = format!("pasting from {}", command);
+            shell.log_feedback(&msg);
+        }
+        None => {
+            shell.log_feedback("pasting from sbyte's clipboard");
+        }
+    }
+
+    Ok(())
+}
+
+// The arguments as one command line, or none if there aren't any
+fn command_from_args(args: &[&str]) -> Option<String> {
+    let command = args.join(" ").trim().to_string();
+    if command.is_empty() {
+        None
+    } else {
+        Some(command)
+    }
+}
+
+// How bytes are written on the system clipboard, set with the clipboard_hex and clipboard_base64 flags
+fn get_clipboard_encoding(shell: &mut Shell) -> Encoding {
+    if shell.get_option_flag("clipboard_base64") {
+        Encoding::Base64
+    } else if shell.get_option_flag("clipboard_hex") {
+        Encoding::Hex
+    } else {
+        Encoding::Raw
+    }
+}
+
+// Share what was just yanked through the terminal and the copy command, whichever are set up
+fn copy_to_system_clipboard(shell: &mut Shell) {
+    let encoding = get_clipboard_encoding(shell);
+    let text = clipboard::encode(&shell.get_editor().get_clipboard(), encoding);
+    share_with_system_clipboard(shell, &text);
+}
+
+// Put `text` on the system clipboard as it is, through whichever of the terminal and the copy command are set up
+fn share_with_system_clipboard(shell: &mut Shell, text: &[u8]) {
+    let use_osc52 = shell.get_option_flag("clipboard_osc52");
+    let command = shell.clipboard_copy_command.clone();
+
+    if use_osc52 {
+        if let Err(e) = clipboard::copy_with_osc52(&text) {
+            shell.log_error(&format!("couldn't reach the terminal's clipboard: {}", e));
+        }
+    }
+
+    if let Some(command) = command {
+        match run_with_input(&command, text) {
+            Ok(status) if status.success() => (),
+            Ok(status) => {
+                shell.log_error(&format!("{} failed ({})", command, status));
+            }
+            Err(e) => {
+                shell.log_error(&format!("couldn't run {}: {}", command, e));
+            }
+        }
+    }
+}
+
+// Run `command` with `input` on its stdin, waiting only for it to exit.
+// Copy commands like xclip and wl-copy leave a process behind to hold the clipboard, which keeps any
+// stdout or stderr pipe open, so their output goes nowhere rather than being waited on.
+fn run_with_input(command: &str, input: &[u8]) -> io::Result<ExitStatus> {
+    let mut child = shell_command(command).stdin(Stdio::piped()).stdout(Stdio::null()).stderr(Stdio::null()).spawn()?;
+
+    let write_result = match child.stdin.take() {
+        // Dropped once written, so the command sees the end of its input
+        Some(mut stdin) => stdin.write_all(input),
+        None => Ok(())
+    };
+    let status = child.wait()?;
+    write_result?;
+
+    Ok(status)
+}
+
+// The bytes on the system clipboard, or None if they couldn't be got, with the reason logged
+fn paste_from_system_clipboard(shell: &mut Shell, command: &str) -> Option<Vec<u8>> {
+    let encoding = get_clipboard_encoding(shell);
+    match run_piped(command, |_| Ok(())) {
+        Ok((status, stdout, _, _)) if status.success() => {
+            match clipboard::decode(&stdout, encoding) {
+                Ok(bytes) => Some(bytes),
+                Err(ImportError::InvalidToken(token)) => {
+                    shell.log_error(&format!("couldn't paste: invalid value \"{}\" on the clipboard", token));
+                    None
+                }
+                Err(ImportError::InvalidLine(line)) => {
+                    shell.log_error(&format!("couldn't paste: invalid line {} on the clipboard", line));
+                    None
+                }
+            }
+        }
+        Ok((status, _, stderr, _)) => {
+            let stderr = String::from_utf8_lossy(&stderr).trim().to_string();
+            shell.log_error(&format!("{} failed ({}): {}", command, status, stderr));
+            None
+        }
+        Err(e) => {
+            shell.log_error(&format!("couldn't run {}: {}", command, e));
+            None
+        }
+    }
+}
+
+fn hook_select_register(shell: &mut Shell, args: &[&str]) -> R {
+    if let Some(name) = take_register(shell, args)? {
+        shell.selected_register = Some(name);
+        shell.log_feedback(&format!("\"{}", name));
+    }
+
+    Ok(())
+}
+
+fn hook_registers(shell: &mut Shell, _args: &[&str]) -> R {
+    let editor = shell.get_editor();
+    let mut entries = vec![];
+    for name in std::iter::once('"').chain(editor.get_register_names()) {
+        let bytes = editor.get_register(name).unwrap_or_default();
+        entries.push(format!("\"{} {}", name, preview_bytes(&bytes)));
+    }
+    shell.log_feedback(&entries.join("  "));
+
+    Ok(())
+}
+
+// The register named in the arguments, otherwise the one picked beforehand, if any
+fn take_register(shell: &mut Shell, args: &[&str]) -> Result<Option<char>, SbyteError> {
+    let name = match args.first() {
+        Some(arg) => {
+            let mut chars = arg.chars();
+            match (chars.next(), chars.next()) {
+                (Some(name), None) if name.is_ascii_alphabetic() || name == '-' || name == '"' => name,
+                _ => {
+                    Err(SbyteError::InvalidCommand(format!("no register named {}", arg)))?
+                }
+            }
+        }
+        None => {
+            match shell.selected_register.take() {
+                Some(name) => name,
+                None => {
+                    return Ok(None);
+                }
+            }
+        }
+    };
+
+    Ok(Some(name))
+}
+
+// Removed bytes go in the - register and any register picked, and on the clipboard as yanked bytes do
+fn keep_deleted_bytes(shell: &mut Shell, register: Option<char>, removed_bytes: Vec<u8>) {
+    let editor = shell.get_editor_mut();
+    editor.copy_to_register('-', &removed_bytes);
+    match register {
+        Some(name) => {
+            editor.copy_to_register(name, &removed_bytes);
+            let register_bytes = editor.get_register(name).unwrap_or_default();
+            editor.copy_to_clipboard(register_bytes);
+        }
+        None => {
+            editor.copy_to_clipboard(removed_bytes);
+        }
+    }
+}
+
+// The first few bytes in hex, then as text
+fn preview_bytes(bytes: &[u8]) -> String {
+    let preview_length = 8;
+    let mut hex = vec![];
+    let mut text = String::new();
+    for byte in bytes.iter().take(preview_length) {
+        hex.push(format!("{:02X}", byte));
+        if (0x20 .. 0x7F).contains(byte) {
+            text.push(*byte as char);
+        } else {
+            text.push('.');
+        }
+    }
+
+    let ellipsis = if bytes.len() > preview_length { "..." } else { "" };
+    format!("{}{} |{}{}|", hex.join(" "), ellipsis, text, ellipsis)
+}
@@ -0,0 +1,203 @@
+use crate::clipboard::{self, Encoding};
+use crate::batch;
+use crate::dump;
+use crate::editor::export::{self, ExportFormat, ImportError};
+use crate::editor::formatter::FormatterRef;
+use crate::editor::records::FileFormat;
+use crate::editor::SbyteError;
+use crate::shell::Shell;
+
+#[test]
+fn test_clipboard_encoding() {
+    let bytes = b"\x00\xFFhi!";
+    assert_eq!(clipboard::encode(bytes, Encoding::Raw), bytes.to_vec());
+    assert_eq!(clipboard::encode(bytes, Encoding::Hex), b"00FF686921".to_vec());
+    assert_eq!(clipboard::encode(bytes, Encoding::Base64), b"AP9oaSE=".to_vec());
+
+    for encoding in [Encoding::Raw, Encoding::Hex, Encoding::Base64] {
+        let text = clipboard::encode(bytes, encoding);
+        assert_eq!(clipboard::decode(&text, encoding), Ok(bytes.to_vec()), "{:?} didn't round trip", encoding);
+    }
+    assert_eq!(clipboard::decode(b"", Encoding::Hex), Ok(vec![]));
+}
+
+#[test]
+fn test_clipboard_decoding() {
+    // Spaced out, lowercase and with a newline at the end, as other programs may copy it
+    assert_eq!(clipboard::decode(b"00 ff\n68 69\n", Encoding::Hex), Ok(b"\x00\xFFhi".to_vec()));
+    assert_eq!(clipboard::decode(b"AP9oaQ==\n", Encoding::Base64), Ok(b"\x00\xFFhi".to_vec()));
+
+    assert_eq!(clipboard::decode(b"0G", Encoding::Hex), Err(ImportError::InvalidToken("0G".to_string())));
+    assert_eq!(clipboard::decode(b"ABC", Encoding::Hex), Err(ImportError::InvalidToken("C".to_string())));
+    assert_eq!(clipboard::decode(b"AP9?", Encoding::Base64), Err(ImportError::InvalidToken("?".to_string())));
+}
+
+fn dump_options(args: &[&str]) -> dump::Options {
+    let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+    dump::parse_args(&args).expect("Couldn't parse dump options")
+}
+
+#[test]
+fn test_dump_like_xxd() {
+    // As written by xxd, and xxd -c 8 -g 1
+    let bytes = b"hello\nworld, this is a test of xxd\x00\x01\xff";
+    let expected = "\
+00000000: 6865 6c6c 6f0a 776f 726c 642c 2074 6869  hello.world, thi
+00000010: 7320 6973 2061 2074 6573 7420 6f66 2078  s is a test of x
+00000020: 7864 0001 ff                             xd...
+";
+    let mut output = Vec::new();
+    dump::dump(&mut &bytes[..], &mut output, &dump_options(&[])).expect("Couldn't dump");
+    assert_eq!(String::from_utf8_lossy(&output), expected);
+
+    let mut output = Vec::new();
+    dump::dump(&mut &bytes[.. 6], &mut output, &dump_options(&["--width", "8", "--group", "1"])).expect("Couldn't dump");
+    assert_eq!(String::from_utf8_lossy(&output), "00000000: 68 65 6c 6c 6f 0a        hello.\n");
+
+    // The dump export is the same
+    assert_eq!(export::export(ExportFormat::Xxd, bytes, 0), expected);
+}
+
+#[test]
+fn test_dump_round_trip() {
+    let bytes: Vec<u8> = (0 ..= 255).chain(0 .. 7).collect();
+    let layouts: [&[&str]; 4] = [
+        &[],
+        &["--format", "dec", "--width", "7", "--group", "0"],
+        &["--format", "bin", "--width", "3"],
+        &["--offset-radix", "8", "--width", "32", "--group", "4"]
+    ];
+    for args in layouts.iter() {
+        let options = dump_options(args);
+        let mut text = Vec::new();
+        dump::dump(&mut bytes.as_slice(), &mut text, &options).expect("Couldn't dump");
+        let mut output = Vec::new();
+        assert_eq!(dump::undump(&mut text.as_slice(), &mut output, &options), Ok(()));
+        assert_eq!(output, bytes, "{:?} didn't come back the same", args);
+    }
+
+    // Gaps between rows are filled in, and rows can't go back
+    let mut output = Vec::new();
+    dump::undump(&mut "00000004: 4142\n*\n00000008: 43  C\n".as_bytes(), &mut output, &dump_options(&["-r"])).ok();
+    assert_eq!(output, b"\0\0\0\0AB\0\0C");
+    let mut output = Vec::new();
+    assert_eq!(
+        dump::undump(&mut "00000004: 4142\n00000002: 43\n".as_bytes(), &mut output, &dump_options(&["-r"])),
+        Err("line 2: offset 2 goes back over what's already written".to_string())
+    );
+    assert_eq!(
+        dump::undump(&mut "00000000: 4g".as_bytes(), &mut output, &dump_options(&["-r"])),
+        Err("line 1: invalid byte 4g".to_string())
+    );
+}
+
+fn parse(args: &[&str]) -> Result<crate::Options, String> {
+    let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+    crate::parse_args(&args)
+}
+
+#[test]
+fn test_parse_args() {
+    let options = parse(&["--offset", "\\x10", "--width", "8", "--format", "BIN", "-R", "-c", "w", "-c", "q", "a", "-", "b"])
+        .expect("Couldn't parse options");
+    assert_eq!(options.offset, Some(16));
+    assert_eq!(options.width, Some(8));
+    assert_eq!(options.format, Some(FormatterRef::BIN));
+    assert!(options.readonly);
+    assert_eq!(options.commands, vec!["w", "q"]);
+    assert_eq!(options.paths, vec!["a", "-", "b"]);
+
+    let options = parse(&["--file-format", "ihex", "--base", "\\x8000", "--pad", "0", "--rc", "x", "--no-rc", "--", "-R"])
+        .expect("Couldn't parse options");
+    assert_eq!(options.file_format, Some(FileFormat::IntelHex));
+    assert_eq!(options.base_address, Some(0x8000));
+    assert_eq!(options.pad_byte, Some(0));
+    assert_eq!(options.rc_path, Some("x".to_string()));
+    assert!(options.no_rc);
+    assert!(!options.readonly, "An option was taken after --");
+    assert_eq!(options.paths, vec!["-R"]);
+
+    assert_eq!(parse(&["--width"]).err(), Some("--width needs a value".to_string()));
+    assert_eq!(parse(&["--width", "0"]).err(), Some("--width must be at least 1".to_string()));
+    assert_eq!(parse(&["--offset", "ten"]).err(), Some("--offset needs a number, got ten".to_string()));
+    assert_eq!(parse(&["--pad", "256"]).err(), Some("--pad must be a single byte".to_string()));
+    assert_eq!(parse(&["--format", "oct"]).err(), Some("unknown format: oct (expected hex, dec or bin)".to_string()));
+    assert_eq!(parse(&["--bogus"]).err(), Some("unknown option: --bogus".to_string()));
+}
+
+#[test]
+fn test_rc_path() {
+    // Only an rc file asked for by name has to exist
+    let options = parse(&["--rc", "src/testfiles/does_not_exist"]).expect("Couldn't parse options");
+    assert_eq!(crate::get_rc_path(&options), Err("src/testfiles/does_not_exist: no such rc file".to_string()));
+    let options = parse(&["--rc", "src/testfiles/00"]).expect("Couldn't parse options");
+    assert_eq!(crate::get_rc_path(&options), Ok(Some("src/testfiles/00".to_string())));
+    let options = parse(&["--rc", "src/testfiles/does_not_exist", "--no-rc"]).expect("Couldn't parse options");
+    assert_eq!(crate::get_rc_path(&options), Ok(None));
+}
+
+#[test]
+fn test_incremental_search() {
+    let mut shell = Shell::new();
+    shell.get_editor_mut().open_buffer_from_reader(&mut &b"abcab"[..]).expect("Couldn't open buffer");
+
+    // The matches come in from the worker as they're found
+    shell.buffer_push("find ab");
+    shell.update_incremental_search();
+    while shell.get_search_status().is_some_and(|status| status.contains('%')) {
+        std::thread::sleep(std::time::Duration::from_millis(1));
+        shell.poll_incremental_search();
+    }
+    assert_eq!(shell.get_search_status(), Some("match 2 of 2".to_string()));
+    assert_eq!(shell.get_editor().get_cursor_offset(), 3);
+    assert_eq!(shell.get_editor().get_search_mask(0, 5), vec![true, true, false, true, true]);
+
+    // Leaving the line puts the cursor back
+    shell.buffer_clear();
+    assert_eq!(shell.get_search_status(), None);
+    assert_eq!(shell.get_editor().get_cursor_offset(), 0);
+    assert_eq!(shell.get_editor().get_search_mask(0, 5), vec![]);
+}
+
+#[test]
+fn test_save_quit_keeps_unsaved_changes() {
+    let path = std::env::temp_dir().join("sbyte_test_save_quit");
+    let path = path.to_str().unwrap();
+    std::fs::write(path, "abcdef").expect("Couldn't write test file");
+
+    let mut shell = Shell::new();
+    shell.get_editor_mut().open_buffer(path).expect("Couldn't open file");
+    shell.get_editor_mut().insert_bytes(0, b"Z").ok();
+    std::fs::write(path, "changed on disk").expect("Couldn't write test file");
+
+    // Nothing is written, so nothing quits
+    assert!(shell.run_line("wq").is_ok());
+    assert!(shell.fetch_error().is_some());
+    assert!(shell.run_line(&format!("w {}", path)).is_ok());
+    assert!(shell.fetch_error().is_some());
+    assert!(shell.run_line("wq !false").is_ok());
+    assert!(shell.fetch_error().is_some());
+    // Forced, but the bytes still to be read from the file aren't the ones loaded
+    assert!(shell.run_line("wq!").is_ok());
+    assert!(shell.fetch_error().is_some());
+    assert_eq!(std::fs::read_to_string(path).unwrap(), "changed on disk");
+
+
+    // Once it's saved, it's safe to go
+    std::fs::write(path, "abcdef").expect("Couldn't write test file");
+    let mut shell = Shell::new();
+    shell.get_editor_mut().open_buffer(path).expect("Couldn't open file");
+    shell.get_editor_mut().insert_bytes(0, b"Z").ok();
+    assert_eq!(shell.run_line("wq").err(), Some(SbyteError::KillSignal));
+    assert_eq!(std::fs::read_to_string(path).unwrap(), "Zabcdef");
+
+    std::fs::remove_file(path).ok();
+}
+
+#[test]
+fn test_batch_line_results() {
+    let mut shell = Shell::new();
+    assert_eq!(batch::run_line(&mut shell, "  "), Ok(true));
+    assert_eq!(batch::run_line(&mut shell, "wq"), Err("failed to save: no path set".to_string()));
+    assert_eq!(batch::run_line(&mut shell, "q"), Ok(false));
+}